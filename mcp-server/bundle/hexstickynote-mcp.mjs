@@ -23608,8 +23608,9 @@ function parseMarkdownWithFrontmatter(fileContent) {
   const metadata = jsYaml.load(yamlStr);
   return { metadata, content: markdownContent };
 }
-function createMarkdownWithFrontmatter(card) {
+function createMarkdownWithFrontmatter(card, existing = {}) {
   const metadata = {
+    ...existing,
     id: card.id,
     created_at: card.created_at,
     updated_at: card.updated_at
@@ -23699,7 +23700,7 @@ async function updateCard(id, content) {
   const sanitized = sanitizeFilename(title);
   const filename = await getUniqueFilename(dir, sanitized);
   const newPath = path2.join(dir, filename);
-  await fs.writeFile(newPath, createMarkdownWithFrontmatter(updated), "utf-8");
+  await fs.writeFile(newPath, createMarkdownWithFrontmatter(updated, metadata), "utf-8");
   if (oldPath !== newPath) {
     try {
       await fs.unlink(oldPath);
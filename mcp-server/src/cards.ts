@@ -9,6 +9,12 @@ import { getCardsDirectory } from "./paths.js";
 // Helper Functions
 // ============================================================================
 
+// First `maxChars` characters of a text; counts code points so emoji aren't split
+export function truncateChars(text: string, maxChars: number): string {
+  const chars = Array.from(text);
+  return chars.length > maxChars ? chars.slice(0, maxChars).join("") : text;
+}
+
 // Extract title from markdown content (first # heading)
 function extractTitleFromContent(content: string): string {
   for (const line of content.split('\n')) {
@@ -44,9 +50,7 @@ function sanitizeFilename(title: string): string {
   sanitized = sanitized.trim().replace(/\.+$/, '');
 
   // Limit length to 100 characters
-  if (sanitized.length > 100) {
-    sanitized = sanitized.substring(0, 100).trim();
-  }
+  sanitized = truncateChars(sanitized, 100).trim();
 
   // Ensure not empty
   if (!sanitized) {
@@ -81,8 +85,12 @@ async function getUniqueFilename(cardsDir: string, baseName: string): Promise<st
   }
 }
 
-// Find existing file by ID in front matter
-async function findFileByID(cardsDir: string, id: string): Promise<string | null> {
+// Find existing file by ID in front matter or the index
+async function findFileByID(
+  cardsDir: string,
+  id: string,
+  index: CardIndex | null
+): Promise<string | null> {
   try {
     const entries = await fs.readdir(cardsDir);
     for (const entry of entries) {
@@ -90,7 +98,7 @@ async function findFileByID(cardsDir: string, id: string): Promise<string | null
       try {
         const filePath = path.join(cardsDir, entry);
         const content = await fs.readFile(filePath, 'utf-8');
-        const { metadata } = parseMarkdownWithFrontmatter(content);
+        const { metadata } = parseCardFile(content, index?.[entry]);
         if (metadata.id === id) {
           return filePath;
         }
@@ -104,6 +112,69 @@ async function findFileByID(cardsDir: string, id: string): Promise<string | null
   return null;
 }
 
+// Sidecar file holding the metadata of cards stored without front matter, keyed by
+// file name (see card_manager.rs). The app removes it when no card uses it, so its
+// presence means the app is set to store cards without front matter.
+const CARD_INDEX_FILE = ".index.json";
+
+// Index entries as the app writes them (timestamps as RFC3339 strings)
+type CardIndex = Record<string, Record<string, unknown>>;
+
+// Read the sidecar index; null when there is none
+async function readCardIndex(cardsDir: string): Promise<CardIndex | null> {
+  let json: string;
+  try {
+    json = await fs.readFile(path.join(cardsDir, CARD_INDEX_FILE), "utf-8");
+  } catch {
+    return null;
+  }
+  try {
+    return JSON.parse(json) as CardIndex;
+  } catch (err) {
+    console.error(`Ignoring unreadable card index: ${err}`);
+    return {};
+  }
+}
+
+// Write the sidecar index through a temp file, removing it when it's empty
+async function writeCardIndex(cardsDir: string, index: CardIndex): Promise<void> {
+  const indexPath = path.join(cardsDir, CARD_INDEX_FILE);
+  if (Object.keys(index).length === 0) {
+    await fs.rm(indexPath, { force: true });
+    return;
+  }
+  const tempPath = `${indexPath}.tmp`;
+  await fs.writeFile(tempPath, JSON.stringify(index, null, 2), "utf-8");
+  await fs.rename(tempPath, indexPath);
+}
+
+// Metadata of a card as an index entry, keeping fields managed by the app
+function indexEntry(card: Card, existing: Record<string, unknown> = { pinned: false }) {
+  return {
+    ...existing,
+    id: card.id,
+    created_at: toTimestamp(card.created_at),
+    updated_at: toTimestamp(card.updated_at),
+  };
+}
+
+// Front matter timestamps are RFC3339 strings (js-yaml may already have turned
+// them into Dates); cards written by older versions use epoch seconds
+function toEpochSeconds(value: unknown): number {
+  if (typeof value === "number") return value;
+  const date = value instanceof Date ? value : new Date(String(value));
+  const ms = date.getTime();
+  if (Number.isNaN(ms)) {
+    throw new Error(`Invalid timestamp: ${value}`);
+  }
+  return Math.floor(ms / 1000);
+}
+
+// Format epoch seconds the way the app writes them, e.g. "2024-05-01T12:00:00Z"
+function toTimestamp(seconds: number): string {
+  return new Date(seconds * 1000).toISOString().replace(/\.\d{3}Z$/, "Z");
+}
+
 function parseMarkdownWithFrontmatter(fileContent: string): {
   metadata: CardMetadata;
   content: string;
@@ -122,16 +193,55 @@ function parseMarkdownWithFrontmatter(fileContent: string): {
   const yamlStr = rest.slice(0, endPos);
   const markdownContent = rest.slice(endPos + 5);
 
-  const metadata = yaml.load(yamlStr) as CardMetadata;
+  const raw = yaml.load(yamlStr) as Record<string, unknown>;
+  const metadata = {
+    ...raw,
+    created_at: toEpochSeconds(raw.created_at),
+    updated_at: toEpochSeconds(raw.updated_at),
+  } as CardMetadata;
 
   return { metadata, content: markdownContent };
 }
 
-function createMarkdownWithFrontmatter(card: Card): string {
-  const metadata: CardMetadata = {
+// Parse a card file, taking the metadata from its index entry when it has no
+// front matter; front matter wins over a (stale) index entry, as in the app
+function parseCardFile(
+  fileContent: string,
+  indexed?: Record<string, unknown>
+): { metadata: CardMetadata; content: string } {
+  try {
+    return parseMarkdownWithFrontmatter(fileContent);
+  } catch (err) {
+    if (!indexed) throw err;
+    const metadata = {
+      ...indexed,
+      created_at: toEpochSeconds(indexed.created_at),
+      updated_at: toEpochSeconds(indexed.updated_at),
+    } as CardMetadata;
+    return { metadata, content: fileContent };
+  }
+}
+
+function hasCardFrontmatter(fileContent: string): boolean {
+  try {
+    parseMarkdownWithFrontmatter(fileContent);
+    return true;
+  } catch {
+    return false;
+  }
+}
+
+// Existing front matter fields (e.g. pinned) are carried over so edits made
+// through MCP don't drop metadata managed by the app
+function createMarkdownWithFrontmatter(
+  card: Card,
+  existing: Partial<CardMetadata> = {}
+): string {
+  const metadata = {
+    ...existing,
     id: card.id,
-    created_at: card.created_at,
-    updated_at: card.updated_at,
+    created_at: toTimestamp(card.created_at),
+    updated_at: toTimestamp(card.updated_at),
   };
 
   const yamlStr = yaml.dump(metadata, {
@@ -170,7 +280,16 @@ export async function createCard(content: string): Promise<Card> {
   const filename = await getUniqueFilename(dir, sanitized);
   const filePath = path.join(dir, filename);
 
-  await fs.writeFile(filePath, createMarkdownWithFrontmatter(card), "utf-8");
+  // Follow the app's layout; the index entry goes first so the file is never
+  // there without its metadata
+  const index = await readCardIndex(dir);
+  if (index) {
+    index[filename] = indexEntry(card);
+    await writeCardIndex(dir, index);
+    await fs.writeFile(filePath, content, "utf-8");
+  } else {
+    await fs.writeFile(filePath, createMarkdownWithFrontmatter(card), "utf-8");
+  }
 
   return card;
 }
@@ -178,6 +297,7 @@ export async function createCard(content: string): Promise<Card> {
 export async function listCards(): Promise<Card[]> {
   const dir = await ensureCardsDirectory();
   const entries = await fs.readdir(dir);
+  const index = await readCardIndex(dir);
   const cards: Card[] = [];
 
   for (const entry of entries) {
@@ -185,7 +305,9 @@ export async function listCards(): Promise<Card[]> {
     try {
       const filePath = path.join(dir, entry);
       const fileContent = await fs.readFile(filePath, "utf-8");
-      const { metadata, content } = parseMarkdownWithFrontmatter(fileContent);
+      const { metadata, content } = parseCardFile(fileContent, index?.[entry]);
+      // Archived cards are hidden from the list, as in the app
+      if (metadata.archived) continue;
       cards.push({
         id: metadata.id,
         content,
@@ -203,14 +325,15 @@ export async function listCards(): Promise<Card[]> {
 export async function readCard(id: string): Promise<Card> {
   const dir = await ensureCardsDirectory();
 
-  // Find file by ID in front matter
-  const filePath = await findFileByID(dir, id);
+  // Find file by ID in front matter or the index
+  const index = await readCardIndex(dir);
+  const filePath = await findFileByID(dir, id, index);
   if (!filePath) {
     throw new Error(`Card with ID ${id} not found`);
   }
 
   const fileContent = await fs.readFile(filePath, "utf-8");
-  const { metadata, content } = parseMarkdownWithFrontmatter(fileContent);
+  const { metadata, content } = parseCardFile(fileContent, index?.[path.basename(filePath)]);
   return {
     id: metadata.id,
     content,
@@ -223,13 +346,19 @@ export async function updateCard(id: string, content: string): Promise<Card> {
   const dir = await ensureCardsDirectory();
 
   // Find old file path
-  const oldPath = await findFileByID(dir, id);
+  const index = await readCardIndex(dir);
+  const oldPath = await findFileByID(dir, id, index);
   if (!oldPath) {
     throw new Error(`Card with ID ${id} not found`);
   }
 
+  const oldName = path.basename(oldPath);
   const fileContent = await fs.readFile(oldPath, "utf-8");
-  const { metadata } = parseMarkdownWithFrontmatter(fileContent);
+  const indexed = hasCardFrontmatter(fileContent) ? undefined : index?.[oldName];
+  const { metadata } = parseCardFile(fileContent, indexed);
+  if (metadata.locked) {
+    throw new Error(`Card ${id} is locked`);
+  }
 
   const updated: Card = {
     id: metadata.id,
@@ -244,8 +373,14 @@ export async function updateCard(id: string, content: string): Promise<Card> {
   const filename = await getUniqueFilename(dir, sanitized);
   const newPath = path.join(dir, filename);
 
-  // Write to new file
-  await fs.writeFile(newPath, createMarkdownWithFrontmatter(updated), "utf-8");
+  // Write to new file, keeping the card in the layout it's stored in
+  if (index && indexed) {
+    index[filename] = indexEntry(updated, indexed);
+    await writeCardIndex(dir, index);
+    await fs.writeFile(newPath, content, "utf-8");
+  } else {
+    await fs.writeFile(newPath, createMarkdownWithFrontmatter(updated, metadata), "utf-8");
+  }
 
   // If filename changed, delete old file
   if (oldPath !== newPath) {
@@ -254,6 +389,10 @@ export async function updateCard(id: string, content: string): Promise<Card> {
     } catch {
       // Ignore error if old file doesn't exist
     }
+    if (index && indexed) {
+      delete index[oldName];
+      await writeCardIndex(dir, index);
+    }
   }
 
   return updated;
@@ -263,10 +402,21 @@ export async function deleteCard(id: string): Promise<void> {
   const dir = await ensureCardsDirectory();
 
   // Find file by ID
-  const filePath = await findFileByID(dir, id);
+  const index = await readCardIndex(dir);
+  const filePath = await findFileByID(dir, id, index);
   if (!filePath) {
     throw new Error(`Card with ID ${id} not found`);
   }
 
+  const name = path.basename(filePath);
+  const { metadata } = parseCardFile(await fs.readFile(filePath, "utf-8"), index?.[name]);
+  if (metadata.locked) {
+    throw new Error(`Card ${id} is locked`);
+  }
+
   await fs.unlink(filePath);
+  if (index && name in index) {
+    delete index[name];
+    await writeCardIndex(dir, index);
+  }
 }
@@ -7,6 +7,7 @@ import {
   readCard,
   updateCard,
   deleteCard,
+  truncateChars,
 } from "./cards.js";
 
 const server = new McpServer({
@@ -22,7 +23,7 @@ server.tool(
   },
   async ({ content }) => {
     try {
-      console.error(`[DEBUG] Creating card with content: ${content.substring(0, 50)}...`);
+      console.error(`[DEBUG] Creating card with content: ${truncateChars(content, 50)}...`);
       const card = await createCard(content);
       console.error(`[DEBUG] Card created successfully: ${card.id}`);
       return {
@@ -49,8 +50,8 @@ server.tool(
       const previews = cards.map((card) => ({
         id: card.id,
         preview:
-          card.content.slice(0, 200) +
-          (card.content.length > 200 ? "..." : ""),
+          truncateChars(card.content, 200) +
+          (Array.from(card.content).length > 200 ? "..." : ""),
         created_at: new Date(card.created_at * 1000).toISOString(),
         updated_at: new Date(card.updated_at * 1000).toISOString(),
       }));
@@ -9,4 +9,9 @@ export interface CardMetadata {
   id: string;
   created_at: number;
   updated_at: number;
+  pinned?: boolean;
+  color?: string;
+  locked?: boolean;
+  archived?: boolean;
+  tags?: string[];
 }
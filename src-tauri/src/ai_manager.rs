@@ -1,21 +1,71 @@
 //! AI Manager - Routes prompts to different AI providers
 //!
-//! Supports streaming responses from OpenAI, Anthropic, Google Gemini, and local models.
+//! Supports streaming responses from OpenAI, Anthropic, Google Gemini, Groq, xAI Grok, Mistral, Cohere, and local models.
 
+use crate::ai_providers::{self, PendingToolCall, Provider, ProviderRequest, RequestParams};
 use crate::ai_tools;
+use crate::card_manager::{self, Card};
+use crate::conversation_log::{self, ConversationLogEntry};
 use crate::keyring_store::{AiProvider, KeyringStore};
 use crate::settings_manager::SettingsManager;
 use crate::{local_inference, local_model};
 use directories::ProjectDirs;
 use futures::StreamExt;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter};
 use thiserror::Error;
-use tokio::sync::Mutex;
+use tokio::sync::{oneshot, Mutex};
+
+/// Timeout for provider connectivity probes
+const CONNECTIVITY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a fetched provider model list is reused before asking the provider again
+const MODEL_LIST_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Default system prompt for cloud providers when the response replaces the note
+const REPLACE_SYSTEM_PROMPT: &str = "You are a text editor for a sticky note application. The user's current note is given as context.
+If the user asks to create, update, or delete a note, use the provided tools (`create_note`, `update_note`, `delete_note`; use `get_note` to read a note before updating it) and reply with at most a brief confirmation.
+Otherwise, update the current note based on the user request: output ONLY the full updated note content in Markdown. Do not output conversational text.";
+
+/// Default system prompt for cloud providers when the response is inserted into the note
+const APPEND_SYSTEM_PROMPT: &str = "You are a writing assistant for a sticky note application. The user's current note is given as context.
+If the user asks to create, update, or delete a note, use the provided tools (`create_note`, `update_note`, `delete_note`; use `get_note` to read a note before updating it) and reply with at most a brief confirmation.
+Otherwise, write the text the user asked for: output ONLY the new text to insert into the note, in Markdown. Do not repeat the existing content and do not output conversational text.";
+
+/// Character budget for the notes digest sent to cloud providers
+const SUMMARY_CONTEXT_CHARS: usize = 24_000;
+
+/// Character budget for local models (2048 token context, leaving room for the answer)
+const LOCAL_SUMMARY_CONTEXT_CHARS: usize = 4_000;
+
+/// Character budget for related notes added to a prompt's context (cloud / local)
+const RELATED_CONTEXT_CHARS: usize = 8_000;
+const LOCAL_RELATED_CONTEXT_CHARS: usize = 1_500;
+
+const SUMMARY_SYSTEM_PROMPT: &str = "You summarize a user's sticky notes. Write a concise Markdown digest grouped by topic, starting with a single '# ' heading. Output only the digest.";
+
+const CONTINUE_SYSTEM_PROMPT: &str = "You continue text that was cut off. The note given as context ends where the previous answer stopped.
+Output ONLY the text that comes next, starting exactly where the note ends. Do not repeat any of the existing content and do not output conversational text.";
+
+/// Prompt and token cap for the local model self-test
+const LOCAL_MODEL_TEST_PROMPT: &str = "Say hello in one short sentence.";
+const LOCAL_MODEL_TEST_MAX_TOKENS: usize = 24;
+
+/// Prompt and token cap for the local inference benchmark
+const LOCAL_BENCHMARK_PROMPT: &str = "Count from one to twenty in words, separated by commas.";
+const LOCAL_BENCHMARK_MAX_TOKENS: usize = 64;
+
+/// How long to wait for the user to confirm a tool call before rejecting it
+const TOOL_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Latency samples kept per provider; older ones are dropped
+const MAX_LATENCY_SAMPLES: usize = 500;
 
 #[derive(Debug, Error)]
 pub enum AiError {
@@ -33,6 +83,10 @@ pub enum AiError {
     LocalModelError(#[from] local_model::LocalModelError),
     #[error("Local inference error: {0}")]
     LocalInferenceError(#[from] local_inference::LocalInferenceError),
+    #[error("A local generation is already running")]
+    Busy,
+    #[error("Generation stopped: {0}")]
+    Aborted(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,16 +96,672 @@ pub struct AiStreamChunk {
     pub gpu_info: Option<String>,
 }
 
+/// How the frontend applies a streamed response to the card
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamMode {
+    /// Insert the generated text at the cursor
+    Append,
+    /// Replace the card body with the generated text
+    #[default]
+    ReplaceContent,
+}
+
+impl StreamMode {
+    /// Default cloud system prompt for this mode
+    fn default_system_prompt(self) -> &'static str {
+        match self {
+            StreamMode::Append => APPEND_SYSTEM_PROMPT,
+            StreamMode::ReplaceContent => REPLACE_SYSTEM_PROMPT,
+        }
+    }
+}
+
+/// Emitted as 'ai-stream-start' before the first chunk of a response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiStreamStart {
+    pub mode: StreamMode,
+    /// The card the response is written into, for `invoke_ai_to_new_card`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_id: Option<String>,
+}
+
+/// Per-request options for a streamed generation
+#[derive(Debug, Clone, Default)]
+pub struct StreamOptions {
+    /// Don't offer the note tools to the model (plain text generation)
+    pub disable_tools: bool,
+    /// System prompt for this request only (overrides the configured one)
+    pub system_prompt: Option<String>,
+    /// Whether the response is inserted into or replaces the card content
+    pub mode: StreamMode,
+    /// Cap on generated tokens for local models (None = built-in limit)
+    pub max_tokens: Option<usize>,
+}
+
+/// Destination for streamed response chunks
+///
+/// The app streams to the frontend through `TauriChunkSink`; tests can plug in
+/// a sink that collects the chunks instead.
+pub trait ChunkSink: Send + Sync {
+    fn send(&self, chunk: AiStreamChunk);
+
+    /// Send a piece of generated text
+    fn send_text(&self, text: &str) {
+        self.send(AiStreamChunk {
+            chunk: text.to_string(),
+            done: false,
+            gpu_info: None,
+        });
+    }
+
+    /// Signal that the response is complete
+    fn send_done(&self) {
+        self.send(AiStreamChunk {
+            chunk: String::new(),
+            done: true,
+            gpu_info: None,
+        });
+    }
+
+    /// Send a piece of the model's reasoning, separate from the answer; ignored by default
+    fn send_reasoning(&self, _text: &str) {}
+
+    /// Announce a response and how it applies to the card; ignored by default
+    fn send_start(&self, _mode: StreamMode) {}
+
+    /// Report a local inference phase (e.g. "loading-model"); ignored by default
+    fn send_state(&self, _state: &str) {}
+
+    /// Report an error the provider sent mid-stream; ignored by default
+    fn send_error(&self, _message: &str) {}
+
+    /// Report that the response was cut off by the token limit; ignored by default
+    fn send_truncated(&self) {}
+
+    /// The generation is (or stopped) waiting for the user, e.g. for a tool call
+    /// confirmation; ignored by default
+    fn set_waiting_for_user(&self, _waiting: bool) {}
+
+    /// Why the generation should stop (e.g. the response can't be saved), checked
+    /// after each piece of text; None by default
+    fn failure(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Drops everything; used with `CapturingSink` when nothing should reach the frontend
+pub struct DiscardSink;
+
+impl ChunkSink for DiscardSink {
+    fn send(&self, _chunk: AiStreamChunk) {}
+}
+
+/// App-wide events a generation emits besides its chunks: tool confirmations, usage
+/// and refresh requests
+///
+/// The app passes its `AppHandle`; tests can pass `NoEvents` to drive a sink without Tauri.
+pub trait AppEvents: Send + Sync {
+    fn emit_event(&self, event: &str, payload: serde_json::Value);
+}
+
+impl AppEvents for AppHandle {
+    fn emit_event(&self, event: &str, payload: serde_json::Value) {
+        self.emit(event, payload).ok();
+    }
+}
+
+impl dyn AppEvents + '_ {
+    /// Serialize the payload and emit it
+    fn emit_json<S: Serialize>(&self, event: &str, payload: S) {
+        match serde_json::to_value(payload) {
+            Ok(payload) => self.emit_event(event, payload),
+            Err(e) => log::warn!("Failed to serialize '{}' event: {}", event, e),
+        }
+    }
+}
+
+/// Drops every app event
+pub struct NoEvents;
+
+impl AppEvents for NoEvents {
+    fn emit_event(&self, _event: &str, _payload: serde_json::Value) {}
+}
+
+/// Forwards chunks to another sink while keeping the generated text
+struct CapturingSink<'a> {
+    inner: &'a dyn ChunkSink,
+    text: std::sync::Mutex<String>,
+}
+
+impl ChunkSink for CapturingSink<'_> {
+    fn send(&self, chunk: AiStreamChunk) {
+        if let Ok(mut text) = self.text.lock() {
+            text.push_str(&chunk.chunk);
+        }
+        self.inner.send(chunk);
+    }
+
+    fn send_reasoning(&self, text: &str) {
+        self.inner.send_reasoning(text);
+    }
+
+    fn send_start(&self, mode: StreamMode) {
+        self.inner.send_start(mode);
+    }
+
+    fn send_state(&self, state: &str) {
+        self.inner.send_state(state);
+    }
+
+    fn send_error(&self, message: &str) {
+        self.inner.send_error(message);
+    }
+
+    fn send_truncated(&self) {
+        self.inner.send_truncated();
+    }
+
+    fn set_waiting_for_user(&self, waiting: bool) {
+        self.inner.set_waiting_for_user(waiting);
+    }
+
+    fn failure(&self) -> Option<String> {
+        self.inner.failure()
+    }
+}
+
+/// Time a generation spent waiting, left out of its timings
+#[derive(Debug, Default)]
+struct Pauses {
+    /// Start of the current pause
+    since: Option<Instant>,
+    /// Length of the pauses that ended
+    total: Duration,
+}
+
+/// Forwards chunks to another sink while timing the first piece of text
+/// Loading a local model and waiting for tool call confirmations don't count.
+struct TimingSink<'a> {
+    inner: &'a dyn ChunkSink,
+    started: Instant,
+    first_chunk: std::sync::OnceLock<Duration>,
+    pauses: std::sync::Mutex<Pauses>,
+}
+
+impl<'a> TimingSink<'a> {
+    fn new(inner: &'a dyn ChunkSink) -> Self {
+        Self {
+            inner,
+            started: Instant::now(),
+            first_chunk: std::sync::OnceLock::new(),
+            pauses: std::sync::Mutex::new(Pauses::default()),
+        }
+    }
+
+    fn pause(&self, now: Instant) {
+        if let Ok(mut pauses) = self.pauses.lock() {
+            pauses.since.get_or_insert(now);
+        }
+    }
+
+    fn resume(&self, now: Instant) {
+        if let Ok(mut pauses) = self.pauses.lock() {
+            if let Some(since) = pauses.since.take() {
+                pauses.total += now.saturating_duration_since(since);
+            }
+        }
+    }
+
+    /// Time since the start, minus the pauses
+    fn active_time(&self, now: Instant) -> Duration {
+        let paused = self
+            .pauses
+            .lock()
+            .map(|pauses| {
+                pauses.total + pauses.since.map_or(Duration::ZERO, |since| now.saturating_duration_since(since))
+            })
+            .unwrap_or_default();
+        now.saturating_duration_since(self.started).saturating_sub(paused)
+    }
+
+    /// Timings of the generation so far
+    fn sample(&self) -> LatencySample {
+        LatencySample {
+            first_chunk: self.first_chunk.get().copied(),
+            total: self.active_time(Instant::now()),
+        }
+    }
+}
+
+impl ChunkSink for TimingSink<'_> {
+    fn send(&self, chunk: AiStreamChunk) {
+        if !chunk.done && !chunk.chunk.is_empty() {
+            // Only the first call sets it
+            self.first_chunk.set(self.active_time(Instant::now())).ok();
+        }
+        self.inner.send(chunk);
+    }
+
+    fn send_reasoning(&self, text: &str) {
+        self.inner.send_reasoning(text);
+    }
+
+    fn send_start(&self, mode: StreamMode) {
+        self.inner.send_start(mode);
+    }
+
+    fn send_state(&self, state: &str) {
+        // Loading the model isn't part of the response time
+        if state == local_inference::STATE_LOADING_MODEL {
+            self.pause(Instant::now());
+        } else {
+            self.resume(Instant::now());
+        }
+        self.inner.send_state(state);
+    }
+
+    fn send_error(&self, message: &str) {
+        self.inner.send_error(message);
+    }
+
+    fn send_truncated(&self) {
+        self.inner.send_truncated();
+    }
+
+    fn set_waiting_for_user(&self, waiting: bool) {
+        if waiting {
+            self.pause(Instant::now());
+        } else {
+            self.resume(Instant::now());
+        }
+        self.inner.set_waiting_for_user(waiting);
+    }
+
+    fn failure(&self) -> Option<String> {
+        self.inner.failure()
+    }
+}
+
+/// Emits chunks as 'ai-stream-chunk' events to the frontend
+/// Events go to every window unless a target window label is set. A sink for a card
+/// emits 'ai-card-stream-chunk' instead, so windows streaming into their own card
+/// don't pick up its text.
+pub struct TauriChunkSink {
+    app: AppHandle,
+    target: Option<String>,
+    card_id: Option<String>,
+}
+
+impl TauriChunkSink {
+    pub fn new(app: &AppHandle) -> Self {
+        Self {
+            app: app.clone(),
+            target: None,
+            card_id: None,
+        }
+    }
+
+    /// Send events only to the window with the given label (e.g. "main" or "orb")
+    pub fn for_window(app: &AppHandle, label: &str) -> Self {
+        Self {
+            app: app.clone(),
+            target: Some(label.to_string()),
+            card_id: None,
+        }
+    }
+
+    /// Send the text of a response written into a card, tagged with the card id
+    pub fn for_card(app: &AppHandle, card_id: &str) -> Self {
+        Self {
+            app: app.clone(),
+            target: None,
+            card_id: Some(card_id.to_string()),
+        }
+    }
+
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        let result = match &self.target {
+            Some(label) => self.app.emit_to(label.as_str(), event, payload),
+            None => self.app.emit(event, payload),
+        };
+        result.ok();
+    }
+}
+
+impl ChunkSink for TauriChunkSink {
+    fn send(&self, chunk: AiStreamChunk) {
+        match &self.card_id {
+            Some(card_id) => self.emit("ai-card-stream-chunk", AiCardStreamChunk {
+                card_id: card_id.clone(),
+                chunk: chunk.chunk,
+                done: chunk.done,
+            }),
+            None => self.emit("ai-stream-chunk", chunk),
+        }
+    }
+
+    fn send_reasoning(&self, text: &str) {
+        if self.card_id.is_none() {
+            self.emit("ai-reasoning-chunk", AiReasoningChunk {
+                chunk: text.to_string(),
+            });
+        }
+    }
+
+    fn send_start(&self, mode: StreamMode) {
+        self.emit("ai-stream-start", AiStreamStart {
+            mode,
+            card_id: self.card_id.clone(),
+        });
+    }
+
+    fn send_state(&self, state: &str) {
+        self.emit("local-inference-state", state);
+    }
+
+    fn send_error(&self, message: &str) {
+        self.emit("ai-stream-error", AiStreamError {
+            message: message.to_string(),
+            card_id: self.card_id.clone(),
+        });
+    }
+
+    fn send_truncated(&self) {
+        if self.card_id.is_none() {
+            self.emit("ai-stream-truncated", ());
+        }
+    }
+}
+
+/// Emitted as 'ai-reasoning-chunk' for reasoning text, which stays out of the card
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiReasoningChunk {
+    pub chunk: String,
+}
+
+/// Emitted as 'ai-card-stream-chunk' for text the backend appends to a card itself
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiCardStreamChunk {
+    pub card_id: String,
+    pub chunk: String,
+    pub done: bool,
+}
+
+/// Appends text to a card; `card_manager::append_to_card` outside of tests
+type CardAppender = dyn Fn(&str, &str) -> Result<(), String> + Send + Sync;
+
+/// Appends streamed text to a card while forwarding everything to another sink
+/// The card is saved through the usual debounce, so the text is kept even when no
+/// window is listening. When an append fails, no further text is forwarded and
+/// `failure` stops the generation.
+struct CardSink {
+    inner: Box<dyn ChunkSink>,
+    card_id: String,
+    append: Box<CardAppender>,
+    failure: std::sync::Mutex<Option<String>>,
+    error_sent: std::sync::atomic::AtomicBool,
+}
+
+impl CardSink {
+    fn new(inner: Box<dyn ChunkSink>, card_id: &str) -> Self {
+        Self::with_appender(inner, card_id, Box::new(card_manager::append_to_card))
+    }
+
+    fn with_appender(inner: Box<dyn ChunkSink>, card_id: &str, append: Box<CardAppender>) -> Self {
+        Self {
+            inner,
+            card_id: card_id.to_string(),
+            append,
+            failure: std::sync::Mutex::new(None),
+            error_sent: std::sync::atomic::AtomicBool::new(false),
+        }
+    }
+
+    /// Whether an error was already reported through this sink
+    fn error_sent(&self) -> bool {
+        self.error_sent.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
+
+impl ChunkSink for CardSink {
+    fn send(&self, chunk: AiStreamChunk) {
+        if self.failure().is_some() {
+            return;
+        }
+        if !chunk.chunk.is_empty() {
+            if let Err(e) = (self.append)(&self.card_id, &chunk.chunk) {
+                log::warn!("Failed to append response to card {}: {}", self.card_id, e);
+                if let Ok(mut failure) = self.failure.lock() {
+                    *failure = Some(e);
+                }
+                return;
+            }
+        }
+        self.inner.send(chunk);
+    }
+
+    fn send_reasoning(&self, text: &str) {
+        self.inner.send_reasoning(text);
+    }
+
+    fn send_start(&self, mode: StreamMode) {
+        self.inner.send_start(mode);
+    }
+
+    fn send_state(&self, state: &str) {
+        self.inner.send_state(state);
+    }
+
+    fn send_error(&self, message: &str) {
+        self.error_sent.store(true, std::sync::atomic::Ordering::SeqCst);
+        self.inner.send_error(message);
+    }
+
+    fn send_truncated(&self) {
+        self.inner.send_truncated();
+    }
+
+    fn set_waiting_for_user(&self, waiting: bool) {
+        self.inner.set_waiting_for_user(waiting);
+    }
+
+    fn failure(&self) -> Option<String> {
+        self.failure.lock().ok().and_then(|failure| failure.clone())
+    }
+}
+
+/// Emitted as 'ai-stream-error' when a provider reports an error mid-stream
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AiStreamError {
-    pub code: String,
     pub message: String,
+    /// The card the failed response was written into, for `invoke_ai_to_new_card`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub card_id: Option<String>,
+}
+
+/// Emitted as 'tool-call-pending' when a tool call awaits user confirmation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallPending {
+    pub call_id: String,
+    pub name: String,
+    pub arguments: String,
+    pub preview: String,
+}
+
+/// Token usage reported by a provider for a single generation
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// Emitted as 'ai-usage' after a cloud generation finishes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiUsageEvent {
+    pub provider: String,
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// Running token totals for a provider since app start
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageStats {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// Timings of one successful generation
+#[derive(Debug, Clone, Copy)]
+struct LatencySample {
+    /// Until the first non-empty chunk; None if no text was generated
+    first_chunk: Option<Duration>,
+    total: Duration,
+}
+
+/// Min, average and 95th percentile of a set of durations, in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub min_ms: u64,
+    pub avg_ms: u64,
+    pub p95_ms: u64,
+}
+
+impl LatencySummary {
+    /// Summarize durations; None when there are none
+    fn from_durations(durations: impl IntoIterator<Item = Duration>) -> Option<Self> {
+        let mut millis: Vec<u64> = durations.into_iter().map(|d| d.as_millis() as u64).collect();
+        if millis.is_empty() {
+            return None;
+        }
+        millis.sort_unstable();
+
+        // Nearest-rank percentile
+        let p95_rank = (millis.len() * 95).div_ceil(100);
+        Some(Self {
+            min_ms: millis[0],
+            avg_ms: millis.iter().sum::<u64>() / millis.len() as u64,
+            p95_ms: millis[p95_rank - 1],
+        })
+    }
+}
+
+/// Response times of a provider since app start, from `get_provider_latency_stats`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderLatencyStats {
+    pub requests: usize,
+    /// Time until the first piece of text arrived
+    pub first_chunk: Option<LatencySummary>,
+    /// Time until the response was complete
+    pub total: Option<LatencySummary>,
+}
+
+impl ProviderLatencyStats {
+    fn from_samples(samples: &[LatencySample]) -> Self {
+        Self {
+            requests: samples.len(),
+            first_chunk: LatencySummary::from_durations(samples.iter().filter_map(|s| s.first_chunk)),
+            total: LatencySummary::from_durations(samples.iter().map(|s| s.total)),
+        }
+    }
+}
+
+/// Result of a provider connectivity check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderConnectivity {
+    pub provider: String,
+    pub reachable: bool,
+    pub reason: String,
 }
 
-struct PendingToolCall {
-    id: String,
-    name: String,
-    arguments: String,
+/// Result of running the canned test prompt through a local model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalModelTestResult {
+    pub provider: String,
+    pub success: bool,
+    /// Generated text (may be partial when the test failed mid-generation)
+    pub text: String,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// Local inference speed measured with the current GPU settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalBenchmarkResult {
+    pub provider: String,
+    /// Prompt evaluation speed in tokens per second
+    pub prompt_tps: f64,
+    /// Generation speed in tokens per second
+    pub gen_tps: f64,
+    /// Prompt and generated tokens together
+    pub total_tokens: usize,
+    /// Wall time including model loading
+    pub elapsed_ms: u64,
+}
+
+/// Spaces out requests to one provider: one token per request, refilled at the configured rate
+#[derive(Debug)]
+struct TokenBucket {
+    requests_per_minute: u32,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(requests_per_minute: u32, now: Instant) -> Self {
+        Self {
+            requests_per_minute,
+            tokens: 1.0,
+            last_refill: now,
+        }
+    }
+
+    /// Take a token, or return how long to wait until one is available
+    /// Capacity is a single token, so requests are evenly spaced rather than bursting.
+    fn try_acquire(&mut self, now: Instant) -> Result<(), Duration> {
+        let per_second = self.requests_per_minute as f64 / 60.0;
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_second).min(1.0);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / per_second))
+        }
+    }
+}
+
+/// Counts a request as queued for as long as it waits for the rate limiter
+struct QueuedRequest<'a> {
+    queued: &'a std::sync::Mutex<HashMap<String, usize>>,
+    provider: AiProvider,
+}
+
+impl<'a> QueuedRequest<'a> {
+    fn enter(queued: &'a std::sync::Mutex<HashMap<String, usize>>, provider: AiProvider) -> Self {
+        *queued
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(provider.as_str().to_string())
+            .or_insert(0) += 1;
+        Self { queued, provider }
+    }
+}
+
+impl Drop for QueuedRequest<'_> {
+    fn drop(&mut self) {
+        let mut queued = self.queued.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = queued.get_mut(self.provider.as_str()) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                queued.remove(self.provider.as_str());
+            }
+        }
+    }
 }
 
 // ============================================================================
@@ -70,7 +780,7 @@ fn get_active_provider_file() -> Result<PathBuf, String> {
 }
 
 /// Load the saved active provider from disk
-fn load_active_provider() -> Option<AiProvider> {
+fn load_active_provider(settings: &SettingsManager) -> Option<AiProvider> {
     let file_path = get_active_provider_file().ok()?;
 
     if !file_path.exists() {
@@ -82,8 +792,8 @@ fn load_active_provider() -> Option<AiProvider> {
 
     match AiProvider::from_str(provider_str) {
         Ok(provider) => {
-            // Verify the API key still exists
-            if KeyringStore::has_api_key(provider) {
+            // Verify the active profile's API key still exists
+            if KeyringStore::has_api_key(provider, &settings.get_active_profile(provider)) {
                 log::info!("Loaded active provider from disk: {}", provider_str);
                 Some(provider)
             } else {
@@ -115,23 +825,60 @@ fn save_active_provider(provider: AiProvider) -> Result<(), String> {
 
 /// AI Manager handles routing prompts to different providers
 pub struct AiManager {
-    client: Client,
+    /// Shared HTTP client, rebuilt when the proxy setting changes
+    client: std::sync::RwLock<Client>,
     active_provider: Arc<Mutex<Option<AiProvider>>>,
     settings: Arc<SettingsManager>,
+    /// Tool calls waiting for confirm_tool_call / reject_tool_call, keyed by call ID
+    pending_confirmations: Arc<Mutex<HashMap<String, oneshot::Sender<bool>>>>,
+    /// Accumulated token usage per provider
+    usage_stats: Arc<Mutex<HashMap<String, UsageStats>>>,
+    /// Timings of recent successful generations per provider
+    latency_samples: Arc<Mutex<HashMap<String, Vec<LatencySample>>>>,
+    /// Held while a local model generates; local inference must never run twice at once
+    /// (cloud requests don't take it and may run concurrently)
+    local_inference_lock: Arc<Mutex<()>>,
+    /// Model ids fetched from provider APIs with fetch time, keyed by provider and API
+    /// key fingerprint (see `model_list_cache_key`)
+    model_list_cache: Arc<Mutex<HashMap<String, (Instant, Vec<String>)>>>,
+    /// Request rate limiters for cloud providers that have a requests-per-minute limit
+    rate_limiters: Arc<Mutex<HashMap<String, TokenBucket>>>,
+    /// Requests currently waiting for a rate limiter, per provider
+    queued_requests: Arc<std::sync::Mutex<HashMap<String, usize>>>,
 }
 
 impl AiManager {
     pub fn new(settings: Arc<SettingsManager>) -> Self {
         // Load the saved active provider from disk
-        let saved_provider = load_active_provider();
+        let saved_provider = load_active_provider(&settings);
+        Self::with_active_provider(settings, saved_provider)
+    }
 
+    fn with_active_provider(settings: Arc<SettingsManager>, saved_provider: Option<AiProvider>) -> Self {
         Self {
-            client: Client::new(),
+            client: std::sync::RwLock::new(settings.http_client()),
             active_provider: Arc::new(Mutex::new(saved_provider)),
             settings,
+            pending_confirmations: Arc::new(Mutex::new(HashMap::new())),
+            usage_stats: Arc::new(Mutex::new(HashMap::new())),
+            latency_samples: Arc::new(Mutex::new(HashMap::new())),
+            local_inference_lock: Arc::new(Mutex::new(())),
+            model_list_cache: Arc::new(Mutex::new(HashMap::new())),
+            rate_limiters: Arc::new(Mutex::new(HashMap::new())),
+            queued_requests: Arc::new(std::sync::Mutex::new(HashMap::new())),
         }
     }
 
+    /// Current HTTP client (cheap to clone, shares the connection pool)
+    fn client(&self) -> Client {
+        self.client.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Rebuild the HTTP client after the proxy setting changed
+    pub fn reload_http_client(&self) {
+        *self.client.write().unwrap_or_else(|e| e.into_inner()) = self.settings.http_client();
+    }
+
     pub async fn set_active_provider(&self, provider: AiProvider) {
         let mut active = self.active_provider.lock().await;
         *active = Some(provider);
@@ -148,273 +895,746 @@ impl AiManager {
         *self.active_provider.lock().await
     }
 
-    /// Invoke AI with streaming response
-    /// Emits 'ai-stream-chunk' events to the frontend
+    /// Build a request that lists the provider's models (used as a cheap authenticated probe)
+    fn list_models_request(&self, provider: AiProvider, api_key: &str) -> Option<RequestBuilder> {
+        if let Some(base_url) = ai_providers::openai_compatible_base_url(provider) {
+            return Some(
+                self.client()
+                    .get(format!("{}/models", base_url))
+                    .header("Authorization", format!("Bearer {}", api_key)),
+            );
+        }
+
+        match provider {
+            AiProvider::Anthropic => Some(
+                self.client()
+                    .get("https://api.anthropic.com/v1/models")
+                    .header("x-api-key", api_key)
+                    .header("anthropic-version", "2023-06-01"),
+            ),
+            // The key goes in a header; in the query it would show up in request errors
+            AiProvider::Google => Some(
+                self.client()
+                    .get("https://generativelanguage.googleapis.com/v1beta/models")
+                    .header("x-goog-api-key", api_key),
+            ),
+            AiProvider::Cohere => Some(
+                self.client()
+                    .get("https://api.cohere.com/v1/models?endpoint=chat")
+                    .header("Authorization", format!("Bearer {}", api_key)),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Fetch the model ids a cloud provider currently offers, using the stored API key
+    /// Results are cached for a few minutes.
+    pub async fn fetch_models(&self, provider: AiProvider) -> Result<Vec<String>, AiError> {
+        let profile = self.settings.get_active_profile(provider);
+        let api_key = KeyringStore::get_api_key(provider, &profile)
+            .map_err(|e| AiError::NoApiKey(e.to_string()))?;
+        let cache_key = model_list_cache_key(provider, &api_key);
+
+        if let Some((fetched_at, models)) = self.model_list_cache.lock().await.get(&cache_key) {
+            if fetched_at.elapsed() < MODEL_LIST_CACHE_TTL {
+                return Ok(models.clone());
+            }
+        }
+
+        let request = self
+            .list_models_request(provider, &api_key)
+            .ok_or_else(|| AiError::UnsupportedProvider(provider.as_str().to_string()))?;
+
+        let response = request.timeout(CONNECTIVITY_TIMEOUT).send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(AiError::ApiError(format!("HTTP {}: {}", status, error_text)));
+        }
+
+        let json: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| AiError::ParseError(e.to_string()))?;
+        let models = parse_model_ids(provider, &json);
+
+        self.model_list_cache
+            .lock()
+            .await
+            .insert(cache_key, (Instant::now(), models.clone()));
+
+        Ok(models)
+    }
+
+    /// Check whether a single provider can be used right now
+    async fn probe_provider(&self, provider: AiProvider) -> ProviderConnectivity {
+        let (reachable, reason) = if !provider.requires_api_key() {
+            match local_model::is_model_downloaded(provider, Some(&self.settings)) {
+                Ok(true) => (true, "Model downloaded".to_string()),
+                Ok(false) => (false, "Model not downloaded".to_string()),
+                Err(e) => (false, e.to_string()),
+            }
+        } else {
+            let profile = self.settings.get_active_profile(provider);
+            match KeyringStore::get_api_key(provider, &profile) {
+                Err(e) => (false, e.to_string()),
+                Ok(api_key) => match self.list_models_request(provider, &api_key) {
+                    None => (false, "No connectivity probe for this provider".to_string()),
+                    Some(request) => match request.timeout(CONNECTIVITY_TIMEOUT).send().await {
+                        Ok(response) if response.status().is_success() => (true, "OK".to_string()),
+                        Ok(response) => (false, format!("HTTP {}", response.status())),
+                        Err(e) if e.is_timeout() => (false, "Request timed out".to_string()),
+                        Err(e) => (false, format!("Unreachable: {}", e.without_url())),
+                    },
+                },
+            }
+        };
+
+        ProviderConnectivity {
+            provider: provider.as_str().to_string(),
+            reachable,
+            reason,
+        }
+    }
+
+    /// Check all configured providers concurrently
+    /// Cloud providers are probed over the network, local providers check their model file.
+    pub async fn check_connectivity(&self) -> Vec<ProviderConnectivity> {
+        let probes = KeyringStore::get_configured_providers(|p| self.settings.get_active_profile(p))
+            .into_iter()
+            .map(|provider| self.probe_provider(provider));
+
+        futures::future::join_all(probes).await
+    }
+
+    /// Run a short fixed prompt through a local model and return the text directly
+    /// Nothing is streamed to the frontend; failures are reported in the result.
+    pub async fn test_local_model(&self, app: &AppHandle, provider: AiProvider) -> LocalModelTestResult {
+        let started = Instant::now();
+        let sink = CapturingSink {
+            inner: &DiscardSink,
+            text: std::sync::Mutex::new(String::new()),
+        };
+        let options = StreamOptions {
+            disable_tools: true,
+            max_tokens: Some(LOCAL_MODEL_TEST_MAX_TOKENS),
+            ..StreamOptions::default()
+        };
+
+        let result = if provider.requires_api_key() {
+            Err(AiError::UnsupportedProvider(format!("{} is not a local model", provider.as_str())))
+        } else {
+            self.stream_with_provider(app, &sink, provider, LOCAL_MODEL_TEST_PROMPT, "", &options)
+                .await
+        };
+
+        let text = sink.text.into_inner().unwrap_or_default().trim().to_string();
+        let error = match result {
+            Ok(()) if text.is_empty() => Some("The model produced no text".to_string()),
+            Ok(()) => None,
+            Err(e) => Some(e.to_string()),
+        };
+
+        LocalModelTestResult {
+            provider: provider.as_str().to_string(),
+            success: error.is_none(),
+            text,
+            error,
+            duration_ms: started.elapsed().as_millis() as u64,
+        }
+    }
+
+    /// Measure prompt evaluation and generation speed of a local model
+    /// Uses the configured GPU type and layer count so setups can be compared.
+    pub async fn benchmark_local_model(&self, provider: AiProvider) -> Result<LocalBenchmarkResult, AiError> {
+        if provider.requires_api_key() {
+            return Err(AiError::UnsupportedProvider(format!("{} is not a local model", provider.as_str())));
+        }
+
+        let _guard = self
+            .local_inference_lock
+            .try_lock()
+            .map_err(|_| AiError::Busy)?;
+
+        let started = Instant::now();
+        let options = StreamOptions {
+            disable_tools: true,
+            max_tokens: Some(LOCAL_BENCHMARK_MAX_TOKENS),
+            ..StreamOptions::default()
+        };
+        let stats = local_inference::run_local_inference(
+            &DiscardSink,
+            provider,
+            LOCAL_BENCHMARK_PROMPT,
+            "",
+            &options,
+            Some(&self.settings),
+        )
+        .await?;
+
+        let result = LocalBenchmarkResult {
+            provider: provider.as_str().to_string(),
+            prompt_tps: tokens_per_second(stats.prompt_tokens, stats.prompt_eval),
+            gen_tps: tokens_per_second(stats.generated_tokens, stats.generation),
+            total_tokens: stats.prompt_tokens + stats.generated_tokens,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        };
+        log::info!(
+            "Benchmark {}: prompt {:.1} t/s, generation {:.1} t/s",
+            result.provider,
+            result.prompt_tps,
+            result.gen_tps
+        );
+        Ok(result)
+    }
+
+    /// Wait until the provider's rate limit allows another request
+    /// Providers without a requests-per-minute limit (and local models) pass straight through.
+    async fn wait_for_rate_limit(&self, provider: AiProvider) {
+        let Some(requests_per_minute) = self.settings.get_requests_per_minute(provider) else {
+            return;
+        };
+        let _queued = QueuedRequest::enter(&self.queued_requests, provider);
+
+        loop {
+            let wait = {
+                let mut limiters = self.rate_limiters.lock().await;
+                let now = Instant::now();
+                let bucket = limiters
+                    .entry(provider.as_str().to_string())
+                    .or_insert_with(|| TokenBucket::new(requests_per_minute, now));
+                bucket.requests_per_minute = requests_per_minute;
+                bucket.try_acquire(now)
+            };
+
+            match wait {
+                Ok(()) => return,
+                Err(delay) => {
+                    log::info!("Rate limit for {}: waiting {:?}", provider.as_str(), delay);
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+    }
+
+    /// Number of requests waiting for a rate limiter, per provider
+    pub fn get_request_queue_status(&self) -> HashMap<String, usize> {
+        self.queued_requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Get accumulated token usage per provider
+    pub async fn get_usage_stats(&self) -> HashMap<String, UsageStats> {
+        self.usage_stats.lock().await.clone()
+    }
+
+    /// Clear the usage totals of one provider, or of all providers with None
+    /// Returns the totals that were cleared; nothing else about the session changes.
+    pub async fn reset_usage_stats(&self, provider: Option<AiProvider>) -> HashMap<String, UsageStats> {
+        let mut stats = self.usage_stats.lock().await;
+        let cleared: HashMap<String, UsageStats> = match provider {
+            Some(provider) => stats
+                .remove_entry(provider.as_str())
+                .into_iter()
+                .collect(),
+            None => stats.drain().collect(),
+        };
+
+        log::info!("Cleared usage stats for {} providers", cleared.len());
+        cleared
+    }
+
+    /// Get min/avg/p95 time to first chunk and total duration per provider
+    pub async fn get_latency_stats(&self) -> HashMap<String, ProviderLatencyStats> {
+        self.latency_samples
+            .lock()
+            .await
+            .iter()
+            .map(|(provider, samples)| (provider.clone(), ProviderLatencyStats::from_samples(samples)))
+            .collect()
+    }
+
+    /// Keep the timings of a successful generation
+    async fn record_latency(&self, provider: AiProvider, sample: LatencySample) {
+        let mut latency = self.latency_samples.lock().await;
+        let samples = latency.entry(provider.as_str().to_string()).or_default();
+        samples.push(sample);
+        if samples.len() > MAX_LATENCY_SAMPLES {
+            samples.remove(0);
+        }
+    }
+
+    /// Reset per-session AI state: usage totals, latency timings and pending tool calls
+    /// Cards, settings, API keys and the active provider are left alone.
+    pub async fn reset_session(&self) {
+        self.usage_stats.lock().await.clear();
+        self.latency_samples.lock().await.clear();
+
+        // Reject anything still waiting so the generation it belongs to can finish
+        let pending: Vec<_> = self.pending_confirmations.lock().await.drain().collect();
+        for (_, sender) in pending {
+            sender.send(false).ok();
+        }
+
+        log::info!("AI session state reset");
+    }
+
+    /// Add a generation's token usage to the running totals and emit 'ai-usage'
+    async fn record_usage(&self, events: &dyn AppEvents, provider: AiProvider, model: &str, usage: TokenUsage) {
+        {
+            let mut stats = self.usage_stats.lock().await;
+            let entry = stats.entry(provider.as_str().to_string()).or_default();
+            entry.requests += 1;
+            entry.prompt_tokens += usage.prompt_tokens;
+            entry.completion_tokens += usage.completion_tokens;
+        }
+
+        events.emit_json("ai-usage", AiUsageEvent {
+            provider: provider.as_str().to_string(),
+            model: model.to_string(),
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+        });
+    }
+
+    /// Resolve a tool call that is waiting for user confirmation
+    pub async fn resolve_tool_call(&self, call_id: &str, approved: bool) -> Result<(), String> {
+        let sender = self
+            .pending_confirmations
+            .lock()
+            .await
+            .remove(call_id)
+            .ok_or_else(|| format!("No pending tool call with ID {}", call_id))?;
+
+        sender
+            .send(approved)
+            .map_err(|_| "Tool call is no longer waiting for confirmation".to_string())
+    }
+
+    /// Execute a tool call requested by the model
+    /// If confirmation is required, emits 'tool-call-pending' for tools that change notes
+    /// and waits for the user first; read-only tools run right away.
+    async fn handle_tool_call(
+        &self,
+        events: &dyn AppEvents,
+        sink: &dyn ChunkSink,
+        tool: &PendingToolCall,
+    ) -> Result<String, String> {
+        if self.settings.get_require_tool_confirmation() && !ai_tools::is_read_only(&tool.name) {
+            let call_id = if tool.id.is_empty() {
+                uuid::Uuid::new_v4().to_string()
+            } else {
+                tool.id.clone()
+            };
+            let preview = ai_tools::preview_tool(&tool.name, &tool.arguments)?;
+
+            let (tx, rx) = oneshot::channel();
+            self.pending_confirmations
+                .lock()
+                .await
+                .insert(call_id.clone(), tx);
+
+            events.emit_json("tool-call-pending", ToolCallPending {
+                call_id: call_id.clone(),
+                name: tool.name.clone(),
+                arguments: tool.arguments.clone(),
+                preview,
+            });
+
+            sink.set_waiting_for_user(true);
+            let approved = matches!(
+                tokio::time::timeout(TOOL_CONFIRMATION_TIMEOUT, rx).await,
+                Ok(Ok(true))
+            );
+            sink.set_waiting_for_user(false);
+            self.pending_confirmations.lock().await.remove(&call_id);
+
+            if !approved {
+                log::info!("Tool call {} ({}) rejected", call_id, tool.name);
+                return Err(format!("Tool call {} was rejected by the user", tool.name));
+            }
+        }
+
+        ai_tools::execute_tool(&tool.name, &tool.arguments)
+    }
+
+    /// Execute the tool calls from one model turn in order
+    /// The frontend gets a single 'refresh-required' once all of them have run.
+    async fn handle_tool_calls(&self, events: &dyn AppEvents, sink: &dyn ChunkSink, tools: Vec<PendingToolCall>) {
+        if tools.is_empty() {
+            return;
+        }
+
+        for tool in &tools {
+            if let Err(e) = self.handle_tool_call(events, sink, tool).await {
+                log::warn!("Tool call {} failed: {}", tool.name, e);
+            }
+        }
+
+        // Signal frontend to refresh data
+        events.emit_json("refresh-required", ());
+    }
+
+    /// System prompt for a request: per-request override, then the configured one
+    fn resolve_system_prompt(&self, provider: AiProvider, options: &StreamOptions) -> Option<String> {
+        options
+            .system_prompt
+            .clone()
+            .or_else(|| self.settings.get_system_prompt(provider))
+    }
+
+    /// Invoke AI with streaming response
+    /// Emits 'ai-stream-chunk' events to the frontend, only to `target_window` when given
     pub async fn invoke_stream(
         &self,
         app: &AppHandle,
         prompt: &str,
         context: &str,
+        mode: StreamMode,
+        target_window: Option<&str>,
+    ) -> Result<(), AiError> {
+        let sink = match target_window {
+            Some(label) => TauriChunkSink::for_window(app, label),
+            None => TauriChunkSink::new(app),
+        };
+        let options = StreamOptions {
+            mode,
+            ..StreamOptions::default()
+        };
+        self.invoke_stream_with_sink(app, &sink, prompt, context, &options)
+            .await
+    }
+
+    /// Invoke AI with streaming response, sending chunks to the given sink
+    /// Tool calls, usage and refresh requests go through `events` (the `AppHandle` in
+    /// the app). Successful generations are timed for `get_latency_stats`.
+    pub async fn invoke_stream_with_sink(
+        &self,
+        events: &dyn AppEvents,
+        sink: &dyn ChunkSink,
+        prompt: &str,
+        context: &str,
+        options: &StreamOptions,
     ) -> Result<(), AiError> {
+        let timing = TimingSink::new(sink);
         let provider = self
             .active_provider
             .lock()
             .await
             .ok_or_else(|| AiError::NoApiKey("No provider selected".to_string()))?;
 
+        if !options.disable_tools && !provider.supports_tools() {
+            log::warn!(
+                "{} doesn't support tool calling; note tools won't be available",
+                provider.display_name()
+            );
+        }
+
+        timing.send_start(options.mode);
+
+        let result = if self.settings.get_log_conversations() {
+            self.stream_and_log(events, &timing, provider, prompt, context, options)
+                .await
+        } else {
+            self.stream_with_provider(events, &timing, provider, prompt, context, options)
+                .await
+        };
+
+        if result.is_ok() {
+            self.record_latency(provider, timing.sample()).await;
+        }
+        result
+    }
+
+    /// Stream a response and append the exchange to the conversation log
+    async fn stream_and_log(
+        &self,
+        events: &dyn AppEvents,
+        sink: &dyn ChunkSink,
+        provider: AiProvider,
+        prompt: &str,
+        context: &str,
+        options: &StreamOptions,
+    ) -> Result<(), AiError> {
+        let capturing = CapturingSink {
+            inner: sink,
+            text: std::sync::Mutex::new(String::new()),
+        };
+        let result = self
+            .stream_with_provider(events, &capturing, provider, prompt, context, options)
+            .await;
+
+        let model = if provider.requires_api_key() {
+            self.settings.get_provider_model(provider)
+        } else {
+            self.settings
+                .get_local_model_config(provider)
+                .map(|config| config.filename)
+                .unwrap_or_default()
+        };
+        let entry = ConversationLogEntry {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            provider: provider.as_str().to_string(),
+            model,
+            prompt: prompt.to_string(),
+            context: context.to_string(),
+            response: capturing.text.into_inner().unwrap_or_default(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        };
+        if let Err(e) = conversation_log::append(&entry) {
+            log::warn!("Failed to write conversation log: {}", e);
+        }
+
+        result
+    }
+
+    /// Stream a response from a specific provider
+    async fn stream_with_provider(
+        &self,
+        events: &dyn AppEvents,
+        sink: &dyn ChunkSink,
+        provider: AiProvider,
+        prompt: &str,
+        context: &str,
+        options: &StreamOptions,
+    ) -> Result<(), AiError> {
         // Check if it's a local model
         if !provider.requires_api_key() {
-            // Local model inference
-            local_inference::run_local_inference(app, provider, prompt, context, Some(&self.settings)).await?;
+            // Local model inference, one at a time; fail fast instead of queueing
+            let _guard = self
+                .local_inference_lock
+                .try_lock()
+                .map_err(|_| AiError::Busy)?;
+            local_inference::run_local_inference(
+                sink,
+                provider,
+                prompt,
+                context,
+                options,
+                Some(&self.settings),
+            )
+            .await?;
             return Ok(());
         }
 
-        // Cloud API inference
-        let api_key = KeyringStore::get_api_key(provider)
+        // Cloud API inference, queued behind the provider's rate limit
+        self.wait_for_rate_limit(provider).await;
+        let profile = self.settings.get_active_profile(provider);
+        let api_key = KeyringStore::get_api_key(provider, &profile)
             .map_err(|e| AiError::NoApiKey(e.to_string()))?;
 
-        match provider {
-            AiProvider::OpenAI => self.stream_openai(app, &api_key, prompt, context).await,
-            AiProvider::Anthropic => self.stream_anthropic(app, &api_key, prompt, context).await,
-            AiProvider::Google => self.stream_google(app, &api_key, prompt, context).await,
-            _ => Err(AiError::UnsupportedProvider(format!("{:?}", provider))),
-        }
+        self.stream(events, sink, provider, &api_key, prompt, context, options)
+            .await
     }
 
-    async fn stream_openai(
+    /// Stream a response into an existing (new, empty) card, appending as it arrives
+    /// 'ai-stream-start' carries the card id and the text follows as 'ai-card-stream-chunk'
+    /// events. The generation stops when the text can't be appended. If the request
+    /// fails before any text arrived, the empty card is deleted again.
+    pub async fn stream_to_card(
         &self,
         app: &AppHandle,
-        api_key: &str,
+        card_id: &str,
         prompt: &str,
         context: &str,
     ) -> Result<(), AiError> {
-        let tools = ai_tools::get_all_tools();
-        let model = self.settings.get_provider_model(AiProvider::OpenAI);
-
-        let body = serde_json::json!({
-            "model": model,
-            "messages": [
-                {
-                    "role": "system",
-                    "content": "You are a helpful AI assistant for a sticky note application.
-CRITICAL INSTRUCTION: When the user asks to create, update, or delete a note, you MUST use the provided tools (`create_note`, `update_note`, `delete_note`).
-DO NOT rewrite the note content in your text response. Only use the tool.
-If you use a tool, your text response should be empty or a very brief confirmation (e.g. 'Done').
-Only output long text if you are answering a general question without modifying a note."
-                },
-                {
-                    "role": "user",
-                    "content": format!("Context (current card content):\n{}\n\nUser request: {}", context, prompt)
-                }
-            ],
-            "tools": tools,
-            "stream": true
-        });
-
-        let response = self
-            .client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(AiError::ApiError(error_text));
-        }
+        let sink = CardSink::new(Box::new(TauriChunkSink::for_card(app, card_id)), card_id);
+        let options = StreamOptions {
+            mode: StreamMode::Append,
+            ..StreamOptions::default()
+        };
 
-        let mut stream = response.bytes_stream();
-        let mut pending_tool: Option<PendingToolCall> = None;
-
-        while let Some(chunk_result) = stream.next().await {
-            let chunk = chunk_result?;
-            let text = String::from_utf8_lossy(&chunk);
-
-            for line in text.lines() {
-                if let Some(data) = line.strip_prefix("data: ") {
-                    if data == "[DONE]" {
-                        // If there is a pending tool call that finished exactly at the end
-                        if let Some(tool) = pending_tool.take() {
-                            let _ = ai_tools::execute_tool(&tool.name, &tool.arguments);
-                            // Signal frontend to refresh data
-                            app.emit("refresh-required", ()).ok();
-                        }
-
-                        app.emit("ai-stream-chunk", AiStreamChunk {
-                            chunk: String::new(),
-                            done: true,
-                            gpu_info: None,
-                        }).ok();
-                        return Ok(());
-                    }
+        let result = self
+            .invoke_stream_with_sink(app, &sink, prompt, context, &options)
+            .await;
 
-                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
-                        let delta = &json["choices"][0]["delta"];
-
-                        // 1. Handle normal text content
-                        if let Some(content) = delta["content"].as_str() {
-                            app.emit("ai-stream-chunk", AiStreamChunk {
-                                chunk: content.to_string(),
-                                done: false,
-                                gpu_info: None,
-                            }).ok();
-                        }
-
-                        // 2. Handle Tool Calls
-                        if let Some(tool_calls) = delta["tool_calls"].as_array() {
-                            for call in tool_calls {
-                                let _index = call["index"].as_u64().unwrap_or(0);
-                                
-                                // New tool call starting (assuming index 0 for simplicity in streaming one tool)
-                                if let Some(id) = call["id"].as_str() {
-                                    // If we had a previous one, execute it now (though OpenAI usually finishes one before starting next?)
-                                    // In streaming, 'id' is sent only in the first chunk of the tool call.
-                                    pending_tool = Some(PendingToolCall {
-                                        id: id.to_string(),
-                                        name: String::new(),
-                                        arguments: String::new(),
-                                    });
-                                }
-
-                                if let Some(function) = call["function"].as_object() {
-                                    if let Some(pt) = &mut pending_tool {
-                                        if let Some(name) = function.get("name").and_then(|n| n.as_str()) {
-                                            pt.name.push_str(name);
-                                        }
-                                        if let Some(args) = function.get("arguments").and_then(|a| a.as_str()) {
-                                            pt.arguments.push_str(args);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        
-                        // Check finish_reason to execute tool
-                        if let Some(finish_reason) = json["choices"][0]["finish_reason"].as_str() {
-                            if finish_reason == "tool_calls" {
-                                if let Some(tool) = pending_tool.take() {
-                                    let _ = ai_tools::execute_tool(&tool.name, &tool.arguments);
-                                    // Signal frontend to refresh data
-                                    app.emit("refresh-required", ()).ok();
-                                }
-                            }
-                        }
-                    }
-                }
+        if let Err(e) = &result {
+            // Errors the provider sent mid-stream were reported already
+            if !sink.error_sent() {
+                sink.send_error(&e.to_string());
+            }
+            if card_manager::get_card_by_id(card_id).is_ok_and(|card| card.content.is_empty()) {
+                card_manager::delete_card(card_id).ok();
             }
         }
+        app.emit("refresh-required", ()).ok();
 
-        Ok(())
+        result
     }
 
-    async fn stream_anthropic(
+    /// Generate text with the active provider, streaming it as 'ai-stream-chunk' events
+    /// Returns the full generated text once the stream is done.
+    pub async fn generate_text(
         &self,
         app: &AppHandle,
-        api_key: &str,
         prompt: &str,
         context: &str,
-    ) -> Result<(), AiError> {
-        let model = self.settings.get_provider_model(AiProvider::Anthropic);
-
-        let body = serde_json::json!({
-            "model": model,
-            "max_tokens": 4096,
-            "messages": [
-                {
-                    "role": "user",
-                    "content": format!("Context (current card content):\n{}\n\nUser request: {}", context, prompt)
-                }
-            ],
-            "stream": true
-        });
+        options: &StreamOptions,
+    ) -> Result<String, AiError> {
+        let tauri_sink = TauriChunkSink::new(app);
+        let sink = CapturingSink {
+            inner: &tauri_sink,
+            text: std::sync::Mutex::new(String::new()),
+        };
 
-        let response = self
-            .client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("Content-Type", "application/json")
-            .json(&body)
-            .send()
+        self.invoke_stream_with_sink(app, &sink, prompt, context, options)
             .await?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(AiError::ApiError(error_text));
-        }
+        Ok(sink.text.into_inner().unwrap_or_default())
+    }
 
-        let mut stream = response.bytes_stream();
+    /// Continue a response that stopped at the token limit, appending to the note
+    /// `content` is the note including the partial output; 'ai-stream-truncated' is
+    /// emitted again if the continuation is cut off too.
+    pub async fn continue_generation(
+        &self,
+        app: &AppHandle,
+        content: &str,
+        target_window: Option<&str>,
+    ) -> Result<(), AiError> {
+        let sink = match target_window {
+            Some(label) => TauriChunkSink::for_window(app, label),
+            None => TauriChunkSink::new(app),
+        };
+        let options = StreamOptions {
+            disable_tools: true,
+            system_prompt: Some(CONTINUE_SYSTEM_PROMPT.to_string()),
+            mode: StreamMode::Append,
+            ..StreamOptions::default()
+        };
 
-        while let Some(chunk_result) = stream.next().await {
-            let chunk = chunk_result?;
-            let text = String::from_utf8_lossy(&chunk);
-
-            for line in text.lines() {
-                if let Some(data) = line.strip_prefix("data: ") {
-                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
-                        let event_type = json["type"].as_str().unwrap_or("");
-
-                        match event_type {
-                            "content_block_delta" => {
-                                if let Some(text) = json["delta"]["text"].as_str() {
-                                    app.emit("ai-stream-chunk", AiStreamChunk {
-                                        chunk: text.to_string(),
-                                        done: false,
-                                        gpu_info: None,
-                                    }).ok();
-                                }
-                            }
-                            "message_stop" => {
-                                app.emit("ai-stream-chunk", AiStreamChunk {
-                                    chunk: String::new(),
-                                    done: true,
-                                    gpu_info: None,
-                                }).ok();
-                                return Ok(());
-                            }
-                            _ => {}
-                        }
-                    }
-                }
-            }
-        }
+        let prompt = "Continue exactly where the text stops.";
+        self.invoke_stream_with_sink(app, &sink, prompt, content, &options)
+            .await
+    }
 
-        Ok(())
+    /// Prepend the notes most relevant to `context` (the current card) to it
+    /// `card_id` is left out of the candidates. The notes share a budget that fits the
+    /// active provider's context window; the context is unchanged when none match.
+    pub async fn with_related_notes(&self, context: &str, card_id: Option<&str>, limit: usize) -> String {
+        let is_local = self
+            .get_active_provider()
+            .await
+            .is_some_and(|p| !p.requires_api_key());
+        let budget = if is_local {
+            LOCAL_RELATED_CONTEXT_CHARS
+        } else {
+            RELATED_CONTEXT_CHARS
+        };
+
+        let related = card_manager::find_related_cards(context, card_id, limit);
+        log::debug!("Adding {} related notes to the context", related.len());
+        format!("{}{}", card_manager::build_related_context(&related, budget), context)
     }
 
-    async fn stream_google(
+    /// Summarize the given cards into a digest with the active provider (no tools)
+    /// Long inputs are truncated to fit the provider's context window.
+    pub async fn summarize_notes(
         &self,
         app: &AppHandle,
+        cards: &[Card],
+    ) -> Result<String, AiError> {
+        let is_local = self
+            .get_active_provider()
+            .await
+            .is_some_and(|p| !p.requires_api_key());
+        let budget = if is_local {
+            LOCAL_SUMMARY_CONTEXT_CHARS
+        } else {
+            SUMMARY_CONTEXT_CHARS
+        };
+
+        let digest = card_manager::build_notes_digest(cards, budget);
+        let options = StreamOptions {
+            disable_tools: true,
+            system_prompt: Some(SUMMARY_SYSTEM_PROMPT.to_string()),
+            ..StreamOptions::default()
+        };
+
+        self.generate_text(app, "Summarize these notes.", &digest, &options)
+            .await
+    }
+
+    /// Assemble the request a cloud provider is sent, system prompt and tools included
+    fn build_request(
+        &self,
+        wire: &dyn Provider,
+        provider: AiProvider,
         api_key: &str,
         prompt: &str,
         context: &str,
-    ) -> Result<(), AiError> {
-        let model = self.settings.get_provider_model(AiProvider::Google);
+        options: &StreamOptions,
+    ) -> ProviderRequest {
+        let model = self.settings.get_provider_model(provider);
+        let system_prompt = self
+            .resolve_system_prompt(provider, options)
+            .unwrap_or_else(|| options.mode.default_system_prompt().to_string());
 
-        let url = format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent?key={}&alt=sse",
-            model, api_key
-        );
+        wire.build_request(&RequestParams {
+            model: &model,
+            api_key,
+            system_prompt: &system_prompt,
+            prompt,
+            context,
+            tools: !options.disable_tools && wire.supports_tools(),
+        })
+    }
 
-        let body = serde_json::json!({
-            "contents": [
-                {
-                    "parts": [
-                        {
-                            "text": format!("SYSTEM: You are a text editor. Your goal is to update the note content based on the user request. Output ONLY the full updated note content. Do not output conversational text.\n\nContext (current content):\n{}\n\nUser request: {}", context, prompt)
-                        }
-                    ]
-                }
-            ]
-        });
+    /// What the active provider would be sent for a prompt, without sending anything
+    /// Cloud providers: the request body as pretty JSON (the API key is left out).
+    /// Local models: the formatted prompt text.
+    pub async fn preview_prompt(&self, prompt: &str, context: &str, mode: StreamMode) -> Result<String, AiError> {
+        let provider = self
+            .active_provider
+            .lock()
+            .await
+            .ok_or_else(|| AiError::NoApiKey("No provider selected".to_string()))?;
+        let options = StreamOptions {
+            mode,
+            ..StreamOptions::default()
+        };
+
+        let Some(wire) = ai_providers::for_provider(provider) else {
+            return Ok(local_inference::build_prompt(
+                provider,
+                prompt,
+                context,
+                &options,
+                Some(&self.settings),
+            ));
+        };
+
+        let request = self.build_request(wire.as_ref(), provider, "", prompt, context, &options);
+        serde_json::to_string_pretty(&request.body).map_err(|e| AiError::ParseError(e.to_string()))
+    }
 
-        let response = self
-            .client
-            .post(&url)
+    /// Stream a generation from a cloud provider
+    /// The provider builds the request and interprets each SSE line; this handles the
+    /// HTTP, the chunk sink, tool execution and usage accounting.
+    async fn stream(
+        &self,
+        events: &dyn AppEvents,
+        sink: &dyn ChunkSink,
+        provider: AiProvider,
+        api_key: &str,
+        prompt: &str,
+        context: &str,
+        options: &StreamOptions,
+    ) -> Result<(), AiError> {
+        let mut wire = ai_providers::for_provider(provider)
+            .ok_or_else(|| AiError::UnsupportedProvider(format!("{:?}", provider)))?;
+        let model = self.settings.get_provider_model(provider);
+        let request = self.build_request(wire.as_ref(), provider, api_key, prompt, context, options);
+
+        let mut builder = self.client().post(&request.url);
+        for (name, value) in &request.headers {
+            builder = builder.header(*name, value);
+        }
+        let response = builder
             .header("Content-Type", "application/json")
-            .json(&body)
+            .json(&request.body)
             .send()
             .await?;
 
@@ -424,35 +1644,355 @@ Only output long text if you are answering a general question without modifying
         }
 
         let mut stream = response.bytes_stream();
+        let mut lines = SseLines::default();
+        let mut ended = false;
+
+        while !ended {
+            // Network reads can end mid-line or mid-character, so only whole lines are parsed
+            let complete = match stream.next().await {
+                Some(chunk_result) => lines.push(&chunk_result?),
+                None => {
+                    ended = true;
+                    lines.finish().into_iter().collect()
+                }
+            };
 
-        while let Some(chunk_result) = stream.next().await {
-            let chunk = chunk_result?;
-            let text = String::from_utf8_lossy(&chunk);
-
-            for line in text.lines() {
-                if let Some(data) = line.strip_prefix("data: ") {
-                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
-                        if let Some(text) = json["candidates"][0]["content"]["parts"][0]["text"].as_str() {
-                            app.emit("ai-stream-chunk", AiStreamChunk {
-                                chunk: text.to_string(),
-                                done: false,
-                                gpu_info: None,
-                            }).ok();
-                        }
-
-                        if json["candidates"][0]["finishReason"].as_str().is_some() {
-                            app.emit("ai-stream-chunk", AiStreamChunk {
-                                chunk: String::new(),
-                                done: true,
-                                gpu_info: None,
-                            }).ok();
-                            return Ok(());
-                        }
+            for line in complete {
+                let Some(event) = line.strip_prefix("data: ").and_then(|data| wire.parse_chunk(data)) else {
+                    continue;
+                };
+
+                if let Some(message) = event.error {
+                    sink.send_error(&message);
+                    return Err(AiError::ApiError(message));
+                }
+                for piece in &event.reasoning {
+                    sink.send_reasoning(piece);
+                }
+                for piece in &event.text {
+                    sink.send_text(piece);
+                }
+                if let Some(reason) = sink.failure() {
+                    return Err(AiError::Aborted(reason));
+                }
+                if event.truncated {
+                    sink.send_truncated();
+                }
+                self.handle_tool_calls(events, sink, event.tool_calls).await;
+
+                if event.done {
+                    sink.send_done();
+
+                    if let Some(usage) = event.usage {
+                        self.record_usage(events, provider, &model, usage).await;
                     }
+                    return Ok(());
                 }
             }
         }
 
+        // Stream ended without a done event; still run any collected tool calls
+        self.handle_tool_calls(events, sink, wire.finish()).await;
+
         Ok(())
     }
 }
+
+/// Splits a byte stream into SSE lines, keeping an unfinished line until its '\n' arrives
+#[derive(Default)]
+struct SseLines {
+    buffer: Vec<u8>,
+}
+
+impl SseLines {
+    /// Add a chunk and return the lines it completes, without line endings
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+        let Some(last_newline) = self.buffer.iter().rposition(|&b| b == b'\n') else {
+            return Vec::new();
+        };
+
+        let rest = self.buffer.split_off(last_newline + 1);
+        let mut complete = std::mem::replace(&mut self.buffer, rest);
+        complete.pop(); // the final '\n'
+        complete.split(|&b| b == b'\n').map(decode_line).collect()
+    }
+
+    /// The last line if the stream ended without a final newline
+    fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.buffer);
+        (!rest.is_empty()).then(|| decode_line(&rest))
+    }
+}
+
+/// Decode one complete line, dropping a trailing '\r'
+fn decode_line(line: &[u8]) -> String {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    String::from_utf8_lossy(line).into_owned()
+}
+
+/// Tokens per second, 0 when nothing was timed
+fn tokens_per_second(tokens: usize, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 {
+        tokens as f64 / secs
+    } else {
+        0.0
+    }
+}
+
+/// Model list cache key: the provider plus a fingerprint of the API key.
+/// Different keys (profiles, accounts) can see different models, so each gets its own
+/// entry; the key itself is never kept.
+fn model_list_cache_key(provider: AiProvider, api_key: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    let digest = Sha256::digest(api_key.as_bytes());
+    let fingerprint: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}:{}", provider.as_str(), fingerprint)
+}
+
+/// Extract model ids from a provider's list-models response, sorted
+/// Gemini names look like "models/gemini-2.5-flash" and include embedding models,
+/// so only models that support generateContent are kept.
+fn parse_model_ids(provider: AiProvider, json: &serde_json::Value) -> Vec<String> {
+    let mut ids: Vec<String> = match provider {
+        AiProvider::Google => json["models"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|model| {
+                model["supportedGenerationMethods"]
+                    .as_array()
+                    .map_or(true, |methods| methods.iter().any(|m| m == "generateContent"))
+            })
+            .filter_map(|model| model["name"].as_str())
+            .map(|name| name.trim_start_matches("models/").to_string())
+            .collect(),
+        AiProvider::Cohere => json["models"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|model| model["name"].as_str())
+            .map(|name| name.to_string())
+            .collect(),
+        _ => json["data"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|model| model["id"].as_str())
+            .map(|id| id.to_string())
+            .collect(),
+    };
+    ids.sort();
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use serde_json::json;
+
+    #[test]
+    fn test_latency_summary() {
+        assert_eq!(LatencySummary::from_durations(Vec::new()), None);
+
+        let summary = LatencySummary::from_durations((1..=20).map(|i| Duration::from_millis(i * 10))).unwrap();
+        assert_eq!(summary, LatencySummary { min_ms: 10, avg_ms: 105, p95_ms: 190 });
+
+        let stats = ProviderLatencyStats::from_samples(&[
+            LatencySample { first_chunk: Some(Duration::from_millis(300)), total: Duration::from_secs(2) },
+            LatencySample { first_chunk: None, total: Duration::from_secs(1) },
+        ]);
+        assert_eq!(stats.requests, 2);
+        assert_eq!(stats.first_chunk.unwrap().p95_ms, 300);
+        assert_eq!(stats.total.unwrap().min_ms, 1000);
+    }
+
+    #[test]
+    fn test_parse_model_ids() {
+        let openai = json!({ "data": [{ "id": "gpt-4o" }, { "id": "gpt-4.1" }] });
+        assert_eq!(parse_model_ids(AiProvider::OpenAI, &openai), vec!["gpt-4.1", "gpt-4o"]);
+
+        let google = json!({ "models": [
+            { "name": "models/gemini-2.5-flash", "supportedGenerationMethods": ["generateContent"] },
+            { "name": "models/text-embedding-004", "supportedGenerationMethods": ["embedContent"] },
+        ] });
+        assert_eq!(parse_model_ids(AiProvider::Google, &google), vec!["gemini-2.5-flash"]);
+
+        assert!(parse_model_ids(AiProvider::Anthropic, &json!({})).is_empty());
+    }
+
+    #[test]
+    fn test_token_bucket_spaces_requests() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(60, start);
+
+        assert!(bucket.try_acquire(start).is_ok());
+        let wait = bucket.try_acquire(start).unwrap_err();
+        assert!((wait.as_secs_f64() - 1.0).abs() < 0.01);
+
+        // Half a second later, half a token has refilled
+        let wait = bucket.try_acquire(start + Duration::from_millis(500)).unwrap_err();
+        assert!((wait.as_secs_f64() - 0.5).abs() < 0.01);
+        assert!(bucket.try_acquire(start + Duration::from_secs(1)).is_ok());
+    }
+
+    #[test]
+    fn test_tokens_per_second() {
+        assert_eq!(tokens_per_second(50, Duration::from_millis(500)), 100.0);
+        assert_eq!(tokens_per_second(10, Duration::ZERO), 0.0);
+    }
+
+    /// Records the text and errors it's given, shared with the test
+    struct RecordingSink {
+        events: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl ChunkSink for RecordingSink {
+        fn send(&self, chunk: AiStreamChunk) {
+            let event = if chunk.done { "done".to_string() } else { chunk.chunk };
+            self.events.lock().unwrap().push(event);
+        }
+
+        fn send_error(&self, message: &str) {
+            self.events.lock().unwrap().push(format!("error: {}", message));
+        }
+    }
+
+    #[test]
+    fn test_card_sink_appends_and_forwards() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let appended = Arc::new(std::sync::Mutex::new(String::new()));
+        let card_text = appended.clone();
+        let sink = CardSink::with_appender(
+            Box::new(RecordingSink { events: events.clone() }),
+            "card-1",
+            Box::new(move |id: &str, text: &str| {
+                assert_eq!(id, "card-1");
+                card_text.lock().unwrap().push_str(text);
+                Ok(())
+            }),
+        );
+
+        sink.send_text("Hello, ");
+        sink.send_text("world");
+        sink.send_done();
+
+        assert_eq!(*appended.lock().unwrap(), "Hello, world");
+        assert_eq!(*events.lock().unwrap(), vec!["Hello, ", "world", "done"]);
+        assert_eq!(sink.failure(), None);
+        assert!(!sink.error_sent());
+    }
+
+    #[test]
+    fn test_card_sink_stops_after_failed_append() {
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = CardSink::with_appender(
+            Box::new(RecordingSink { events: events.clone() }),
+            "card-1",
+            Box::new(|_: &str, text: &str| match text {
+                "full" => Err("Storage limit reached".to_string()),
+                _ => Ok(()),
+            }),
+        );
+        let timing = TimingSink::new(&sink);
+
+        timing.send_text("kept");
+        assert_eq!(timing.failure(), None);
+        timing.send_text("full");
+        assert_eq!(timing.failure().as_deref(), Some("Storage limit reached"));
+
+        // Nothing is forwarded once an append failed, not even the done signal
+        timing.send_text("more");
+        timing.send_done();
+        assert_eq!(*events.lock().unwrap(), vec!["kept"]);
+
+        timing.send_error("Generation stopped");
+        assert!(sink.error_sent());
+        assert_eq!(events.lock().unwrap().last().unwrap(), "error: Generation stopped");
+    }
+
+    #[test]
+    fn test_app_events_serialize_payloads() {
+        struct RecordingEvents(std::sync::Mutex<Vec<(String, serde_json::Value)>>);
+
+        impl AppEvents for RecordingEvents {
+            fn emit_event(&self, event: &str, payload: serde_json::Value) {
+                self.0.lock().unwrap().push((event.to_string(), payload));
+            }
+        }
+
+        let recording = RecordingEvents(std::sync::Mutex::new(Vec::new()));
+        let events: &dyn AppEvents = &recording;
+        events.emit_json("ai-usage", AiUsageEvent {
+            provider: "openai".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            prompt_tokens: 12,
+            completion_tokens: 34,
+        });
+        events.emit_json("refresh-required", ());
+
+        let emitted = recording.0.into_inner().unwrap();
+        assert_eq!(emitted[0].0, "ai-usage");
+        assert_eq!(emitted[0].1["completion_tokens"], 34);
+        assert_eq!(emitted[1], ("refresh-required".to_string(), serde_json::Value::Null));
+    }
+
+    #[test]
+    fn test_timing_leaves_out_pauses() {
+        let timing = TimingSink::new(&DiscardSink);
+        let start = timing.started;
+
+        timing.pause(start + Duration::from_secs(1));
+        // A second pause while paused doesn't move the start
+        timing.pause(start + Duration::from_secs(2));
+        assert_eq!(timing.active_time(start + Duration::from_secs(3)), Duration::from_secs(1));
+
+        timing.resume(start + Duration::from_secs(4));
+        assert_eq!(timing.active_time(start + Duration::from_secs(6)), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_model_list_cache_key() {
+        let key = model_list_cache_key(AiProvider::OpenAI, "sk-one");
+        assert!(key.starts_with("openai:"));
+        assert!(!key.contains("sk-one"));
+        assert_eq!(key, model_list_cache_key(AiProvider::OpenAI, "sk-one"));
+        assert_ne!(key, model_list_cache_key(AiProvider::OpenAI, "sk-two"));
+        assert_ne!(key, model_list_cache_key(AiProvider::Groq, "sk-one"));
+    }
+
+    #[tokio::test]
+    async fn test_reset_session_clears_stats() {
+        let dir = TempDir::new("ai-reset-session");
+        let settings = Arc::new(SettingsManager::at_path(dir.join("settings.json")).unwrap());
+        let manager = AiManager::with_active_provider(settings, None);
+
+        let usage = TokenUsage { prompt_tokens: 10, completion_tokens: 5 };
+        manager.record_usage(&NoEvents, AiProvider::OpenAI, "gpt", usage).await;
+        manager
+            .record_latency(AiProvider::OpenAI, LatencySample { first_chunk: None, total: Duration::from_secs(1) })
+            .await;
+        assert_eq!(manager.get_usage_stats().await.len(), 1);
+        assert_eq!(manager.get_latency_stats().await.len(), 1);
+
+        manager.reset_session().await;
+        assert!(manager.get_usage_stats().await.is_empty());
+        assert!(manager.get_latency_stats().await.is_empty());
+    }
+
+    #[test]
+    fn test_sse_lines_wait_for_complete_lines() {
+        let mut lines = SseLines::default();
+        assert!(lines.push(b"data: {\"text\":\"caf").is_empty());
+        // "é" is split between two reads
+        assert!(lines.push(b"\xC3").is_empty());
+        assert_eq!(lines.push(b"\xA9\"}\r\ndata: [DO"), vec!["data: {\"text\":\"café\"}"]);
+        assert_eq!(lines.push(b"NE]\n\n"), vec!["data: [DONE]", ""]);
+        assert_eq!(lines.push(b"data: tail"), Vec::<String>::new());
+        assert_eq!(lines.finish().as_deref(), Some("data: tail"));
+        assert_eq!(lines.finish(), None);
+    }
+}
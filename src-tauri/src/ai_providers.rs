@@ -0,0 +1,717 @@
+//! AI Providers - Request building and SSE parsing for cloud providers
+//!
+//! Each provider turns a prompt into an HTTP request and its streamed lines into
+//! `ProviderEvent`s. The HTTP and SSE plumbing lives in `AiManager::stream`.
+
+use crate::ai_manager::TokenUsage;
+use crate::ai_tools;
+use crate::keyring_store::AiProvider;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Base URL of a provider that speaks the OpenAI chat completions API
+pub fn openai_compatible_base_url(provider: AiProvider) -> Option<&'static str> {
+    match provider {
+        AiProvider::OpenAI => Some("https://api.openai.com/v1"),
+        AiProvider::Groq => Some("https://api.groq.com/openai/v1"),
+        AiProvider::Grok => Some("https://api.x.ai/v1"),
+        AiProvider::Mistral => Some("https://api.mistral.ai/v1"),
+        _ => None,
+    }
+}
+
+/// A tool call requested by the model, possibly assembled from several deltas
+#[derive(Debug, Default)]
+pub struct PendingToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: String,
+}
+
+/// Everything a provider needs to build a generation request
+pub struct RequestParams<'a> {
+    pub model: &'a str,
+    pub api_key: &'a str,
+    pub system_prompt: &'a str,
+    pub prompt: &'a str,
+    pub context: &'a str,
+    /// Include tool definitions (only honoured by providers that support tools)
+    pub tools: bool,
+}
+
+/// A ready-to-send request; `Content-Type: application/json` is added by the driver
+pub struct ProviderRequest {
+    pub url: String,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Value,
+}
+
+/// What one streamed line means to the driver
+/// The driver applies it in field order: error, reasoning, text, truncated, tool calls, done.
+#[derive(Debug, Default)]
+pub struct ProviderEvent {
+    /// Reported mid-stream; ends the stream with an error
+    pub error: Option<String>,
+    /// Chain-of-thought text from reasoning models, kept apart from the answer
+    pub reasoning: Vec<String>,
+    pub text: Vec<String>,
+    pub truncated: bool,
+    /// Tool calls that are complete and should run now
+    pub tool_calls: Vec<PendingToolCall>,
+    /// The generation is complete
+    pub done: bool,
+    /// Token usage to record once done
+    pub usage: Option<TokenUsage>,
+}
+
+/// A cloud provider's wire format
+/// Implementations keep per-stream state (partial tool calls, usage), so create a
+/// fresh one with `for_provider` for every request.
+pub trait Provider: Send {
+    fn build_request(&self, params: &RequestParams) -> ProviderRequest;
+
+    /// Parse the payload of one `data: ` line; None if it means nothing to the driver
+    fn parse_chunk(&mut self, data: &str) -> Option<ProviderEvent>;
+
+    fn supports_tools(&self) -> bool;
+
+    /// Tool calls still collected when the stream ends without a done event
+    fn finish(&mut self) -> Vec<PendingToolCall> {
+        Vec::new()
+    }
+}
+
+/// Create the wire format for a cloud provider, None for local models
+pub fn for_provider(provider: AiProvider) -> Option<Box<dyn Provider>> {
+    match provider {
+        AiProvider::OpenAI | AiProvider::Groq | AiProvider::Grok | AiProvider::Mistral => {
+            Some(Box::new(OpenAiCompatible::new(provider)))
+        }
+        AiProvider::Anthropic => Some(Box::new(Anthropic::default())),
+        AiProvider::Google => Some(Box::new(Google::default())),
+        AiProvider::Cohere => Some(Box::new(Cohere)),
+        _ => None,
+    }
+}
+
+fn user_message(context: &str, prompt: &str) -> String {
+    format!("Context (current card content):\n{}\n\nUser request: {}", context, prompt)
+}
+
+// ============================================================================
+// OpenAI-compatible (OpenAI, Groq, xAI Grok, Mistral)
+// ============================================================================
+
+/// OpenAI chat completions, with tool calling
+pub struct OpenAiCompatible {
+    provider: AiProvider,
+    pending_tools: HashMap<u64, PendingToolCall>,
+    usage: Option<TokenUsage>,
+}
+
+impl OpenAiCompatible {
+    pub fn new(provider: AiProvider) -> Self {
+        Self {
+            provider,
+            pending_tools: HashMap::new(),
+            usage: None,
+        }
+    }
+}
+
+impl Provider for OpenAiCompatible {
+    fn build_request(&self, params: &RequestParams) -> ProviderRequest {
+        let base_url = openai_compatible_base_url(self.provider).unwrap_or_default();
+
+        let mut body = json!({
+            "model": params.model,
+            "messages": [
+                {
+                    "role": "system",
+                    "content": params.system_prompt
+                },
+                {
+                    "role": "user",
+                    "content": user_message(params.context, params.prompt)
+                }
+            ],
+            "stream": true
+        });
+
+        if params.tools {
+            body["tools"] = ai_tools::get_all_tools();
+        }
+
+        // Groq reports usage in `x_groq` on its own; OpenAI and xAI need to be asked for it
+        if matches!(self.provider, AiProvider::OpenAI | AiProvider::Grok) {
+            body["stream_options"] = json!({ "include_usage": true });
+        }
+
+        ProviderRequest {
+            url: format!("{}/chat/completions", base_url),
+            headers: vec![("Authorization", format!("Bearer {}", params.api_key))],
+            body,
+        }
+    }
+
+    fn parse_chunk(&mut self, data: &str) -> Option<ProviderEvent> {
+        if data == "[DONE]" {
+            // Tool calls that finished exactly at the end
+            return Some(ProviderEvent {
+                tool_calls: take_tool_calls(&mut self.pending_tools),
+                done: true,
+                usage: self.usage,
+                ..ProviderEvent::default()
+            });
+        }
+
+        let json: Value = serde_json::from_str(data).ok()?;
+        let mut event = ProviderEvent::default();
+
+        // Final chunk carries usage (OpenAI: requested via stream_options, Groq: x_groq)
+        let chunk_usage = if json["usage"].is_object() {
+            &json["usage"]
+        } else {
+            &json["x_groq"]["usage"]
+        };
+        if chunk_usage.is_object() {
+            self.usage = Some(TokenUsage {
+                prompt_tokens: chunk_usage["prompt_tokens"].as_u64().unwrap_or(0),
+                completion_tokens: chunk_usage["completion_tokens"].as_u64().unwrap_or(0),
+            });
+        }
+
+        let delta = &json["choices"][0]["delta"];
+
+        // 1. Handle reasoning text (DeepSeek-style reasoning_content, Groq: reasoning)
+        if let Some(reasoning) = delta["reasoning_content"].as_str().or_else(|| delta["reasoning"].as_str()) {
+            event.reasoning.push(reasoning.to_string());
+        }
+
+        // 2. Handle normal text content
+        if let Some(content) = delta["content"].as_str() {
+            event.text.push(content.to_string());
+        }
+
+        // 3. Accumulate tool calls; several can stream interleaved, keyed by index
+        if let Some(tool_calls) = delta["tool_calls"].as_array() {
+            accumulate_tool_call_deltas(&mut self.pending_tools, tool_calls);
+        }
+
+        // Check finish_reason to execute the tools
+        match json["choices"][0]["finish_reason"].as_str() {
+            Some("tool_calls") => {
+                event.tool_calls = take_tool_calls(&mut self.pending_tools);
+            }
+            Some("length") => {
+                log::warn!("{} response truncated at the token limit", self.provider.display_name());
+                event.truncated = true;
+            }
+            _ => {}
+        }
+
+        Some(event)
+    }
+
+    fn supports_tools(&self) -> bool {
+        true
+    }
+}
+
+/// Merge streamed OpenAI tool-call deltas into the calls being built, keyed by `index`
+/// `id` and `name` arrive in a call's first delta, `arguments` in pieces after it.
+/// Mistral sends each call whole in a single delta and may leave out `index`, so a
+/// call without one is treated as a new call.
+fn accumulate_tool_call_deltas(pending: &mut HashMap<u64, PendingToolCall>, deltas: &[Value]) {
+    for call in deltas {
+        let index = call["index"].as_u64().unwrap_or(pending.len() as u64);
+        let tool = pending.entry(index).or_default();
+
+        if let Some(id) = call["id"].as_str() {
+            tool.id = id.to_string();
+        }
+        if let Some(name) = call["function"]["name"].as_str() {
+            tool.name.push_str(name);
+        }
+        if let Some(args) = call["function"]["arguments"].as_str() {
+            tool.arguments.push_str(args);
+        }
+    }
+}
+
+/// Take all accumulated tool calls in index order
+fn take_tool_calls(pending: &mut HashMap<u64, PendingToolCall>) -> Vec<PendingToolCall> {
+    let mut calls: Vec<(u64, PendingToolCall)> = pending.drain().collect();
+    calls.sort_by_key(|(index, _)| *index);
+    calls.into_iter().map(|(_, tool)| tool).collect()
+}
+
+// ============================================================================
+// Anthropic
+// ============================================================================
+
+/// Anthropic Messages API (no tool calling)
+#[derive(Default)]
+pub struct Anthropic {
+    usage: TokenUsage,
+}
+
+impl Provider for Anthropic {
+    fn build_request(&self, params: &RequestParams) -> ProviderRequest {
+        let body = json!({
+            "model": params.model,
+            "max_tokens": 4096,
+            "messages": [
+                {
+                    "role": "user",
+                    "content": user_message(params.context, params.prompt)
+                }
+            ],
+            "stream": true,
+            "system": params.system_prompt
+        });
+
+        ProviderRequest {
+            url: "https://api.anthropic.com/v1/messages".to_string(),
+            headers: vec![
+                ("x-api-key", params.api_key.to_string()),
+                ("anthropic-version", "2023-06-01".to_string()),
+            ],
+            body,
+        }
+    }
+
+    fn parse_chunk(&mut self, data: &str) -> Option<ProviderEvent> {
+        let json: Value = serde_json::from_str(data).ok()?;
+
+        match json["type"].as_str().unwrap_or("") {
+            "message_start" => {
+                let message_usage = &json["message"]["usage"];
+                self.usage.prompt_tokens = message_usage["input_tokens"].as_u64().unwrap_or(0);
+                self.usage.completion_tokens = message_usage["output_tokens"].as_u64().unwrap_or(0);
+                None
+            }
+            "message_delta" => {
+                // output_tokens is cumulative
+                if let Some(output_tokens) = json["usage"]["output_tokens"].as_u64() {
+                    self.usage.completion_tokens = output_tokens;
+                }
+                if json["delta"]["stop_reason"].as_str() == Some("max_tokens") {
+                    log::warn!("Anthropic response truncated at max_tokens");
+                    return Some(ProviderEvent {
+                        truncated: true,
+                        ..ProviderEvent::default()
+                    });
+                }
+                None
+            }
+            "content_block_delta" => json["delta"]["text"].as_str().map(|text| ProviderEvent {
+                text: vec![text.to_string()],
+                ..ProviderEvent::default()
+            }),
+            "message_stop" => Some(ProviderEvent {
+                done: true,
+                usage: Some(self.usage),
+                ..ProviderEvent::default()
+            }),
+            "error" => {
+                let error_type = json["error"]["type"].as_str().unwrap_or("error");
+                let message = json["error"]["message"].as_str().unwrap_or("Unknown error");
+                let message = format!("{}: {}", error_type, message);
+                log::error!("Anthropic stream error: {}", message);
+                Some(ProviderEvent {
+                    error: Some(message),
+                    ..ProviderEvent::default()
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn supports_tools(&self) -> bool {
+        false
+    }
+}
+
+// ============================================================================
+// Google Gemini
+// ============================================================================
+
+/// Gemini streamGenerateContent, with function calling
+#[derive(Default)]
+pub struct Google {
+    tool_calls: Vec<PendingToolCall>,
+    usage: Option<TokenUsage>,
+}
+
+impl Provider for Google {
+    fn build_request(&self, params: &RequestParams) -> ProviderRequest {
+        let mut body = json!({
+            "contents": [
+                {
+                    "parts": [
+                        {
+                            "text": format!(
+                                "SYSTEM: {}\n\nContext (current content):\n{}\n\nUser request: {}",
+                                params.system_prompt, params.context, params.prompt
+                            )
+                        }
+                    ]
+                }
+            ]
+        });
+
+        if params.tools {
+            body["tools"] = ai_tools::get_gemini_tools();
+        }
+
+        ProviderRequest {
+            url: format!(
+                "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent?alt=sse",
+                params.model
+            ),
+            headers: vec![("x-goog-api-key", params.api_key.to_string())],
+            body,
+        }
+    }
+
+    fn parse_chunk(&mut self, data: &str) -> Option<ProviderEvent> {
+        let json: Value = serde_json::from_str(data).ok()?;
+        let mut event = ProviderEvent::default();
+
+        // usageMetadata totals are cumulative, the last one wins
+        if json["usageMetadata"].is_object() {
+            self.usage = Some(TokenUsage {
+                prompt_tokens: json["usageMetadata"]["promptTokenCount"].as_u64().unwrap_or(0),
+                completion_tokens: json["usageMetadata"]["candidatesTokenCount"].as_u64().unwrap_or(0),
+            });
+        }
+
+        let parts = json["candidates"][0]["content"]["parts"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+
+        for part in &parts {
+            // 1. Handle normal text content
+            if let Some(text) = part["text"].as_str() {
+                event.text.push(text.to_string());
+            }
+
+            // 2. Collect function calls, executed once the candidate finishes
+            if let Some(call) = part["functionCall"].as_object() {
+                // Gemini sends args as a JSON object, but accept a string too
+                let arguments = match call.get("args") {
+                    Some(Value::String(args)) => args.clone(),
+                    Some(args) => args.to_string(),
+                    None => "{}".to_string(),
+                };
+                self.tool_calls.push(PendingToolCall {
+                    id: call.get("id").and_then(|id| id.as_str()).unwrap_or_default().to_string(),
+                    name: call.get("name").and_then(|n| n.as_str()).unwrap_or_default().to_string(),
+                    arguments,
+                });
+            }
+        }
+
+        if let Some(finish_reason) = json["candidates"][0]["finishReason"].as_str() {
+            if finish_reason == "MAX_TOKENS" {
+                log::warn!("Google response truncated at maxOutputTokens");
+                event.truncated = true;
+            }
+            event.tool_calls = std::mem::take(&mut self.tool_calls);
+            event.done = true;
+            event.usage = self.usage;
+        }
+
+        Some(event)
+    }
+
+    fn supports_tools(&self) -> bool {
+        true
+    }
+
+    fn finish(&mut self) -> Vec<PendingToolCall> {
+        std::mem::take(&mut self.tool_calls)
+    }
+}
+
+// ============================================================================
+// Cohere
+// ============================================================================
+
+/// Cohere Chat API v2 (no tool calling)
+pub struct Cohere;
+
+impl Provider for Cohere {
+    fn build_request(&self, params: &RequestParams) -> ProviderRequest {
+        let body = json!({
+            "model": params.model,
+            "messages": [
+                {
+                    "role": "system",
+                    "content": params.system_prompt
+                },
+                {
+                    "role": "user",
+                    "content": user_message(params.context, params.prompt)
+                }
+            ],
+            "stream": true
+        });
+
+        ProviderRequest {
+            url: "https://api.cohere.com/v2/chat".to_string(),
+            headers: vec![("Authorization", format!("Bearer {}", params.api_key))],
+            body,
+        }
+    }
+
+    fn parse_chunk(&mut self, data: &str) -> Option<ProviderEvent> {
+        let json: Value = serde_json::from_str(data).ok()?;
+
+        match json["type"].as_str().unwrap_or("") {
+            "content-delta" => json["delta"]["message"]["content"]["text"]
+                .as_str()
+                .map(|text| ProviderEvent {
+                    text: vec![text.to_string()],
+                    ..ProviderEvent::default()
+                }),
+            "message-end" => {
+                let delta = &json["delta"];
+                let truncated = delta["finish_reason"].as_str() == Some("MAX_TOKENS");
+                if truncated {
+                    log::warn!("Cohere response truncated at max_tokens");
+                }
+
+                let tokens = &delta["usage"]["tokens"];
+                let usage = tokens.is_object().then(|| TokenUsage {
+                    prompt_tokens: tokens["input_tokens"].as_u64().unwrap_or(0),
+                    completion_tokens: tokens["output_tokens"].as_u64().unwrap_or(0),
+                });
+
+                Some(ProviderEvent {
+                    truncated,
+                    done: true,
+                    usage,
+                    ..ProviderEvent::default()
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn supports_tools(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(tools: bool) -> RequestParams<'static> {
+        RequestParams {
+            model: "test-model",
+            api_key: "sk-test",
+            system_prompt: "Be brief.",
+            prompt: "Hi",
+            context: "Card",
+            tools,
+        }
+    }
+
+    #[test]
+    fn test_build_requests() {
+        let openai = OpenAiCompatible::new(AiProvider::Groq).build_request(&params(false));
+        assert_eq!(openai.url, "https://api.groq.com/openai/v1/chat/completions");
+        assert_eq!(openai.body["messages"][0]["content"], "Be brief.");
+        assert!(openai.body.get("tools").is_none());
+        assert!(openai.body.get("stream_options").is_none());
+
+        let anthropic = Anthropic::default().build_request(&params(false));
+        assert_eq!(anthropic.body["system"], "Be brief.");
+        assert!(anthropic.headers.contains(&("x-api-key", "sk-test".to_string())));
+
+        let google = Google::default().build_request(&params(true));
+        assert!(google.url.ends_with("models/test-model:streamGenerateContent?alt=sse"));
+        assert!(google.headers.contains(&("x-goog-api-key", "sk-test".to_string())));
+        assert!(google.body["tools"].is_array());
+    }
+
+    #[test]
+    fn test_anthropic_events() {
+        let mut anthropic = Anthropic::default();
+
+        let lines = [
+            r#"{ "type": "message_start", "message": { "usage": { "input_tokens": 12, "output_tokens": 1 } } }"#,
+            r#"{ "type": "content_block_delta", "delta": { "type": "text_delta", "text": "Hel" } }"#,
+            r#"{ "type": "ping" }"#,
+            r#"{ "type": "content_block_delta", "delta": { "type": "text_delta", "text": "lo" } }"#,
+            r#"{ "type": "message_delta", "usage": { "output_tokens": 5 } }"#,
+        ];
+        let text: Vec<String> = lines
+            .iter()
+            .filter_map(|line| anthropic.parse_chunk(line))
+            .flat_map(|event| {
+                assert!(!event.done);
+                event.text
+            })
+            .collect();
+        assert_eq!(text, vec!["Hel", "lo"]);
+
+        let stop = anthropic.parse_chunk(r#"{ "type": "message_stop" }"#).unwrap();
+        assert!(stop.done);
+        let usage = stop.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 12);
+        assert_eq!(usage.completion_tokens, 5);
+    }
+
+    #[test]
+    fn test_anthropic_error_and_truncation_events() {
+        let mut anthropic = Anthropic::default();
+
+        let truncated = anthropic
+            .parse_chunk(r#"{ "type": "message_delta", "delta": { "stop_reason": "max_tokens" }, "usage": { "output_tokens": 9 } }"#)
+            .unwrap();
+        assert!(truncated.truncated);
+        assert!(!truncated.done);
+
+        let error = anthropic
+            .parse_chunk(r#"{ "type": "error", "error": { "type": "overloaded_error", "message": "Overloaded" } }"#)
+            .unwrap();
+        assert_eq!(error.error.as_deref(), Some("overloaded_error: Overloaded"));
+    }
+
+    #[test]
+    fn test_openai_done_flushes_tool_calls_and_usage() {
+        let mut openai = OpenAiCompatible::new(AiProvider::OpenAI);
+
+        let lines = [
+            r#"{"choices":[{"delta":{"content":"Sure"}}]}"#,
+            r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_a","function":{"name":"create_note","arguments":"{}"}}]}}]}"#,
+            r#"{"choices":[],"usage":{"prompt_tokens":7,"completion_tokens":3}}"#,
+        ];
+        let text: Vec<String> = lines
+            .iter()
+            .filter_map(|line| openai.parse_chunk(line))
+            .flat_map(|event| event.text)
+            .collect();
+        assert_eq!(text, vec!["Sure"]);
+
+        let done = openai.parse_chunk("[DONE]").unwrap();
+        assert!(done.done);
+        assert_eq!(done.tool_calls.len(), 1);
+        assert_eq!(done.tool_calls[0].id, "call_a");
+        assert_eq!(done.usage.unwrap().prompt_tokens, 7);
+    }
+
+    #[test]
+    fn test_openai_reasoning_kept_apart_from_text() {
+        let mut openai = OpenAiCompatible::new(AiProvider::Groq);
+
+        let thinking = openai
+            .parse_chunk(r#"{"choices":[{"delta":{"content":null,"reasoning_content":"Let me think"}}]}"#)
+            .unwrap();
+        assert_eq!(thinking.reasoning, vec!["Let me think"]);
+        assert!(thinking.text.is_empty());
+
+        let answer = openai
+            .parse_chunk(r#"{"choices":[{"delta":{"content":"Done","reasoning":null}}]}"#)
+            .unwrap();
+        assert!(answer.reasoning.is_empty());
+        assert_eq!(answer.text, vec!["Done"]);
+    }
+
+    #[test]
+    fn test_google_finish_reason_ends_stream() {
+        let mut google = Google::default();
+
+        let call = google
+            .parse_chunk(r#"{"candidates":[{"content":{"parts":[{"functionCall":{"name":"create_note","args":{"content":"A"}}}]}}]}"#)
+            .unwrap();
+        assert!(call.tool_calls.is_empty());
+
+        let finish = google
+            .parse_chunk(r#"{"candidates":[{"content":{"parts":[{"text":"ok"}]},"finishReason":"MAX_TOKENS"}],"usageMetadata":{"promptTokenCount":4,"candidatesTokenCount":2}}"#)
+            .unwrap();
+        assert!(finish.done && finish.truncated);
+        assert_eq!(finish.text, vec!["ok"]);
+        assert_eq!(finish.tool_calls[0].arguments, r#"{"content":"A"}"#);
+        assert_eq!(finish.usage.unwrap().completion_tokens, 2);
+        assert!(google.finish().is_empty());
+    }
+
+    #[test]
+    fn test_cohere_events() {
+        let mut cohere = Cohere;
+
+        let request = cohere.build_request(&params(false));
+        assert_eq!(request.url, "https://api.cohere.com/v2/chat");
+        assert!(request.headers.contains(&("Authorization", "Bearer sk-test".to_string())));
+
+        assert!(cohere.parse_chunk(r#"{"type":"message-start","id":"abc"}"#).is_none());
+        let delta = cohere
+            .parse_chunk(r#"{"type":"content-delta","index":0,"delta":{"message":{"content":{"text":"Hi"}}}}"#)
+            .unwrap();
+        assert_eq!(delta.text, vec!["Hi"]);
+
+        let end = cohere
+            .parse_chunk(r#"{"type":"message-end","delta":{"finish_reason":"COMPLETE","usage":{"tokens":{"input_tokens":6,"output_tokens":2}}}}"#)
+            .unwrap();
+        assert!(end.done && !end.truncated);
+        assert_eq!(end.usage.unwrap().prompt_tokens, 6);
+    }
+
+    #[test]
+    fn test_interleaved_tool_call_deltas() {
+        let mut pending = HashMap::new();
+        let deltas = [
+            json!([{ "index": 0, "id": "call_a", "function": { "name": "create_note", "arguments": "" } }]),
+            json!([{ "index": 1, "id": "call_b", "function": { "name": "create_note", "arguments": "{\"con" } }]),
+            json!([{ "index": 0, "function": { "arguments": "{\"content\":" } }]),
+            json!([{ "index": 1, "function": { "arguments": "tent\":\"B\"}" } }]),
+            json!([{ "index": 0, "function": { "arguments": "\"A\"}" } }]),
+        ];
+        for delta in &deltas {
+            accumulate_tool_call_deltas(&mut pending, delta.as_array().unwrap());
+        }
+
+        let calls = take_tool_calls(&mut pending);
+        assert!(pending.is_empty());
+        let summary: Vec<(&str, &str, &str)> = calls
+            .iter()
+            .map(|c| (c.id.as_str(), c.name.as_str(), c.arguments.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("call_a", "create_note", r#"{"content":"A"}"#),
+                ("call_b", "create_note", r#"{"content":"B"}"#),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mistral_tool_call_deltas_without_index() {
+        let mut pending = HashMap::new();
+        let delta = json!([
+            { "id": "D681PevKs", "function": { "name": "create_note", "arguments": "{\"content\":\"A\"}" } },
+            { "id": "K9zq2LmXp", "function": { "name": "delete_note", "arguments": "{\"id\":\"x\"}" } },
+        ]);
+        accumulate_tool_call_deltas(&mut pending, delta.as_array().unwrap());
+
+        let calls = take_tool_calls(&mut pending);
+        let summary: Vec<(&str, &str, &str)> = calls
+            .iter()
+            .map(|c| (c.id.as_str(), c.name.as_str(), c.arguments.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("D681PevKs", "create_note", r#"{"content":"A"}"#),
+                ("K9zq2LmXp", "delete_note", r#"{"id":"x"}"#),
+            ]
+        );
+    }
+}
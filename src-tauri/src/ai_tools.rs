@@ -43,6 +43,23 @@ pub fn get_all_tools() -> serde_json::Value {
                 }
             }
         },
+        {
+            "type": "function",
+            "function": {
+                "name": "get_note",
+                "description": "Get the full content of a single note card. Use this to read a note before updating it.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "id": {
+                            "type": "string",
+                            "description": "The UUID of the note to read."
+                        }
+                    },
+                    "required": ["id"]
+                }
+            }
+        },
         {
             "type": "function",
             "function": {
@@ -85,10 +102,23 @@ pub fn get_all_tools() -> serde_json::Value {
             "type": "function",
             "function": {
                 "name": "list_notes",
-                "description": "Get a list of all existing notes (id, content, timestamps).",
+                "description": "Get a paginated list of existing notes (id and content preview), optionally filtered by a search query.",
                 "parameters": {
                     "type": "object",
-                    "properties": {},
+                    "properties": {
+                        "query": {
+                            "type": "string",
+                            "description": "Only include notes whose content contains this text (case-insensitive)."
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "Maximum number of notes to return (default 20)."
+                        },
+                        "offset": {
+                            "type": "integer",
+                            "description": "Number of matching notes to skip, for pagination (default 0)."
+                        }
+                    },
                     "required": []
                 }
             }
@@ -96,6 +126,37 @@ pub fn get_all_tools() -> serde_json::Value {
     ])
 }
 
+/// Returns the tools in Gemini's `functionDeclarations` format
+pub fn get_gemini_tools() -> serde_json::Value {
+    let declarations: Vec<serde_json::Value> = get_all_tools()
+        .as_array()
+        .map(|tools| {
+            tools
+                .iter()
+                .map(|tool| {
+                    let function = &tool["function"];
+                    let mut declaration = json!({
+                        "name": function["name"],
+                        "description": function["description"],
+                    });
+
+                    // Gemini rejects object schemas without properties, so omit them
+                    let has_properties = function["parameters"]["properties"]
+                        .as_object()
+                        .is_some_and(|props| !props.is_empty());
+                    if has_properties {
+                        declaration["parameters"] = function["parameters"].clone();
+                    }
+
+                    declaration
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    json!([{ "functionDeclarations": declarations }])
+}
+
 // ============================================================================ 
 // Tool Execution
 // ============================================================================ 
@@ -105,6 +166,11 @@ struct CreateNoteArgs {
     content: String,
 }
 
+#[derive(Deserialize)]
+struct GetNoteArgs {
+    id: String,
+}
+
 #[derive(Deserialize)]
 struct UpdateNoteArgs {
     id: String,
@@ -116,6 +182,87 @@ struct DeleteNoteArgs {
     id: String,
 }
 
+/// Default page size for list_notes to keep tool output compact
+const DEFAULT_LIST_LIMIT: usize = 20;
+
+/// Characters of content shown per note by list_notes
+const LIST_PREVIEW_CHARS: usize = 100;
+
+#[derive(Deserialize, Default)]
+struct ListNotesArgs {
+    #[serde(default)]
+    query: Option<String>,
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    offset: Option<usize>,
+}
+
+/// Find a card by ID (archived cards included)
+fn find_card(id: &str) -> Result<card_manager::Card, String> {
+    card_manager::get_card_by_id(id).map_err(|_| format!("Note {} not found", id))
+}
+
+/// Format the full content of the note with the given ID
+fn format_note(cards: &[card_manager::Card], id: &str) -> Result<String, String> {
+    let card = cards
+        .iter()
+        .find(|c| c.id == id)
+        .ok_or_else(|| format!("Note {} not found", id))?;
+
+    Ok(format!("Note ID: {}\nContent:\n{}", card.id, card.content))
+}
+
+/// Whether a tool only reads notes; these run without asking for confirmation
+pub fn is_read_only(name: &str) -> bool {
+    matches!(name, "get_note" | "list_notes")
+}
+
+/// Describes what a tool call would change without executing it
+pub fn preview_tool(name: &str, arguments: &str) -> Result<String, String> {
+    match name {
+        "create_note" => {
+            let args: CreateNoteArgs = serde_json::from_str(arguments)
+                .map_err(|e| format!("Invalid arguments for create_note: {}", e))?;
+
+            Ok(format!(
+                "Would create a new note titled \"{}\"",
+                card_manager::extract_title_from_content(&args.content)
+            ))
+        }
+        "update_note" => {
+            let args: UpdateNoteArgs = serde_json::from_str(arguments)
+                .map_err(|e| format!("Invalid arguments for update_note: {}", e))?;
+            let card = find_card(&args.id)?;
+
+            Ok(format!(
+                "Would update note {} titled \"{}\"",
+                args.id,
+                card_manager::extract_title_from_content(&card.content)
+            ))
+        }
+        "delete_note" => {
+            let args: DeleteNoteArgs = serde_json::from_str(arguments)
+                .map_err(|e| format!("Invalid arguments for delete_note: {}", e))?;
+            let card = find_card(&args.id)?;
+
+            Ok(format!(
+                "Would delete note {} titled \"{}\"",
+                args.id,
+                card_manager::extract_title_from_content(&card.content)
+            ))
+        }
+        "get_note" => {
+            let args: GetNoteArgs = serde_json::from_str(arguments)
+                .map_err(|e| format!("Invalid arguments for get_note: {}", e))?;
+
+            Ok(format!("Would read note {} (read-only)", args.id))
+        }
+        "list_notes" => Ok("Would list existing notes (read-only)".to_string()),
+        _ => Err(format!("Unknown tool: {}", name)),
+    }
+}
+
 /// Executes a tool call and returns the result as a string
 pub fn execute_tool(name: &str, arguments: &str) -> Result<String, String> {
     match name {
@@ -128,6 +275,14 @@ pub fn execute_tool(name: &str, arguments: &str) -> Result<String, String> {
             
             Ok(format!("Note created successfully. ID: {}", card.id))
         }
+        "get_note" => {
+            let args: GetNoteArgs = serde_json::from_str(arguments)
+                .map_err(|e| format!("Invalid arguments for get_note: {}", e))?;
+
+            let card = find_card(&args.id)?;
+
+            format_note(std::slice::from_ref(&card), &args.id)
+        }
         "update_note" => {
             let args: UpdateNoteArgs = serde_json::from_str(arguments)
                 .map_err(|e| format!("Invalid arguments for update_note: {}", e))?;
@@ -147,20 +302,131 @@ pub fn execute_tool(name: &str, arguments: &str) -> Result<String, String> {
             Ok(format!("Note {} deleted successfully.", args.id))
         }
         "list_notes" => {
+            // Arguments are optional; some models send an empty string
+            let args: ListNotesArgs = if arguments.trim().is_empty() {
+                ListNotesArgs::default()
+            } else {
+                serde_json::from_str(arguments)
+                    .map_err(|e| format!("Invalid arguments for list_notes: {}", e))?
+            };
+
             let cards = card_manager::get_all_cards()
                 .map_err(|e| format!("Failed to list cards: {}", e))?;
-            
+
+            let query = args
+                .query
+                .map(|q| q.trim().to_lowercase())
+                .filter(|q| !q.is_empty());
+            let matching: Vec<_> = cards
+                .into_iter()
+                .filter(|card| {
+                    query
+                        .as_ref()
+                        .is_none_or(|q| card.content.to_lowercase().contains(q))
+                })
+                .collect();
+
+            let total = matching.len();
+            let offset = args.offset.unwrap_or(0);
+            let limit = args.limit.unwrap_or(DEFAULT_LIST_LIMIT).max(1);
+            let page: Vec<_> = matching.into_iter().skip(offset).take(limit).collect();
+
             // Format a concise list for the LLM
             let mut output = String::from("Current Notes:\n");
-            if cards.is_empty() {
-                output.push_str("(No notes found)");
+            if page.is_empty() {
+                output.push_str("(No notes found)\n");
             } else {
-                for card in cards {
-                    output.push_str(&format!("- ID: {}\n  Content (preview): {:.100}...\n", card.id, card.content.replace('\n', " ")));
+                for card in &page {
+                    let content = card.content.replace('\n', " ");
+                    let preview = card_manager::truncate_chars(&content, LIST_PREVIEW_CHARS);
+                    let ellipsis = if preview.len() < content.len() { "..." } else { "" };
+                    output.push_str(&format!("- ID: {}\n  Content (preview): {}{}\n", card.id, preview, ellipsis));
                 }
             }
+
+            let shown_from = if page.is_empty() { 0 } else { offset + 1 };
+            output.push_str(&format!(
+                "Showing {}-{} of {} matching notes.",
+                shown_from,
+                offset + page.len(),
+                total
+            ));
+            if offset + page.len() < total {
+                output.push_str(&format!(" Use offset {} to see more.", offset + page.len()));
+            }
             Ok(output)
         }
         _ => Err(format!("Unknown tool: {}", name)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card_manager::Card;
+
+    fn sample_card(id: &str, content: &str) -> Card {
+        Card {
+            id: id.to_string(),
+            content: content.to_string(),
+            created_at: 0,
+            updated_at: 0,
+            word_count: 0,
+            char_count: 0,
+            pinned: false,
+            color: None,
+            locked: false,
+            archived: false,
+            tags: Vec::new(),
+            content_hash: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_get_note_returns_full_content() {
+        let cards = vec![sample_card("a", "# Shopping\n- milk\n- eggs")];
+        let output = format_note(&cards, "a").unwrap();
+        assert!(output.contains("- milk\n- eggs"));
+    }
+
+    #[test]
+    fn test_get_note_missing_id() {
+        let cards = vec![sample_card("a", "content")];
+        let err = format_note(&cards, "missing").unwrap_err();
+        assert!(err.contains("missing"));
+    }
+
+    #[test]
+    fn test_get_note_requires_id_argument() {
+        assert!(execute_tool("get_note", "{}").is_err());
+    }
+
+    #[test]
+    fn test_execute_get_note() {
+        // The only test that touches the global card list, so it can point it at a temp dir
+        let dir = crate::test_util::TempDir::new("ai-tools-cards");
+        card_manager::set_custom_cards_directory(Some(dir.to_path_buf()));
+        let card = card_manager::create_card("# Shopping\n- milk\n- eggs".to_string()).unwrap();
+
+        let arguments = serde_json::json!({ "id": card.id }).to_string();
+        assert_eq!(
+            execute_tool("get_note", &arguments).unwrap(),
+            format!("Note ID: {}\nContent:\n# Shopping\n- milk\n- eggs", card.id)
+        );
+
+        let err = execute_tool("get_note", r#"{"id": "missing"}"#).unwrap_err();
+        assert_eq!(err, "Note missing not found");
+
+        let err = execute_tool("get_note", r#"{"id": 5}"#).unwrap_err();
+        assert!(err.starts_with("Invalid arguments for get_note"));
+    }
+
+    #[test]
+    fn test_only_reading_tools_are_read_only() {
+        assert!(is_read_only("get_note"));
+        assert!(is_read_only("list_notes"));
+        for name in ["create_note", "update_note", "delete_note", "unknown_tool"] {
+            assert!(!is_read_only(name), "{} must ask for confirmation", name);
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! App Log - Application logging to stderr and a log file
+//!
+//! Everything logged goes to stderr as before and to `hexstickynote.log` in the
+//! data directory. The log is rotated to `hexstickynote.log.1` on every start and
+//! whenever it grows past `MAX_LOG_FILE_BYTES`, so at most two files are kept. The
+//! level can be changed at runtime: the logger lets this crate's records through
+//! at every level (dependencies up to info) and `log::set_max_level` does the
+//! filtering. When `RUST_LOG` is set it takes precedence and the level can't be
+//! changed at runtime.
+
+use directories::ProjectDirs;
+use log::LevelFilter;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AppLogError {
+    #[error("Failed to access log file: {0}")]
+    IoError(String),
+    #[error("Invalid log level: {0} (expected error, warn, info, debug or trace)")]
+    InvalidLevel(String),
+    #[error("Log level is controlled by RUST_LOG")]
+    EnvOverride,
+}
+
+/// Level used when neither RUST_LOG nor a saved level is set
+pub const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// Most verbose level for other crates' records (HTTP, windowing), which flood
+/// the log at debug and trace
+const DEPENDENCY_LOG_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// Size at which the log file is rotated
+const MAX_LOG_FILE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Get the path of the log file
+pub fn get_log_path() -> Result<PathBuf, AppLogError> {
+    let proj_dirs = ProjectDirs::from("com", "HexStickyNote", "HexStickyNote")
+        .ok_or_else(|| AppLogError::IoError("Failed to determine project directories".to_string()))?;
+
+    let data_dir = proj_dirs.data_dir();
+    fs::create_dir_all(data_dir).map_err(|e| AppLogError::IoError(e.to_string()))?;
+
+    Ok(data_dir.join("hexstickynote.log"))
+}
+
+/// The log file, moved aside to `<name>.1` once it reaches `max_bytes`
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+}
+
+impl LogFile {
+    /// Start a new log at `path`, keeping the previous one as the backup
+    fn create(path: PathBuf, max_bytes: u64) -> io::Result<Self> {
+        rotate(&path);
+        let file = File::create(&path)?;
+        Ok(Self {
+            path,
+            file,
+            written: 0,
+            max_bytes,
+        })
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.file.flush().ok();
+            rotate(&self.path);
+            self.file = File::create(&self.path)?;
+            self.written = 0;
+        }
+        self.file.write_all(buf)?;
+        self.written += buf.len() as u64;
+        Ok(())
+    }
+}
+
+/// Path of the rotated log, e.g. `hexstickynote.log.1`
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+/// Replace the backup with the current log, if there is one
+fn rotate(path: &Path) {
+    if path.exists() {
+        fs::rename(path, backup_path(path)).ok();
+    }
+}
+
+/// Writes each log line to stderr and, when it could be opened, the log file
+struct TeeWriter {
+    file: Option<LogFile>,
+}
+
+impl Write for TeeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::stderr().write_all(buf)?;
+        if let Some(file) = self.file.as_mut() {
+            // A full disk shouldn't take stderr logging down with it
+            file.write_all(buf).ok();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()?;
+        if let Some(file) = self.file.as_mut() {
+            file.file.flush().ok();
+        }
+        Ok(())
+    }
+}
+
+/// Whether RUST_LOG decides the filtering
+fn env_override() -> bool {
+    std::env::var_os("RUST_LOG").is_some_and(|v| !v.is_empty())
+}
+
+/// Parse a level name ("off", "error", "warn", "info", "debug", "trace")
+pub fn parse_level(level: &str) -> Result<LevelFilter, AppLogError> {
+    LevelFilter::from_str(level.trim()).map_err(|_| AppLogError::InvalidLevel(level.to_string()))
+}
+
+/// Set up logging; call once at startup before anything is logged
+pub fn init() {
+    let file = get_log_path()
+        .and_then(|path| {
+            LogFile::create(path, MAX_LOG_FILE_BYTES).map_err(|e| AppLogError::IoError(e.to_string()))
+        })
+        .map_err(|e| eprintln!("Logging to stderr only: {}", e))
+        .ok();
+
+    let mut builder = env_logger::Builder::new();
+    if env_override() {
+        builder.parse_env("RUST_LOG");
+    } else {
+        builder
+            .filter_level(DEPENDENCY_LOG_LEVEL)
+            .filter_module(env!("CARGO_CRATE_NAME"), LevelFilter::Trace);
+    }
+    builder
+        .target(env_logger::Target::Pipe(Box::new(TeeWriter { file })))
+        .init();
+
+    if !env_override() {
+        log::set_max_level(DEFAULT_LOG_LEVEL);
+    }
+}
+
+/// Change the log level at runtime
+pub fn set_level(level: LevelFilter) -> Result<(), AppLogError> {
+    if env_override() {
+        return Err(AppLogError::EnvOverride);
+    }
+
+    log::set_max_level(level);
+    log::info!("Log level set to {}", level);
+    Ok(())
+}
+
+/// Apply the log level saved in the settings, or the default when there is none
+/// Does nothing when RUST_LOG is set.
+pub fn apply_saved_level(level: Option<&str>) {
+    let filter = match level.map(parse_level) {
+        Some(Ok(filter)) => filter,
+        Some(Err(e)) => {
+            log::warn!("{}", e);
+            DEFAULT_LOG_LEVEL
+        }
+        None => DEFAULT_LOG_LEVEL,
+    };
+    set_level(filter).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(parse_level("debug").unwrap(), LevelFilter::Debug);
+        assert_eq!(parse_level(" WARN ").unwrap(), LevelFilter::Warn);
+        assert_eq!(parse_level("off").unwrap(), LevelFilter::Off);
+        assert!(parse_level("verbose").is_err());
+    }
+
+    #[test]
+    fn test_log_file_rotates_at_size_cap() {
+        let dir = crate::test_util::TempDir::new("app-log");
+        let path = dir.join("test.log");
+        fs::write(&path, "previous session\n").unwrap();
+
+        let mut log = LogFile::create(path.clone(), 16).unwrap();
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "previous session\n");
+
+        log.write_all(b"first line\n").unwrap();
+        log.write_all(b"second line\n").unwrap();
+        log.file.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second line\n");
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "first line\n");
+    }
+}
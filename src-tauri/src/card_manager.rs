@@ -6,21 +6,97 @@
 use directories::ProjectDirs;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Condvar, Mutex, MutexGuard, RwLock};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 // ============================================================================
 // Types
 // ============================================================================
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Card {
     pub id: String,
     pub content: String,
     pub created_at: i64,
     pub updated_at: i64,
+    /// Number of whitespace-separated words (derived from content, not stored)
+    #[serde(default)]
+    pub word_count: usize,
+    /// Number of characters (derived from content, not stored)
+    #[serde(default)]
+    pub char_count: usize,
+    /// Pinned cards are listed first
+    #[serde(default)]
+    pub pinned: bool,
+    /// Card color as hex (#rrggbb) or a named color
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Locked cards can't be edited or deleted (by the AI or otherwise) until unlocked
+    #[serde(default)]
+    pub locked: bool,
+    /// Archived cards are left out of the main list without being deleted or trashed
+    #[serde(default)]
+    pub archived: bool,
+    /// Tags without the leading '#', in the order they were added
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// SHA-256 of the markdown body as hex (derived from content, not stored)
+    #[serde(default)]
+    pub content_hash: String,
+}
+
+impl Card {
+    /// Recompute the derived word/character counts and content hash from the content
+    fn refresh_derived(&mut self) {
+        self.word_count = self.content.split_whitespace().count();
+        self.char_count = self.content.chars().count();
+        self.content_hash = content_hash(&self.content);
+    }
+}
+
+/// Stable hash of a card's markdown body, used to detect changes made outside the app
+pub fn content_hash(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Aggregate statistics over all cards
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CardStats {
+    pub total_cards: usize,
+    pub total_words: usize,
+    pub average_words: f64,
+    /// Oldest `created_at` (None when there are no cards)
+    pub oldest_created_at: Option<i64>,
+    /// Newest `created_at` (None when there are no cards)
+    pub newest_created_at: Option<i64>,
+    /// Cards with `updated_at` within the last 7 days
+    pub modified_last_7_days: usize,
+}
+
+impl CardStats {
+    fn from_cards(cards: &[Card], now: i64) -> Self {
+        let total_words: usize = cards.iter().map(|c| c.word_count).sum();
+        let week_ago = now - 7 * 24 * 60 * 60;
+
+        Self {
+            total_cards: cards.len(),
+            total_words,
+            average_words: if cards.is_empty() {
+                0.0
+            } else {
+                total_words as f64 / cards.len() as f64
+            },
+            oldest_created_at: cards.iter().map(|c| c.created_at).min(),
+            newest_created_at: cards.iter().map(|c| c.created_at).max(),
+            modified_last_7_days: cards.iter().filter(|c| c.updated_at >= week_ago).count(),
+        }
+    }
 }
 
 // Persistent storage with markdown files
@@ -32,46 +108,547 @@ static CARDS: Lazy<Mutex<Vec<Card>>> = Lazy::new(|| {
     Mutex::new(cards)
 });
 
+/// Lock a mutex, recovering the data if a thread panicked while holding it
+/// The card list is only replaced or edited in place, so it's still valid after a panic.
+fn lock_or_recover<'a, T>(mutex: &'a Mutex<T>, name: &str) -> MutexGuard<'a, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        log::warn!("Recovering poisoned {} lock", name);
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+/// Lock the global card list
+fn lock_cards() -> MutexGuard<'static, Vec<Card>> {
+    lock_or_recover(&CARDS, "cards")
+}
+
+/// Idle time after the last edit of a card before it is written to disk
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Longest wait between retries of a card write that keeps failing
+const SAVE_RETRY_MAX: Duration = Duration::from_secs(60);
+
+/// A card edited in memory but not yet written
+#[derive(Debug, Clone, Copy)]
+struct PendingSave {
+    due: Instant,
+    /// Failed attempts so far; each one doubles the wait before the next
+    failures: u32,
+}
+
+// Cards waiting to be written, by id
+static PENDING_SAVES: Lazy<(Mutex<HashMap<String, PendingSave>>, Condvar)> =
+    Lazy::new(|| (Mutex::new(HashMap::new()), Condvar::new()));
+// Whether the background save thread is running
+static SAVE_FLUSHER: Mutex<bool> = Mutex::new(false);
+
+/// A background card write that failed and will be retried
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SaveFailure {
+    pub id: String,
+    pub error: String,
+    /// Failed attempts so far
+    pub attempts: u32,
+    pub retry_in_ms: u64,
+}
+
+type SaveFailedHandler = Box<dyn Fn(&SaveFailure) + Send + Sync>;
+
+// Called for every failed background write (set from main.rs to emit `save-failed`)
+static SAVE_FAILED_HANDLER: Lazy<RwLock<Option<SaveFailedHandler>>> = Lazy::new(|| RwLock::new(None));
+
+/// Subdirectory of the cards directory holding per-card attachment folders
+const ATTACHMENTS_DIR: &str = "attachments";
+
+// Custom cards directory from settings (None = default data directory)
+static CUSTOM_CARDS_DIR: Lazy<RwLock<Option<PathBuf>>> = Lazy::new(|| RwLock::new(None));
+
+/// Title used for cards without a heading or text, unless one is configured
+const FALLBACK_CARD_TITLE: &str = "Note";
+
+// Configured fallback title from settings (None = FALLBACK_CARD_TITLE)
+static DEFAULT_CARD_TITLE: Lazy<RwLock<Option<String>>> = Lazy::new(|| RwLock::new(None));
+
+/// Size cap on the cards directory, counting attachments
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StorageLimit {
+    max_bytes: u64,
+    /// Refuse writes that grow the directory past the cap (otherwise only warn)
+    block_writes: bool,
+}
+
+/// Size of the cards directory compared to its cap
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct StorageUsage {
+    pub used_bytes: u64,
+    pub max_bytes: u64,
+}
+
+// Configured storage cap from settings (None = unlimited)
+static STORAGE_LIMIT: Lazy<RwLock<Option<StorageLimit>>> = Lazy::new(|| RwLock::new(None));
+
+// Size of the cards directory in bytes, measured once and then adjusted by the writes
+// made here (None = measure again on next use, e.g. after cards were reloaded)
+static STORAGE_USED: Mutex<Option<(PathBuf, u64)>> = Mutex::new(None);
+
+/// Sidecar file in the cards directory holding the metadata of cards stored
+/// without front matter, keyed by file name
+const CARD_INDEX_FILE: &str = ".index.json";
+
+/// How long file events for a card file written here are taken to be our own
+const OWN_WRITE_WINDOW: Duration = Duration::from_secs(2);
+
+// File names in the cards directory this module just wrote, renamed or deleted, so the
+// card watcher can tell its own saves from outside edits
+static RECENT_WRITES: Lazy<Mutex<HashMap<std::ffi::OsString, Instant>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Whether card files are written with YAML front matter (otherwise metadata goes to
+// CARD_INDEX_FILE); from settings, on by default
+static STORE_FRONTMATTER: Lazy<RwLock<bool>> = Lazy::new(|| RwLock::new(true));
+
 // ============================================================================
 // File Storage Functions
 // ============================================================================
 
-/// Metadata stored in YAML front matter
-#[derive(Debug, Serialize, Deserialize)]
+/// Metadata stored in YAML front matter, or in the sidecar index
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct CardMetadata {
     id: String,
+    #[serde(with = "card_timestamp")]
     created_at: i64,
+    #[serde(with = "card_timestamp")]
     updated_at: i64,
+    #[serde(default)]
+    pinned: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    locked: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    archived: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty", deserialize_with = "card_tags::deserialize")]
+    tags: Vec<String>,
 }
 
-/// Get the directory where cards are stored
-pub fn get_cards_directory() -> Result<PathBuf, String> {
+/// Front matter tags: written as a list, read as a list or as a comma or space
+/// separated string the way other markdown apps write them
+mod card_tags {
+    use serde::{Deserialize, Deserializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawTags {
+        List(Vec<serde_yaml::Value>),
+        Text(String),
+        Other(serde_yaml::Value),
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+        let tags: Vec<String> = match RawTags::deserialize(deserializer)? {
+            RawTags::List(values) => values
+                .into_iter()
+                .filter_map(|value| match value {
+                    serde_yaml::Value::String(text) => Some(text),
+                    serde_yaml::Value::Number(n) => Some(n.to_string()),
+                    _ => None,
+                })
+                .collect(),
+            RawTags::Text(text) => text.split([',', ' ']).map(str::to_string).collect(),
+            RawTags::Other(_) => Vec::new(),
+        };
+        Ok(tags
+            .iter()
+            .filter_map(|tag| super::normalize_tag(tag))
+            .collect())
+    }
+}
+
+/// Front matter timestamps: written as RFC3339 so the files stay readable in an
+/// editor, read as RFC3339 or as the epoch seconds older versions wrote
+mod card_timestamp {
+    use chrono::{DateTime, SecondsFormat, Utc};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    /// Format epoch seconds as an RFC3339 UTC timestamp (e.g. "2024-05-01T12:00:00Z")
+    pub fn format(timestamp: i64) -> Option<String> {
+        DateTime::<Utc>::from_timestamp(timestamp, 0)
+            .map(|dt| dt.to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+
+    /// Parse an RFC3339 timestamp or a string of epoch seconds
+    pub fn parse(text: &str) -> Option<i64> {
+        let text = text.trim();
+        DateTime::parse_from_rfc3339(text)
+            .map(|dt| dt.timestamp())
+            .ok()
+            .or_else(|| text.parse().ok())
+    }
+
+    pub fn serialize<S: Serializer>(timestamp: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+        match format(*timestamp) {
+            Some(text) => serializer.serialize_str(&text),
+            None => serializer.serialize_i64(*timestamp),
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawTimestamp {
+        Epoch(i64),
+        Text(String),
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+        match RawTimestamp::deserialize(deserializer)? {
+            RawTimestamp::Epoch(timestamp) => Ok(timestamp),
+            RawTimestamp::Text(text) => parse(&text)
+                .ok_or_else(|| de::Error::custom(format!("invalid timestamp: {}", text))),
+        }
+    }
+}
+
+/// Trim a tag and drop its leading '#'; None if nothing is left
+fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag.trim();
+    let tag = tag.strip_prefix('#').unwrap_or(tag).trim();
+    (!tag.is_empty()).then(|| tag.to_string())
+}
+
+/// A front matter timestamp value in the format `CardMetadata` writes
+fn timestamp_value(timestamp: i64) -> serde_yaml::Value {
+    card_timestamp::format(timestamp).map_or_else(|| timestamp.into(), Into::into)
+}
+
+/// Set a custom cards directory (None restores the default location)
+/// Must be called before the cards are first accessed to take effect on startup.
+pub fn set_custom_cards_directory(path: Option<PathBuf>) {
+    let mut custom = CUSTOM_CARDS_DIR.write().unwrap_or_else(|e| e.into_inner());
+    *custom = path;
+}
+
+/// Set the callback told about background card writes that failed
+pub fn set_save_failed_handler(handler: impl Fn(&SaveFailure) + Send + Sync + 'static) {
+    *SAVE_FAILED_HANDLER.write().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(handler));
+}
+
+/// Set whether card files carry YAML front matter (see `change_frontmatter_storage`)
+/// Must be called before the cards are first accessed to take effect on startup.
+pub fn set_store_frontmatter(enabled: bool) {
+    *STORE_FRONTMATTER.write().unwrap_or_else(|e| e.into_inner()) = enabled;
+}
+
+fn store_frontmatter() -> bool {
+    *STORE_FRONTMATTER.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Set the title given to cards without a heading or text (None restores "Note")
+/// Existing files of untitled cards keep their names; only new cards use the new title.
+pub fn set_default_card_title(title: Option<String>) {
+    let mut default_title = DEFAULT_CARD_TITLE.write().unwrap_or_else(|e| e.into_inner());
+    *default_title = title.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+}
+
+/// Set the storage cap on the cards directory in megabytes (None = unlimited)
+/// With `block_writes` writes that would exceed it fail; otherwise they go through
+/// and callers can report `storage_limit_exceeded`.
+pub fn set_storage_limit(max_mb: Option<u64>, block_writes: bool) {
+    let limit = max_mb.map(|mb| StorageLimit {
+        max_bytes: mb.saturating_mul(1024 * 1024),
+        block_writes,
+    });
+    *STORAGE_LIMIT.write().unwrap_or_else(|e| e.into_inner()) = limit;
+}
+
+/// Total size of the files under a directory; unreadable entries count as empty
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Size of the cards directory, from the cache when it has been measured
+fn storage_used(cards_dir: &Path) -> u64 {
+    let mut used = lock_or_recover(&STORAGE_USED, "storage usage");
+    match &*used {
+        Some((dir, bytes)) if dir == cards_dir => *bytes,
+        _ => {
+            let bytes = dir_size(cards_dir);
+            *used = Some((cards_dir.to_path_buf(), bytes));
+            bytes
+        }
+    }
+}
+
+/// Account for a file changing size from `old` to `new` bytes
+/// Files outside the measured cards directory don't count.
+fn adjust_storage_used(path: &Path, old: u64, new: u64) {
+    if let Some((dir, used)) = lock_or_recover(&STORAGE_USED, "storage usage").as_mut() {
+        if path.starts_with(dir) {
+            *used = used.saturating_sub(old).saturating_add(new);
+        }
+    }
+}
+
+/// Measure the cards directory again on next use, after changes not tracked one by one
+fn invalidate_storage_used() {
+    *lock_or_recover(&STORAGE_USED, "storage usage") = None;
+}
+
+/// Size of a file in bytes, 0 if it doesn't exist
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map_or(0, |meta| meta.len())
+}
+
+/// Remember that the app itself is about to change this file
+fn note_own_write(path: &Path) {
+    if let Some(name) = path.file_name() {
+        let now = Instant::now();
+        let mut writes = lock_or_recover(&RECENT_WRITES, "recent writes");
+        writes.retain(|_, at| now.duration_since(*at) < OWN_WRITE_WINDOW);
+        writes.insert(name.to_os_string(), now);
+    }
+}
+
+/// Whether a file event for this path is likely caused by the app's own write
+/// Used by the card watcher to skip reloading after every save.
+pub fn is_own_write(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| {
+        lock_or_recover(&RECENT_WRITES, "recent writes")
+            .get(name)
+            .is_some_and(|at| at.elapsed() < OWN_WRITE_WINDOW)
+    })
+}
+
+/// Current size of the cards directory if a cap is set and it's exceeded
+pub fn storage_limit_exceeded() -> Option<StorageUsage> {
+    let limit = (*STORAGE_LIMIT.read().unwrap_or_else(|e| e.into_inner()))?;
+    let used_bytes = storage_used(&get_cards_directory().ok()?);
+    (used_bytes > limit.max_bytes).then_some(StorageUsage {
+        used_bytes,
+        max_bytes: limit.max_bytes,
+    })
+}
+
+/// Refuse a write that adds `growth` bytes when it would exceed a blocking cap
+/// Writes that don't grow the directory are always allowed, so cards can still be
+/// trimmed once the cap is reached.
+fn check_storage_limit(growth: u64) -> Result<(), String> {
+    let Some(limit) = *STORAGE_LIMIT.read().unwrap_or_else(|e| e.into_inner()) else {
+        return Ok(());
+    };
+    if !limit.block_writes || growth == 0 {
+        return Ok(());
+    }
+
+    let used = storage_used(&get_cards_directory()?);
+    if used + growth > limit.max_bytes {
+        return Err(format!(
+            "Cards storage limit reached ({:.1} of {} MB used); delete cards or attachments to free space",
+            used as f64 / (1024.0 * 1024.0),
+            limit.max_bytes / (1024 * 1024)
+        ));
+    }
+    Ok(())
+}
+
+/// Title for cards without a heading or text
+fn default_card_title() -> String {
+    DEFAULT_CARD_TITLE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| FALLBACK_CARD_TITLE.to_string())
+}
+
+/// Get the default cards directory inside the app data directory
+fn get_default_cards_directory() -> Result<PathBuf, String> {
     let proj_dirs = ProjectDirs::from("com", "HexStickyNote", "HexStickyNote")
         .ok_or("Failed to determine project directories")?;
 
-    let cards_dir = proj_dirs.data_dir().join("cards");
+    Ok(proj_dirs.data_dir().join("cards"))
+}
+
+/// Get the directory where cards are stored
+pub fn get_cards_directory() -> Result<PathBuf, String> {
+    let custom = CUSTOM_CARDS_DIR
+        .read()
+        .map_err(|e| e.to_string())?
+        .clone();
+
+    let cards_dir = match custom {
+        Some(dir) => dir,
+        None => get_default_cards_directory()?,
+    };
     fs::create_dir_all(&cards_dir)
         .map_err(|e| format!("Failed to create cards directory: {}", e))?;
 
     Ok(cards_dir)
 }
 
-/// Extract title from markdown content (first # heading or first meaningful line)
-fn extract_title_from_content(content: &str) -> String {
-    // 1. Look for first h1 (# Title)
-    for line in content.lines() {
-        let trimmed = line.trim();
-        if trimmed.starts_with("# ") {
-            let title = trimmed.trim_start_matches("# ").trim();
-            if !title.is_empty() {
-                return title.to_string();
+/// Ensure a directory exists and is writable by writing a probe file
+fn validate_writable_directory(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create directory {:?}: {}", dir, e))?;
+
+    let probe = dir.join(format!(".hexstickynote-write-test-{}", Uuid::new_v4()));
+    fs::write(&probe, b"")
+        .map_err(|e| format!("Directory {:?} is not writable: {}", dir, e))?;
+    let _ = fs::remove_file(&probe);
+
+    Ok(())
+}
+
+/// Move a file, falling back to copy + delete when the paths are on different drives
+/// If the original can't be deleted, the copy is removed again so the file isn't doubled.
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    if let Err(e) = fs::remove_file(from) {
+        let _ = fs::remove_file(to);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Move all card files from one directory to another
+/// Falls back to copy + delete when the directories are on different drives. If a
+/// file can't be moved, the files moved so far are moved back and both indexes are
+/// restored, so a failed migration leaves the old directory as it was.
+fn migrate_card_files(from: &Path, to: &Path) -> Result<usize, String> {
+    let from_index = read_card_index(from);
+    let to_index = read_card_index(to);
+    let mut moved: Vec<(PathBuf, PathBuf)> = Vec::new();
+
+    let result = move_card_files(from, to, from_index.clone(), to_index.clone(), &mut moved);
+    if let Err(e) = &result {
+        log::warn!("Migrating cards to {:?} failed, moving {} files back: {}", to, moved.len(), e);
+        for (source, target) in moved.iter().rev() {
+            if let Err(e) = move_file(target, source) {
+                log::error!("Failed to move {:?} back to {:?}: {}", target, source, e);
             }
-        } else if trimmed.starts_with('#') {
-            // Handle #Title (no space)
+        }
+        if let Err(e) = write_card_index(from, &from_index).and_then(|_| write_card_index(to, &to_index)) {
+            log::error!("Failed to restore the card index after a failed migration: {}", e);
+        }
+    }
+    result
+}
+
+/// The moves of `migrate_card_files`; each moved file is recorded in `moved`
+fn move_card_files(
+    from: &Path,
+    to: &Path,
+    mut from_index: BTreeMap<String, CardMetadata>,
+    mut to_index: BTreeMap<String, CardMetadata>,
+    moved: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<usize, String> {
+    let entries = fs::read_dir(from)
+        .map_err(|e| format!("Failed to read cards directory: {}", e))?;
+    let indexed_before = from_index.len();
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("md") {
+            continue;
+        }
+
+        let base_name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Untitled".to_string());
+        let target = to.join(get_unique_filename(to, &base_name, None));
+
+        move_file(&path, &target).map_err(|e| format!("Failed to move {:?}: {}", path, e))?;
+        if let Some(metadata) = from_index.remove(&index_key(&path)) {
+            to_index.insert(index_key(&target), metadata);
+        }
+        moved.push((path, target));
+    }
+
+    if from_index.len() != indexed_before {
+        write_card_index(to, &to_index)?;
+        write_card_index(from, &from_index)?;
+    }
+
+    log::info!("Migrated {} card files from {:?} to {:?}", moved.len(), from, to);
+    Ok(moved.len())
+}
+
+/// Split a leading YAML front matter block off the content
+/// Returns the `title` field (if any) and the remaining markdown.
+fn split_title_front_matter(content: &str) -> (Option<String>, &str) {
+    let Some(rest) = content.strip_prefix("---\n") else {
+        return (None, content);
+    };
+    let Some(end_pos) = rest.find("\n---") else {
+        return (None, content);
+    };
+
+    // Only a YAML mapping counts as front matter; anything else is a thematic break
+    let Ok(serde_yaml::Value::Mapping(yaml)) = serde_yaml::from_str(&rest[..end_pos]) else {
+        return (None, content);
+    };
+
+    let body = rest[end_pos + 4..].trim_start_matches('\n');
+    let title = yaml
+        .get("title")
+        .and_then(|t| t.as_str())
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty());
+
+    (title, body)
+}
+
+/// Check whether a line is a setext heading underline (`===` for H1, `---` for H2)
+fn is_setext_underline(line: &str) -> bool {
+    let trimmed = line.trim();
+    (!trimmed.is_empty() && trimmed.chars().all(|c| c == '='))
+        || (trimmed.len() >= 2 && trimmed.chars().all(|c| c == '-'))
+}
+
+/// Extract title from markdown content
+///
+/// In order of preference: a `title` field in leading front matter, the first
+/// heading (ATX `# Title` or setext `Title` underlined with `===`/`---`),
+/// the first meaningful line, and finally the configured default title.
+pub fn extract_title_from_content(content: &str) -> String {
+    extract_title(content).unwrap_or_else(default_card_title)
+}
+
+/// Title taken from the content itself (None when the card has no text to use)
+fn extract_title(content: &str) -> Option<String> {
+    let (front_matter_title, content) = split_title_front_matter(content);
+    if let Some(title) = front_matter_title {
+        return Some(title);
+    }
+
+    // 1. Look for the first heading
+    let lines: Vec<&str> = content.lines().collect();
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('#') {
+            // Handles both "# Title" and "#Title" (no space)
             let title = trimmed.trim_start_matches('#').trim();
             if !title.is_empty() {
-                return title.to_string();
+                return Some(title.to_string());
             }
+        } else if !trimmed.is_empty()
+            && !is_setext_underline(trimmed)
+            && lines.get(i + 1).is_some_and(|next| is_setext_underline(next))
+        {
+            return Some(trimmed.to_string());
         }
     }
 
@@ -80,16 +657,24 @@ fn extract_title_from_content(content: &str) -> String {
         let trimmed = line.trim();
         if !trimmed.is_empty() && !trimmed.starts_with("---") {
             // Truncate long lines for title
-            let mut title = trimmed.to_string();
-            if title.len() > 50 {
-                title.truncate(50);
-                title.push_str("...");
+            let title = truncate_chars(trimmed, 50);
+            if title.len() < trimmed.len() {
+                return Some(format!("{}...", title));
             }
-            return title;
+            return Some(title.to_string());
         }
     }
 
-    "Note".to_string()
+    None
+}
+
+/// The first `max_chars` characters of a text
+/// Cuts between characters, so multi-byte text (emoji, "ä") never gets split.
+pub fn truncate_chars(text: &str, max_chars: usize) -> &str {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
 }
 
 /// Sanitize title for use as filename
@@ -109,10 +694,16 @@ fn sanitize_filename(title: &str) -> String {
     // Trim whitespace and dots from ends
     sanitized = sanitized.trim().trim_end_matches('.').to_string();
 
-    // Limit length to 100 characters
+    // Limit length to 100 bytes (file systems count name length in bytes), cutting
+    // between characters
     if sanitized.len() > 100 {
-        sanitized.truncate(100);
-        sanitized = sanitized.trim().to_string();
+        let end = sanitized
+            .char_indices()
+            .map(|(start, c)| start + c.len_utf8())
+            .take_while(|end| *end <= 100)
+            .last()
+            .unwrap_or(0);
+        sanitized = sanitized[..end].trim().to_string();
     }
 
     // Ensure not empty
@@ -124,9 +715,12 @@ fn sanitize_filename(title: &str) -> String {
 }
 
 /// Get unique filename, handling duplicates by adding (2), (3), etc.
-fn get_unique_filename(cards_dir: &PathBuf, base_name: &str) -> String {
+/// `own_path` is the file of the card being renamed; it doesn't count as a collision.
+fn get_unique_filename(cards_dir: &Path, base_name: &str, own_path: Option<&Path>) -> String {
+    let is_free = |path: &Path| !path.exists() || own_path == Some(path);
+
     let path = cards_dir.join(format!("{}.md", base_name));
-    if !path.exists() {
+    if is_free(&path) {
         return format!("{}.md", base_name);
     }
 
@@ -135,7 +729,7 @@ fn get_unique_filename(cards_dir: &PathBuf, base_name: &str) -> String {
     loop {
         let numbered_name = format!("{} ({})", base_name, counter);
         let path = cards_dir.join(format!("{}.md", numbered_name));
-        if !path.exists() {
+        if is_free(&path) {
             return format!("{}.md", numbered_name);
         }
         counter += 1;
@@ -146,13 +740,42 @@ fn get_unique_filename(cards_dir: &PathBuf, base_name: &str) -> String {
     }
 }
 
+/// Strip a " (N)" duplicate suffix added by `get_unique_filename` from a file stem
+fn strip_duplicate_suffix(stem: &str) -> &str {
+    if let Some(open) = stem.rfind(" (") {
+        if let Some(number) = stem[open + 2..].strip_suffix(')') {
+            if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) {
+                return &stem[..open];
+            }
+        }
+    }
+    stem
+}
+
+/// Work out where a card file should move to after its content changed
+/// Returns None if the current filename already matches the title (including
+/// a " (N)" suffix from an earlier collision), so unrelated edits never rename.
+/// Untitled cards keep their filename, so changing the default title doesn't rename them.
+fn get_renamed_card_path(cards_dir: &Path, current_path: &Path, content: &str) -> Option<PathBuf> {
+    let sanitized = sanitize_filename(&extract_title(content)?);
+    let current_stem = current_path.file_stem()?.to_str()?;
+
+    if current_stem == sanitized || strip_duplicate_suffix(current_stem) == sanitized {
+        return None;
+    }
+
+    let new_path = cards_dir.join(get_unique_filename(cards_dir, &sanitized, Some(current_path)));
+    (new_path != current_path).then_some(new_path)
+}
+
 /// Get the path for a specific card (by ID or by content for new cards)
 fn get_card_file_path(id: &str) -> Result<PathBuf, String> {
     let cards_dir = get_cards_directory()?;
 
-    // Try to find existing file with this ID in front matter
+    // Try to find existing file with this ID in front matter or the index
     let entries = fs::read_dir(&cards_dir)
         .map_err(|e| format!("Failed to read cards directory: {}", e))?;
+    let index = read_card_index(&cards_dir);
 
     for entry in entries {
         if let Ok(entry) = entry {
@@ -160,7 +783,7 @@ fn get_card_file_path(id: &str) -> Result<PathBuf, String> {
             if path.extension().and_then(|s| s.to_str()) == Some("md") {
                 // Try to read and parse the file
                 if let Ok(content) = fs::read_to_string(&path) {
-                    if let Ok((metadata, _)) = parse_markdown_with_frontmatter(&content) {
+                    if let Ok((metadata, _)) = parse_card_file(&content, index.get(&index_key(&path))) {
                         if metadata.id == id {
                             return Ok(path);
                         }
@@ -179,7 +802,7 @@ fn get_new_card_file_path(content: &str) -> Result<PathBuf, String> {
     let cards_dir = get_cards_directory()?;
     let title = extract_title_from_content(content);
     let sanitized = sanitize_filename(&title);
-    let filename = get_unique_filename(&cards_dir, &sanitized);
+    let filename = get_unique_filename(&cards_dir, &sanitized, None);
     Ok(cards_dir.join(filename))
 }
 
@@ -205,23 +828,188 @@ fn parse_markdown_with_frontmatter(content: &str) -> Result<(CardMetadata, Strin
     }
 }
 
-/// Create markdown file content with YAML front matter
-fn create_markdown_with_frontmatter(card: &Card) -> Result<String, String> {
-    let metadata = CardMetadata {
+/// Parse a card file, taking the metadata from the index when it has no front matter
+/// Front matter wins over a (stale) index entry for the same file.
+fn parse_card_file(raw: &str, indexed: Option<&CardMetadata>) -> Result<(CardMetadata, String), String> {
+    match (parse_markdown_with_frontmatter(raw), indexed) {
+        (Err(_), Some(metadata)) => Ok((metadata.clone(), raw.to_string())),
+        (parsed, _) => parsed,
+    }
+}
+
+/// The metadata of a card as stored in front matter or the index
+fn card_metadata(card: &Card) -> CardMetadata {
+    CardMetadata {
         id: card.id.clone(),
         created_at: card.created_at,
         updated_at: card.updated_at,
-    };
+        pinned: card.pinned,
+        color: card.color.clone(),
+        locked: card.locked,
+        archived: card.archived,
+        tags: card.tags.clone(),
+    }
+}
 
-    let yaml = serde_yaml::to_string(&metadata)
+/// Create markdown file content with YAML front matter
+fn create_markdown_with_frontmatter(card: &Card) -> Result<String, String> {
+    let yaml = serde_yaml::to_string(&card_metadata(card))
         .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
 
     Ok(format!("---\n{}---\n{}", yaml, card.content))
 }
 
+/// Index entry key of a card file: its file name
+fn index_key(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+/// Read the sidecar index of a cards directory; missing or unreadable means empty
+fn read_card_index(dir: &Path) -> BTreeMap<String, CardMetadata> {
+    let Ok(json) = fs::read_to_string(dir.join(CARD_INDEX_FILE)) else {
+        return BTreeMap::new();
+    };
+    serde_json::from_str(&json).unwrap_or_else(|e| {
+        log::warn!("Ignoring unreadable card index in {:?}: {}", dir, e);
+        BTreeMap::new()
+    })
+}
+
+/// Write the sidecar index of a cards directory, removing the file when it's empty
+fn write_card_index(dir: &Path, index: &BTreeMap<String, CardMetadata>) -> Result<(), String> {
+    let path = dir.join(CARD_INDEX_FILE);
+    if index.is_empty() {
+        if path.exists() {
+            let size = file_size(&path);
+            fs::remove_file(&path).map_err(|e| format!("Failed to remove card index: {}", e))?;
+            adjust_storage_used(&path, size, 0);
+        }
+        return Ok(());
+    }
+
+    let json = serde_json::to_string_pretty(index)
+        .map_err(|e| format!("Failed to serialize card index: {}", e))?;
+    write_card_file(&path, &json)
+}
+
+/// Write a card in the configured layout: front matter in the file, or the bare
+/// markdown with the metadata in the index
+fn write_card_storage(path: &Path, card: &Card) -> Result<(), String> {
+    write_card_storage_as(path, card, store_frontmatter())
+}
+
+/// Write a card with or without front matter, regardless of the configured layout
+/// Whatever is written first is enough to load the card on its own (the front matter
+/// file, or the index entry next to the old file), so a crash in between never loses
+/// its metadata.
+fn write_card_storage_as(path: &Path, card: &Card, frontmatter: bool) -> Result<(), String> {
+    let dir = path.parent().ok_or_else(|| format!("Invalid card path: {:?}", path))?;
+    let mut index = read_card_index(dir);
+
+    if frontmatter {
+        write_card_file(path, &create_markdown_with_frontmatter(card)?)?;
+        if index.remove(&index_key(path)).is_some() {
+            write_card_index(dir, &index)?;
+        }
+    } else {
+        index.insert(index_key(path), card_metadata(card));
+        write_card_index(dir, &index)?;
+        write_card_file(path, &card.content)?;
+    }
+
+    Ok(())
+}
+
+/// Rename a card file, moving its index entry along
+/// The entry is added under the new name before the rename and the old one removed
+/// after it, so the file has an entry whichever name it has.
+fn rename_card_file(from: &Path, to: &Path) -> Result<(), String> {
+    let dir = from.parent().ok_or_else(|| format!("Invalid card path: {:?}", from))?;
+    let mut index = read_card_index(dir);
+    let indexed = index.get(&index_key(from)).cloned();
+    if let Some(metadata) = &indexed {
+        index.insert(index_key(to), metadata.clone());
+        write_card_index(dir, &index)?;
+    }
+
+    note_own_write(from);
+    note_own_write(to);
+    fs::rename(from, to).map_err(|e| format!("Failed to rename file: {}", e))?;
+
+    if indexed.is_some() {
+        index.remove(&index_key(from));
+        write_card_index(dir, &index)?;
+    }
+
+    log::debug!("Renamed card file from {:?} to {:?}", from, to);
+    Ok(())
+}
+
+/// Whether a file has no card id of its own: no front matter, or front matter without
+/// an id (e.g. a note written by another markdown app). Unreadable front matter doesn't
+/// count, so it's reported rather than overwritten.
+fn lacks_card_id(raw: &str) -> bool {
+    match raw.strip_prefix("---\n") {
+        None => true,
+        Some(rest) => rest
+            .find("\n---\n")
+            .and_then(|end| serde_yaml::from_str::<serde_yaml::Mapping>(&rest[..end]).ok())
+            .is_some_and(|mapping| is_missing(&mapping, "id")),
+    }
+}
+
+/// Metadata for a file that becomes a card, with a fresh id
+fn new_card_metadata(now: i64) -> CardMetadata {
+    CardMetadata {
+        id: Uuid::new_v4().to_string(),
+        created_at: now,
+        updated_at: now,
+        pinned: false,
+        color: None,
+        locked: false,
+        archived: false,
+        tags: Vec::new(),
+    }
+}
+
+/// Give the `.md` files of a directory that have no card id an index entry, so notes
+/// added by other markdown apps load as cards without front matter being added to them
+/// Returns the adopted file names, sorted.
+fn adopt_unindexed_files(dir: &Path, index: &mut BTreeMap<String, CardMetadata>, now: i64) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("md"))
+        .filter(|path| !index.contains_key(&index_key(path)))
+        .collect();
+    paths.sort();
+
+    let mut adopted = Vec::new();
+    for path in paths {
+        if fs::read_to_string(&path).is_ok_and(|raw| lacks_card_id(&raw)) {
+            index.insert(index_key(&path), new_card_metadata(now));
+            adopted.push(index_key(&path));
+        }
+    }
+    adopted
+}
+
 /// Load all cards from markdown files
 fn load_cards_from_files() -> Result<Vec<Card>, String> {
     let cards_dir = get_cards_directory()?;
+    invalidate_storage_used();
+    let mut index = read_card_index(&cards_dir);
+
+    if !store_frontmatter() {
+        let adopted = adopt_unindexed_files(&cards_dir, &mut index, chrono::Utc::now().timestamp());
+        if !adopted.is_empty() {
+            write_card_index(&cards_dir, &index)?;
+            log::info!("Added {} markdown files without front matter to the card index", adopted.len());
+        }
+    }
 
     let mut cards = Vec::new();
 
@@ -234,7 +1022,7 @@ fn load_cards_from_files() -> Result<Vec<Card>, String> {
         let path = entry.path();
 
         if path.extension().and_then(|s| s.to_str()) == Some("md") {
-            match load_card_from_file(&path) {
+            match load_card_from_file(&path, index.get(&index_key(&path))) {
                 Ok(card) => cards.push(card),
                 Err(e) => log::warn!("Failed to load card from {:?}: {}", path, e),
             }
@@ -245,25 +1033,57 @@ fn load_cards_from_files() -> Result<Vec<Card>, String> {
     Ok(cards)
 }
 
-/// Load a single card from a markdown file
-fn load_card_from_file(path: &PathBuf) -> Result<Card, String> {
+/// Load a single card from a markdown file and its index entry, if any
+fn load_card_from_file(path: &Path, indexed: Option<&CardMetadata>) -> Result<Card, String> {
     let content = fs::read_to_string(path)
         .map_err(|e| format!("Failed to read file: {}", e))?;
 
-    let (metadata, markdown_content) = parse_markdown_with_frontmatter(&content)?;
+    let (metadata, markdown_content) = parse_card_file(&content, indexed)?;
 
-    Ok(Card {
+    let mut card = Card {
         id: metadata.id,
         content: markdown_content,
         created_at: metadata.created_at,
         updated_at: metadata.updated_at,
-    })
+        word_count: 0,
+        char_count: 0,
+        pinned: metadata.pinned,
+        color: metadata.color,
+        locked: metadata.locked,
+        archived: metadata.archived,
+        tags: metadata.tags,
+        content_hash: String::new(),
+    };
+    card.refresh_derived();
+
+    Ok(card)
+}
+
+/// Write a card file atomically
+/// Content goes to a temp file in the same directory which is then renamed over
+/// the target, so a crash mid-write never leaves a truncated card behind.
+fn write_card_file(path: &Path, content: &str) -> Result<(), String> {
+    let temp_path = path.with_extension("md.tmp");
+    let old_size = file_size(path);
+    note_own_write(path);
+
+    let write_temp = || -> std::io::Result<()> {
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()
+    };
+
+    if let Err(e) = write_temp().and_then(|_| fs::rename(&temp_path, path)) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(format!("Failed to write card file: {}", e));
+    }
+
+    adjust_storage_used(path, old_size, content.len() as u64);
+    Ok(())
 }
 
 /// Save a single card to a markdown file
 fn save_card_to_file(card: &Card) -> Result<PathBuf, String> {
-    let content = create_markdown_with_frontmatter(card)?;
-
     // Try to find existing file, or create new one based on content
     let file_path = match get_card_file_path(&card.id) {
         Ok(path) => path,
@@ -273,21 +1093,50 @@ fn save_card_to_file(card: &Card) -> Result<PathBuf, String> {
         }
     };
 
-    fs::write(&file_path, content)
-        .map_err(|e| format!("Failed to write card file: {}", e))?;
+    write_card_storage(&file_path, card)?;
 
     log::debug!("Saved card {} to {:?}", card.id, file_path);
     Ok(file_path)
 }
 
-/// Delete a card's markdown file
-fn delete_card_file(id: &str) -> Result<(), String> {
-    let file_path = get_card_file_path(id)?;
+/// Map card ids to their files in a cards directory, reading each file once
+/// Operations on many cards resolve their paths with this up front rather than
+/// scanning the directory per card with `get_card_file_path`.
+fn card_file_paths(cards_dir: &Path) -> Result<HashMap<String, PathBuf>, String> {
+    let entries = fs::read_dir(cards_dir)
+        .map_err(|e| format!("Failed to read cards directory: {}", e))?;
+    let index = read_card_index(cards_dir);
+
+    let mut paths = HashMap::new();
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension().and_then(|s| s.to_str()) != Some("md") {
+            continue;
+        }
+        if let Ok(content) = fs::read_to_string(&path) {
+            if let Ok((metadata, _)) = parse_card_file(&content, index.get(&index_key(&path))) {
+                paths.entry(metadata.id).or_insert(path);
+            }
+        }
+    }
+    Ok(paths)
+}
 
+/// Delete a card's markdown file and its index entry
+fn delete_card_file(file_path: &Path) -> Result<(), String> {
     if file_path.exists() {
-        fs::remove_file(&file_path)
+        let size = file_size(file_path);
+        note_own_write(file_path);
+        fs::remove_file(file_path)
             .map_err(|e| format!("Failed to delete card file: {}", e))?;
-        log::debug!("Deleted card file for {}", id);
+        adjust_storage_used(file_path, size, 0);
+        log::debug!("Deleted card file {:?}", file_path);
+    }
+
+    if let Some(dir) = file_path.parent() {
+        let mut index = read_card_index(dir);
+        if index.remove(&index_key(file_path)).is_some() {
+            write_card_index(dir, &index)?;
+        }
     }
 
     Ok(())
@@ -299,15 +1148,26 @@ fn delete_card_file(id: &str) -> Result<(), String> {
 
 /// Create a new card
 pub fn create_card(content: String) -> Result<Card, String> {
+    check_storage_limit(content.len() as u64)?;
+
     let now = chrono::Utc::now().timestamp();
-    let card = Card {
+    let mut card = Card {
         id: Uuid::new_v4().to_string(),
         content,
         created_at: now,
         updated_at: now,
+        word_count: 0,
+        char_count: 0,
+        pinned: false,
+        color: None,
+        locked: false,
+        archived: false,
+        tags: Vec::new(),
+        content_hash: String::new(),
     };
+    card.refresh_derived();
 
-    let mut cards = CARDS.lock().map_err(|e| e.to_string())?;
+    let mut cards = lock_cards();
     cards.push(card.clone());
 
     // Save to markdown file
@@ -316,83 +1176,2354 @@ pub fn create_card(content: String) -> Result<Card, String> {
     Ok(card)
 }
 
-/// Get all cards
+/// Prefix the first heading of a card with "Copy of", or add one if there is none
+fn prefix_copy_title(content: &str) -> String {
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+
+    if let Some(line) = lines.iter_mut().find(|l| l.trim_start().starts_with('#')) {
+        let trimmed = line.trim_start();
+        let level = trimmed.chars().take_while(|c| *c == '#').count();
+        let title = trimmed[level..].trim();
+        *line = format!("{} Copy of {}", "#".repeat(level), title);
+        let mut result = lines.join("\n");
+        if content.ends_with('\n') {
+            result.push('\n');
+        }
+        return result;
+    }
+
+    format!("# Copy of {}\n\n{}", extract_title_from_content(content), content)
+}
+
+/// Duplicate a card under a fresh id and timestamps
+/// The copy keeps the color and tags but is never pinned. The original card is untouched.
+pub fn duplicate_card(id: &str, prefix_title: bool) -> Result<Card, String> {
+    let source = get_card_by_id(id)?;
+
+    let content = if prefix_title {
+        prefix_copy_title(&source.content)
+    } else {
+        source.content.clone()
+    };
+    check_storage_limit(content.len() as u64)?;
+
+    let now = chrono::Utc::now().timestamp();
+    let mut card = Card {
+        id: Uuid::new_v4().to_string(),
+        content,
+        created_at: now,
+        updated_at: now,
+        word_count: 0,
+        char_count: 0,
+        pinned: false,
+        color: source.color.clone(),
+        locked: false,
+        archived: false,
+        tags: source.tags.clone(),
+        content_hash: String::new(),
+    };
+    card.refresh_derived();
+
+    let mut cards = lock_cards();
+    cards.push(card.clone());
+
+    // New id means no existing file, so this gets a unique filename next to the source
+    let path = save_card_to_file(&card)?;
+    log::info!("Duplicated card {} as {} ({:?})", id, card.id, path);
+
+    Ok(card)
+}
+
+/// Separator placed between the contents of merged cards
+const MERGE_SEPARATOR: &str = "\n\n---\n\n";
+
+/// Merge several cards into a new card, in the given order
+/// All ids are validated before anything is written, so a merge never happens partially.
+/// With `delete_sources` the original cards are deleted after the merged card is saved.
+pub fn merge_cards(ids: &[String], delete_sources: bool) -> Result<Card, String> {
+    if ids.is_empty() {
+        return Err("No cards to merge".to_string());
+    }
+
+    let contents: Vec<String> = {
+        let cards = lock_cards();
+        let missing: Vec<&str> = ids
+            .iter()
+            .filter(|id| !cards.iter().any(|c| &c.id == *id))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("Cards not found: {}", missing.join(", ")));
+        }
+        if delete_sources {
+            if let Some(locked) = cards.iter().find(|c| c.locked && ids.contains(&c.id)) {
+                ensure_unlocked(locked)?;
+            }
+        }
+
+        ids.iter()
+            .filter_map(|id| cards.iter().find(|c| &c.id == id))
+            .map(|c| c.content.trim_end().to_string())
+            .collect()
+    };
+
+    let mut merged = create_card(contents.join(MERGE_SEPARATOR))?;
+
+    if delete_sources {
+        // The merged content still links to the sources' attachments, so they move
+        // to the merged card before the sources (and their folders) are deleted
+        let cards_dir = get_cards_directory()?;
+        let mut content = merged.content.clone();
+        for id in ids {
+            move_card_attachments(&cards_dir, id, &merged.id, &mut content)?;
+        }
+        if content != merged.content {
+            let mut cards = lock_cards();
+            if let Some(card) = cards.iter_mut().find(|c| c.id == merged.id) {
+                card.content = content;
+                card.refresh_derived();
+                write_card_with_rename(card)?;
+                merged = card.clone();
+            }
+        }
+
+        let mut deleted: Vec<&String> = Vec::new();
+        for id in ids {
+            if !deleted.contains(&id) {
+                delete_card(id)?;
+                deleted.push(id);
+            }
+        }
+    }
+
+    log::info!("Merged {} cards into {}", ids.len(), merged.id);
+    Ok(merged)
+}
+
+/// Get all cards (pinned cards first, otherwise in stored order)
 pub fn get_all_cards() -> Result<Vec<Card>, String> {
-    let cards = CARDS.lock().map_err(|e| e.to_string())?.clone();
+    let mut cards: Vec<Card> = lock_cards().iter().filter(|c| !c.archived).cloned().collect();
+    // Stable sort keeps the existing order within pinned and unpinned groups
+    cards.sort_by_key(|c| !c.pinned);
+    Ok(cards)
+}
+
+/// Get the archived cards, most recently updated first
+pub fn get_archived_cards() -> Vec<Card> {
+    let mut cards: Vec<Card> = lock_cards().iter().filter(|c| c.archived).cloned().collect();
+    cards.sort_by(|a, b| b.updated_at.cmp(&a.updated_at).then_with(|| a.id.cmp(&b.id)));
+    cards
+}
+
+/// Get a single card from the in-memory cache
+pub fn get_card_by_id(id: &str) -> Result<Card, String> {
+    lock_cards()
+        .iter()
+        .find(|c| c.id == id)
+        .cloned()
+        .ok_or_else(|| format!("Card with id {} not found", id))
+}
+
+/// Ids of cards whose content differs from the caller's last-known hashes (id -> hash)
+/// New cards and cards that no longer exist are included too. Sorted by id.
+pub fn changed_card_ids(cards: &[Card], known_hashes: &HashMap<String, String>) -> Vec<String> {
+    let current: HashSet<&str> = cards.iter().map(|c| c.id.as_str()).collect();
+
+    let mut changed: Vec<String> = cards
+        .iter()
+        .filter(|c| known_hashes.get(&c.id) != Some(&c.content_hash))
+        .map(|c| c.id.clone())
+        .chain(
+            known_hashes
+                .keys()
+                .filter(|id| !current.contains(id.as_str()))
+                .cloned(),
+        )
+        .collect();
+    changed.sort();
+    changed
+}
+
+/// Ids of cards changed since the caller last saw them, see `changed_card_ids`
+pub fn get_changed_cards(known_hashes: &HashMap<String, String>) -> Vec<String> {
+    changed_card_ids(&lock_cards(), known_hashes)
+}
+
+/// Sort cards by "created", "updated" or "title" (case-insensitive), ties broken by id
+/// Pinned cards stay first, as in `get_all_cards`.
+pub fn sort_cards(cards: &mut [Card], sort_by: &str, descending: bool) -> Result<(), String> {
+    // Only the sort field follows the direction; the id tie-break is always ascending
+    let directed = |order: std::cmp::Ordering| if descending { order.reverse() } else { order };
+
+    match sort_by {
+        "created" => cards.sort_by(|a, b| {
+            directed(a.created_at.cmp(&b.created_at)).then_with(|| a.id.cmp(&b.id))
+        }),
+        "updated" => cards.sort_by(|a, b| {
+            directed(a.updated_at.cmp(&b.updated_at)).then_with(|| a.id.cmp(&b.id))
+        }),
+        "title" => {
+            let titles: HashMap<String, String> = cards
+                .iter()
+                .map(|c| (c.id.clone(), extract_title_from_content(&c.content).to_lowercase()))
+                .collect();
+            cards.sort_by(|a, b| directed(titles[&a.id].cmp(&titles[&b.id])).then_with(|| a.id.cmp(&b.id)))
+        }
+        _ => return Err(format!("Unknown sort field: {} (expected created, updated or title)", sort_by)),
+    }
+
+    cards.sort_by_key(|c| !c.pinned);
+    Ok(())
+}
+
+/// Get all cards in the given order
+pub fn get_cards_sorted(sort_by: &str, descending: bool) -> Result<Vec<Card>, String> {
+    let mut cards = get_all_cards()?;
+    sort_cards(&mut cards, sort_by, descending)?;
     Ok(cards)
 }
 
 /// Update a card
+/// The in-memory card changes immediately; the file is written once the card has
+/// been idle for `SAVE_DEBOUNCE`, so rapid edits are coalesced into one write.
 pub fn update_card(id: &str, content: Option<String>) -> Result<Card, String> {
-    let mut cards = CARDS.lock().map_err(|e| e.to_string())?;
-
-    if let Some(existing) = cards.iter_mut().find(|c| c.id == id) {
-        // Get old file path before updating content
-        let old_path = get_card_file_path(id).ok();
-
-        if let Some(c) = content {
-            existing.content = c;
-        }
-        existing.updated_at = chrono::Utc::now().timestamp();
-        let updated = existing.clone();
-
-        // Save to markdown file
-        // Note: save_card_to_file will find the OLD path if it exists
-        // so we need to handle the rename manually if the title changed
-        let current_path = if let Some(ref path) = old_path {
-            // It exists, let's write to it first
-            let file_content = create_markdown_with_frontmatter(&updated)?;
-            fs::write(path, file_content).map_err(|e| e.to_string())?;
-            path.clone()
-        } else {
-            save_card_to_file(&updated)?
+    ensure_save_flusher()?;
+    let mut cards = lock_cards();
+
+    let existing = cards
+        .iter_mut()
+        .find(|c| c.id == id)
+        .ok_or_else(|| format!("Card with id {} not found", id))?;
+    ensure_unlocked(existing)?;
+
+    if let Some(c) = content {
+        check_storage_limit(c.len().saturating_sub(existing.content.len()) as u64)?;
+        existing.content = c;
+        existing.refresh_derived();
+    }
+    existing.updated_at = chrono::Utc::now().timestamp();
+    let updated = existing.clone();
+
+    schedule_save(id);
+    Ok(updated)
+}
+
+/// Append text to a card, e.g. a streamed AI response
+/// Saved after `SAVE_DEBOUNCE` like `update_card`, so a stream of small appends is
+/// written a few times rather than once per piece.
+pub fn append_to_card(id: &str, text: &str) -> Result<(), String> {
+    ensure_save_flusher()?;
+    let mut cards = lock_cards();
+
+    let existing = cards
+        .iter_mut()
+        .find(|c| c.id == id)
+        .ok_or_else(|| format!("Card with id {} not found", id))?;
+    ensure_unlocked(existing)?;
+    check_storage_limit(text.len() as u64)?;
+
+    existing.content.push_str(text);
+    existing.refresh_derived();
+    existing.updated_at = chrono::Utc::now().timestamp();
+
+    schedule_save(id);
+    Ok(())
+}
+
+/// Write a card's file, renaming it if the title changed
+fn write_card_with_rename(card: &Card) -> Result<(), String> {
+    // save_card_to_file would keep the OLD path, so an existing file is written
+    // in place and renamed by hand when the title changed
+    let Ok(current_path) = get_card_file_path(&card.id) else {
+        save_card_to_file(card)?;
+        return Ok(());
+    };
+
+    write_card_storage(&current_path, card)?;
+
+    let cards_dir = get_cards_directory()?;
+    if let Some(new_path) = get_renamed_card_path(&cards_dir, &current_path, &card.content) {
+        rename_card_file(&current_path, &new_path)?;
+    }
+
+    Ok(())
+}
+
+/// Start the background save thread unless it's already running
+/// Called before a card changes in memory, so an edit is never accepted that can't be written.
+fn ensure_save_flusher() -> Result<(), String> {
+    let mut started = lock_or_recover(&SAVE_FLUSHER, "save flusher");
+    if !*started {
+        std::thread::Builder::new()
+            .name("card-save-flusher".to_string())
+            .spawn(run_save_flusher)
+            .map_err(|e| format!("Failed to start card save thread: {}", e))?;
+        *started = true;
+    }
+    Ok(())
+}
+
+/// Queue a card for writing after the debounce period, restarting it on repeated edits
+fn schedule_save(id: &str) {
+    let (pending, wake) = &*PENDING_SAVES;
+    let save = PendingSave {
+        due: Instant::now() + SAVE_DEBOUNCE,
+        failures: 0,
+    };
+    lock_or_recover(pending, "pending saves").insert(id.to_string(), save);
+    wake.notify_one();
+}
+
+/// Wait before retrying a write that failed `failures` times: doubles from
+/// `SAVE_DEBOUNCE` up to `SAVE_RETRY_MAX`
+fn save_retry_delay(failures: u32) -> Duration {
+    SAVE_DEBOUNCE
+        .saturating_mul(1 << failures.min(16))
+        .min(SAVE_RETRY_MAX)
+}
+
+/// Queue failed writes again with backoff and report them
+/// A card edited again meanwhile keeps its fresh schedule.
+fn retry_failed_saves(failures: Vec<(String, u32, String)>) {
+    let (pending, wake) = &*PENDING_SAVES;
+    let mut reports = Vec::new();
+    {
+        let mut queue = lock_or_recover(pending, "pending saves");
+        for (id, previous_failures, error) in failures {
+            let attempts = previous_failures + 1;
+            let delay = save_retry_delay(attempts);
+            log::error!("Failed to save card {} (attempt {}): {}; retrying in {:?}", id, attempts, error, delay);
+            queue.entry(id.clone()).or_insert(PendingSave {
+                due: Instant::now() + delay,
+                failures: attempts,
+            });
+            reports.push(SaveFailure {
+                id,
+                error,
+                attempts,
+                retry_in_ms: delay.as_millis() as u64,
+            });
+        }
+    }
+    wake.notify_one();
+
+    if let Some(handler) = &*SAVE_FAILED_HANDLER.read().unwrap_or_else(|e| e.into_inner()) {
+        for report in &reports {
+            handler(report);
+        }
+    }
+}
+
+/// Drop a queued write, e.g. because the file was just written some other way
+fn cancel_pending_save(id: &str) {
+    lock_or_recover(&PENDING_SAVES.0, "pending saves").remove(id);
+}
+
+/// Background loop that writes cards once their debounce period has passed
+fn run_save_flusher() {
+    let (pending, wake) = &*PENDING_SAVES;
+
+    loop {
+        let due: Vec<(String, u32)> = {
+            let mut queue = lock_or_recover(pending, "pending saves");
+            loop {
+                let now = Instant::now();
+                let next_due = queue.values().map(|save| save.due).min();
+                match next_due {
+                    None => queue = wake.wait(queue).unwrap_or_else(|e| e.into_inner()),
+                    Some(next) if next > now => {
+                        queue = wake
+                            .wait_timeout(queue, next - now)
+                            .unwrap_or_else(|e| e.into_inner())
+                            .0
+                    }
+                    Some(_) => break,
+                }
+            }
+
+            let now = Instant::now();
+            let due: Vec<(String, u32)> = queue
+                .iter()
+                .filter(|(_, save)| save.due <= now)
+                .map(|(id, save)| (id.clone(), save.failures))
+                .collect();
+            for (id, _) in &due {
+                queue.remove(id);
+            }
+            due
         };
 
-        // If title changed, rename the file
-        if let Some(old_path) = old_path {
-            let cards_dir = get_cards_directory()?;
-            let new_title = extract_title_from_content(&updated.content);
-            let sanitized = sanitize_filename(&new_title);
-            let new_filename = get_unique_filename(&cards_dir, &sanitized);
-            let new_path = cards_dir.join(new_filename);
-
-            if old_path != new_path {
-                fs::rename(&old_path, &new_path).map_err(|e| format!("Failed to rename file: {}", e))?;
-                log::debug!("Renamed card file from {:?} to {:?}", old_path, new_path);
+        // Copy the due cards and write them without holding the card list, so card
+        // commands don't wait on disk I/O
+        let snapshot: Vec<Card> = lock_cards()
+            .iter()
+            .filter(|c| due.iter().any(|(id, _)| id == &c.id))
+            .cloned()
+            .collect();
+        let written = write_pending_cards(&snapshot, &due);
+        check_flushed_cards(&written);
+    }
+}
+
+/// Write the given cards (id, earlier failures) from the in-memory list
+/// Deleted cards are skipped; failed writes are queued again. Returns the cards written.
+fn write_pending_cards<'a>(cards: &'a [Card], ids: &[(String, u32)]) -> Vec<&'a Card> {
+    let mut written = Vec::new();
+    let mut failures = Vec::new();
+    for (id, previous_failures) in ids {
+        if let Some(card) = cards.iter().find(|c| &c.id == id) {
+            match write_card_with_rename(card) {
+                Ok(()) => written.push(card),
+                Err(e) => failures.push((id.clone(), *previous_failures, e)),
+            }
+        }
+    }
+    if !failures.is_empty() {
+        retry_failed_saves(failures);
+    }
+    written
+}
+
+/// Catch up on changes made while the flusher wrote copies of these cards
+/// A card deleted meanwhile has the file just written removed again; a card changed
+/// meanwhile is queued again so its newer state is written.
+fn check_flushed_cards(written: &[&Card]) {
+    let cards = lock_cards();
+    for snapshot in written {
+        match cards.iter().find(|c| c.id == snapshot.id) {
+            None => {
+                if let Ok(path) = get_card_file_path(&snapshot.id) {
+                    if let Err(e) = delete_card_file(&path) {
+                        log::warn!("Failed to remove file of deleted card {}: {}", snapshot.id, e);
+                    }
+                }
             }
+            Some(current) if current != *snapshot => schedule_save(&current.id),
+            Some(_) => {}
         }
+    }
+}
+
+/// Write every queued card now, with the card list already locked
+fn flush_pending_saves_locked(cards: &[Card]) -> usize {
+    let ids: Vec<(String, u32)> = lock_or_recover(&PENDING_SAVES.0, "pending saves")
+        .drain()
+        .map(|(id, save)| (id, save.failures))
+        .collect();
+    write_pending_cards(cards, &ids).len()
+}
+
+/// Write every card with unsaved edits now instead of waiting for the debounce
+/// Returns how many cards were written.
+pub fn flush_pending_saves() -> usize {
+    let cards = lock_cards();
+    let flushed = flush_pending_saves_locked(&cards);
+    if flushed > 0 {
+        log::info!("Flushed {} pending card saves", flushed);
+    }
+    flushed
+}
+
+/// Apply a metadata-only change to a card and persist it
+/// Content is unchanged, so the file keeps its name.
+fn update_card_metadata(id: &str, apply: impl FnOnce(&mut Card)) -> Result<Card, String> {
+    let mut cards = lock_cards();
+
+    let existing = cards
+        .iter_mut()
+        .find(|c| c.id == id)
+        .ok_or_else(|| format!("Card with id {} not found", id))?;
+    apply(existing);
+    let updated = existing.clone();
+
+    save_card_to_file(&updated)?;
+
+    Ok(updated)
+}
+
+/// Check that a color is a hex code (#rgb, #rrggbb, #rrggbbaa) or a named color
+fn is_valid_color(color: &str) -> bool {
+    if let Some(hex) = color.strip_prefix('#') {
+        matches!(hex.len(), 3 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+    } else {
+        !color.is_empty() && color.chars().all(|c| c.is_ascii_alphabetic())
+    }
+}
 
-        Ok(updated)
+/// Fail with a "card is locked" error for locked cards
+fn ensure_unlocked(card: &Card) -> Result<(), String> {
+    if card.locked {
+        Err(format!("Card {} is locked", card.id))
     } else {
-        Err(format!("Card with id {} not found", id))
+        Ok(())
     }
 }
 
+/// Lock or unlock a card; locked cards reject edits and deletion
+pub fn set_card_locked(id: &str, locked: bool) -> Result<Card, String> {
+    update_card_metadata(id, |card| card.locked = locked)
+}
+
+/// Archive a card (hidden from `get_all_cards`) or bring it back
+pub fn set_card_archived(id: &str, archived: bool) -> Result<Card, String> {
+    update_card_metadata(id, |card| card.archived = archived)
+}
+
+/// Pin or unpin a card
+pub fn set_card_pinned(id: &str, pinned: bool) -> Result<Card, String> {
+    update_card_metadata(id, |card| card.pinned = pinned)
+}
+
+/// Set or clear a card's color
+pub fn set_card_color(id: &str, color: Option<String>) -> Result<Card, String> {
+    let color = color
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty());
+
+    if let Some(ref c) = color {
+        if !is_valid_color(c) {
+            return Err(format!("Invalid color: {}", c));
+        }
+    }
+
+    update_card_metadata(id, |card| card.color = color)
+}
+
+/// Get a card's file exactly as stored on disk, front matter included
+/// A card stored without front matter gets it generated from its index entry.
+pub fn get_card_raw(id: &str) -> Result<String, String> {
+    flush_pending_saves();
+    let path = get_card_file_path(id)?;
+    let raw = fs::read_to_string(&path).map_err(|e| format!("Failed to read card file: {}", e))?;
+    if parse_markdown_with_frontmatter(&raw).is_ok() {
+        return Ok(raw);
+    }
+    create_markdown_with_frontmatter(&get_card_by_id(id)?)
+}
+
+/// Parse and check raw card file content before it replaces a card's file
+/// The front matter must parse, keep the card's id and have a valid color.
+fn parse_raw_card(id: &str, raw: &str) -> Result<(CardMetadata, String), String> {
+    let (metadata, content) = parse_markdown_with_frontmatter(raw)?;
+
+    if metadata.id != id {
+        return Err(format!("Front matter id must stay {}", id));
+    }
+    if let Some(ref color) = metadata.color {
+        if !is_valid_color(color) {
+            return Err(format!("Invalid color: {}", color));
+        }
+    }
+
+    Ok((metadata, content))
+}
+
+/// Replace a card's file with raw content (YAML front matter + markdown)
+/// Malformed front matter is rejected and nothing is written.
+pub fn save_card_raw(id: &str, raw: &str) -> Result<Card, String> {
+    let (metadata, content) = parse_raw_card(id, raw)?;
+
+    let mut cards = lock_cards();
+    let existing = cards
+        .iter_mut()
+        .find(|c| c.id == id)
+        .ok_or_else(|| format!("Card with id {} not found", id))?;
+    ensure_unlocked(existing)?;
+    check_storage_limit(content.len().saturating_sub(existing.content.len()) as u64)?;
+
+    let mut updated = existing.clone();
+    updated.content = content;
+    updated.created_at = metadata.created_at;
+    updated.updated_at = metadata.updated_at;
+    updated.pinned = metadata.pinned;
+    updated.color = metadata.color;
+    updated.locked = metadata.locked;
+    updated.archived = metadata.archived;
+    updated.tags = metadata.tags;
+    updated.refresh_derived();
+
+    // The raw content replaces any edit still waiting to be written
+    cancel_pending_save(id);
+    let current_path = get_card_file_path(id)?;
+    if store_frontmatter() {
+        write_card_file(&current_path, raw)?;
+    } else {
+        write_card_storage(&current_path, &updated)?;
+    }
+    *existing = updated.clone();
+
+    // If title changed, rename the file
+    let cards_dir = get_cards_directory()?;
+    if let Some(new_path) = get_renamed_card_path(&cards_dir, &current_path, &updated.content) {
+        rename_card_file(&current_path, &new_path)?;
+    }
+
+    Ok(updated)
+}
+
 /// Delete a card
 pub fn delete_card(id: &str) -> Result<(), String> {
-    let mut cards = CARDS.lock().map_err(|e| e.to_string())?;
-    let initial_len = cards.len();
+    let mut cards = lock_cards();
+    let cards_dir = get_cards_directory()?;
+    let file_path = get_card_file_path(id).ok();
+    delete_card_locked(&mut cards, &cards_dir, id, file_path.as_deref())
+}
+
+/// Delete a card with the card list already locked
+/// `file_path` is the card's file (None if it was never written). The file goes first,
+/// so a card whose file can't be deleted stays listed, as it would after a reload.
+fn delete_card_locked(
+    cards: &mut Vec<Card>,
+    cards_dir: &Path,
+    id: &str,
+    file_path: Option<&Path>,
+) -> Result<(), String> {
+    let existing = cards
+        .iter()
+        .find(|c| c.id == id)
+        .ok_or_else(|| format!("Card with id {} not found", id))?;
+    ensure_unlocked(existing)?;
+
+    if let Some(path) = file_path {
+        delete_card_file(path)?;
+    }
     cards.retain(|c| c.id != id);
+    cancel_pending_save(id);
+
+    // Then the attachments that belonged to it
+    delete_card_attachments(cards_dir, id)?;
+
+    Ok(())
+}
+
+/// A card a bulk operation couldn't be applied to, and why
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BulkFailure {
+    pub id: String,
+    pub error: String,
+}
+
+/// Per-card outcome of `bulk_delete_cards` / `bulk_set_tag`
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct BulkReport {
+    /// Ids of the cards that were changed (or removed)
+    pub succeeded: Vec<String>,
+    /// Cards that exist but couldn't be changed, e.g. because they're locked
+    pub failed: Vec<BulkFailure>,
+    /// Ids that don't match any card; skipped
+    pub not_found: Vec<String>,
+}
+
+/// Operation applied by `bulk_apply` to one card: (cards, cards dir, id, card file)
+type BulkOperation<'a> = dyn FnMut(&mut Vec<Card>, &Path, &str, Option<&Path>) -> Result<(), String> + 'a;
+
+/// Apply an operation to each card in `ids` while holding the card list lock
+/// The card files are looked up once for the whole batch.
+fn bulk_apply(ids: &[String], apply: &mut BulkOperation) -> Result<BulkReport, String> {
+    if ids.is_empty() {
+        return Err("No card ids given".to_string());
+    }
+
+    let mut cards = lock_cards();
+    let cards_dir = get_cards_directory()?;
+    let paths = card_file_paths(&cards_dir)?;
+    Ok(bulk_apply_to(&mut cards, &cards_dir, &paths, ids, apply))
+}
+
+/// Apply an operation to each card in `ids`, see `bulk_apply`
+/// Duplicate ids are handled once and unknown ids are reported instead of failing.
+fn bulk_apply_to(
+    cards: &mut Vec<Card>,
+    cards_dir: &Path,
+    paths: &HashMap<String, PathBuf>,
+    ids: &[String],
+    apply: &mut BulkOperation,
+) -> BulkReport {
+    let mut report = BulkReport::default();
+    let mut seen = HashSet::new();
+
+    for id in ids.iter().filter(|id| seen.insert(id.as_str())) {
+        if !cards.iter().any(|c| &c.id == id) {
+            report.not_found.push(id.clone());
+            continue;
+        }
+        match apply(cards, cards_dir, id, paths.get(id).map(PathBuf::as_path)) {
+            Ok(()) => report.succeeded.push(id.clone()),
+            Err(error) => report.failed.push(BulkFailure { id: id.clone(), error }),
+        }
+    }
+
+    report
+}
+
+/// Delete several cards at once
+/// Locked cards fail and the rest are still deleted; `succeeded` lists the removed cards.
+pub fn bulk_delete_cards(ids: &[String]) -> Result<BulkReport, String> {
+    let report = bulk_apply(ids, &mut delete_card_locked)?;
+    log::info!(
+        "Bulk deleted {} cards ({} failed, {} not found)",
+        report.succeeded.len(),
+        report.failed.len(),
+        report.not_found.len()
+    );
+    Ok(report)
+}
 
-    if cards.len() == initial_len {
-        return Err(format!("Card with id {} not found", id));
+/// Add a tag to several cards, or remove it with `add` false
+/// Tags are metadata like the color, so locked cards can be tagged too. Cards that
+/// already have (or don't have) the tag are left as they are and count as succeeded.
+pub fn bulk_set_tag(ids: &[String], tag: &str, add: bool) -> Result<BulkReport, String> {
+    let tag = normalize_tag(tag).ok_or("Tag can't be empty")?;
+    if tag.contains(|c: char| c.is_whitespace() || c == ',') {
+        return Err(format!("Tag can't contain spaces or commas: {}", tag));
     }
 
-    // Delete markdown file
-    delete_card_file(id)?;
+    bulk_apply(ids, &mut |cards, _, id, path| {
+        let Some(card) = cards.iter_mut().find(|c| c.id == id) else {
+            return Err(format!("Card with id {} not found", id));
+        };
+        let has_tag = card.tags.contains(&tag);
+        if add == has_tag {
+            return Ok(());
+        }
 
+        let mut updated = card.clone();
+        if add {
+            updated.tags.push(tag.clone());
+        } else {
+            updated.tags.retain(|t| t != &tag);
+        }
+        match path {
+            Some(path) => write_card_storage(path, &updated)?,
+            None => {
+                save_card_to_file(&updated)?;
+            }
+        }
+        *card = updated;
+        Ok(())
+    })
+}
+
+/// A card id as the name of its attachments folder
+/// Ids come from editable front matter, so anything but a single plain path
+/// component (e.g. "..", "a/b" or an absolute path) is refused.
+fn attachment_dir_name(card_id: &str) -> Result<&str, String> {
+    let mut components = Path::new(card_id).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) if !card_id.contains(['/', '\\']) => Ok(card_id),
+        _ => Err(format!("Card id can't be used for attachments: {}", card_id)),
+    }
+}
+
+/// Directory holding a card's attachments: `<cards dir>/attachments/<card id>`
+fn get_attachments_dir(card_id: &str) -> Result<PathBuf, String> {
+    let dir_name = attachment_dir_name(card_id)?;
+    if !lock_cards().iter().any(|c| c.id == card_id) {
+        return Err(format!("Card with id {} not found", card_id));
+    }
+    Ok(get_cards_directory()?.join(ATTACHMENTS_DIR).join(dir_name))
+}
+
+/// Reduce an attachment name to a plain file name (no directories, no hidden files)
+/// Spaces become '-', since the name goes into a markdown link unencoded.
+fn sanitize_attachment_filename(filename: &str) -> Result<String, String> {
+    let name = Path::new(filename.trim())
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| sanitize_filename(n).replace(char::is_whitespace, "-"))
+        .ok_or_else(|| format!("Invalid attachment filename: {}", filename))?;
+
+    if name.starts_with('.') {
+        return Err(format!("Invalid attachment filename: {}", filename));
+    }
+    Ok(name)
+}
+
+/// Pick a name that isn't taken yet in `dir` ("image.png" -> "image-1.png")
+fn unique_attachment_name(dir: &Path, name: &str) -> String {
+    if !dir.join(name).exists() {
+        return name.to_string();
+    }
+
+    let path = Path::new(name);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(name);
+    let extension = path.extension().and_then(|s| s.to_str());
+
+    (1..)
+        .map(|n| match extension {
+            Some(ext) => format!("{}-{}.{}", stem, n, ext),
+            None => format!("{}-{}", stem, n),
+        })
+        .find(|candidate| !dir.join(candidate).exists())
+        .unwrap()
+}
+
+/// Store an attachment for a card
+/// Returns the path relative to the cards directory, ready to embed in the markdown.
+pub fn save_attachment(card_id: &str, filename: &str, bytes: &[u8]) -> Result<String, String> {
+    check_storage_limit(bytes.len() as u64)?;
+
+    let dir = get_attachments_dir(card_id)?;
+    fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create attachments directory: {}", e))?;
+
+    let name = unique_attachment_name(&dir, &sanitize_attachment_filename(filename)?);
+    fs::write(dir.join(&name), bytes)
+        .map_err(|e| format!("Failed to write attachment: {}", e))?;
+    adjust_storage_used(&dir, 0, bytes.len() as u64);
+
+    log::info!("Saved attachment {} for card {}", name, card_id);
+    Ok(format!("{}/{}/{}", ATTACHMENTS_DIR, card_id, name))
+}
+
+/// List a card's attachment file names, sorted
+pub fn list_attachments(card_id: &str) -> Result<Vec<String>, String> {
+    let dir = get_attachments_dir(card_id)?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .map_err(|e| format!("Failed to read attachments directory: {}", e))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().to_str().map(|n| n.to_string()))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Delete one attachment of a card
+pub fn delete_attachment(card_id: &str, filename: &str) -> Result<(), String> {
+    let path = get_attachments_dir(card_id)?.join(sanitize_attachment_filename(filename)?);
+    if !path.is_file() {
+        return Err(format!("Attachment not found: {}", filename));
+    }
+    let size = file_size(&path);
+    fs::remove_file(&path).map_err(|e| format!("Failed to delete attachment: {}", e))?;
+    adjust_storage_used(&path, size, 0);
+    Ok(())
+}
+
+/// Remove a card's attachments directory, if it has one
+/// Cards whose id can't name a folder never had attachments, so there's nothing to remove.
+fn delete_card_attachments(cards_dir: &Path, card_id: &str) -> Result<(), String> {
+    let Ok(dir_name) = attachment_dir_name(card_id) else {
+        return Ok(());
+    };
+    let dir = cards_dir.join(ATTACHMENTS_DIR).join(dir_name);
+    if dir.exists() {
+        let size = dir_size(&dir);
+        fs::remove_dir_all(&dir)
+            .map_err(|e| format!("Failed to delete attachments: {}", e))?;
+        adjust_storage_used(&dir, size, 0);
+    }
     Ok(())
 }
 
+/// Move a card's attachments into another card's folder, rewriting their links in `content`
+/// Names already taken in the target folder get a " (N)" suffix like new uploads.
+fn move_card_attachments(cards_dir: &Path, from_id: &str, to_id: &str, content: &mut String) -> Result<(), String> {
+    let Ok(from_name) = attachment_dir_name(from_id) else {
+        return Ok(());
+    };
+    let from_dir = cards_dir.join(ATTACHMENTS_DIR).join(from_name);
+    if !from_dir.is_dir() {
+        return Ok(());
+    }
+    let to_dir = cards_dir.join(ATTACHMENTS_DIR).join(attachment_dir_name(to_id)?);
+    fs::create_dir_all(&to_dir)
+        .map_err(|e| format!("Failed to create attachments directory: {}", e))?;
+
+    let entries = fs::read_dir(&from_dir)
+        .map_err(|e| format!("Failed to read attachments directory: {}", e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        if !path.is_file() {
+            continue;
+        }
+
+        let new_name = unique_attachment_name(&to_dir, &name);
+        let target = to_dir.join(&new_name);
+        if fs::rename(&path, &target).is_err() {
+            fs::copy(&path, &target).map_err(|e| format!("Failed to copy attachment {}: {}", name, e))?;
+            fs::remove_file(&path).map_err(|e| format!("Failed to remove attachment {}: {}", name, e))?;
+        }
+        *content = content.replace(
+            &format!("{}/{}/{}", ATTACHMENTS_DIR, from_id, name),
+            &format!("{}/{}/{}", ATTACHMENTS_DIR, to_id, new_name),
+        );
+    }
+
+    Ok(())
+}
+
+/// Get the markdown file path of a card
+pub fn get_card_path(id: &str) -> Result<PathBuf, String> {
+    get_card_file_path(id)
+}
+
+/// Get aggregate statistics from the in-memory cards
+pub fn get_card_stats() -> Result<CardStats, String> {
+    let cards = lock_cards();
+    Ok(CardStats::from_cards(&cards, chrono::Utc::now().timestamp()))
+}
+
+/// Inline styles for exported HTML, so the file works on its own
+const EXPORT_HTML_STYLE: &str = "body{max-width:760px;margin:40px auto;padding:0 20px;font-family:-apple-system,BlinkMacSystemFont,'Segoe UI',Roboto,sans-serif;line-height:1.6;color:#1f2328;background:#fff}pre{background:#f6f8fa;padding:12px;border-radius:6px;overflow:auto}code{font-family:ui-monospace,Consolas,monospace;font-size:0.9em}blockquote{margin:0;padding-left:16px;border-left:4px solid #d0d7de;color:#59636e}table{border-collapse:collapse}th,td{border:1px solid #d0d7de;padding:6px 12px}img{max-width:100%}";
+
+/// Escape text for use inside HTML
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Render a card as a standalone HTML document titled after the card
+fn render_card_html(card: &Card) -> String {
+    let options = pulldown_cmark::Options::ENABLE_TABLES
+        | pulldown_cmark::Options::ENABLE_STRIKETHROUGH
+        | pulldown_cmark::Options::ENABLE_TASKLISTS;
+    let parser = pulldown_cmark::Parser::new_ext(&card.content, options);
+    let mut body = String::new();
+    pulldown_cmark::html::push_html(&mut body, parser);
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(&extract_title_from_content(&card.content)),
+        EXPORT_HTML_STYLE,
+        body
+    )
+}
+
+/// Export a card as a self-contained HTML file
+pub fn export_card_html(id: &str, dest: &Path) -> Result<(), String> {
+    let card = lock_cards()
+        .iter()
+        .find(|c| c.id == id)
+        .cloned()
+        .ok_or_else(|| format!("Card with id {} not found", id))?;
+
+    fs::write(dest, render_card_html(&card))
+        .map_err(|e| format!("Failed to write {:?}: {}", dest, e))?;
+
+    log::info!("Exported card {} to {:?}", id, dest);
+    Ok(())
+}
+
+/// Combine cards into a single text for the AI, newest first, within `max_chars`
+/// Each card gets an equal share of the budget and is cut off beyond it; cards that
+/// no longer fit are left out and counted in a final note.
+pub fn build_notes_digest(cards: &[Card], max_chars: usize) -> String {
+    let mut sorted: Vec<&Card> = cards.iter().collect();
+    sorted.sort_by_key(|c| std::cmp::Reverse(c.updated_at));
+
+    let per_card = (max_chars / sorted.len().max(1)).max(400);
+    let mut digest = String::new();
+    let mut digest_chars = 0;
+    let mut included = 0;
+
+    for card in &sorted {
+        let content = card.content.trim();
+        let mut body: String = content.chars().take(per_card).collect();
+        if body.len() < content.len() {
+            body.push_str("…");
+        }
+
+        let entry = format!("## {}\n{}\n\n", extract_title_from_content(content), body);
+        let entry_chars = entry.chars().count();
+        if included > 0 && digest_chars + entry_chars > max_chars {
+            break;
+        }
+
+        digest.push_str(&entry);
+        digest_chars += entry_chars;
+        included += 1;
+    }
+
+    if included < sorted.len() {
+        digest.push_str(&format!("({} more notes omitted)\n", sorted.len() - included));
+    }
+
+    digest
+}
+
+/// Words shorter than this don't count towards relevance ("a", "is", "of"...)
+const MIN_RELEVANCE_WORD_CHARS: usize = 3;
+
+/// Rank cards by relevance to a text, best first; cards sharing no words are left out
+/// Each shared word scores by how rare it is across the cards (inverse document
+/// frequency), and the total is damped by card length so long cards don't win by size.
+pub fn rank_cards_by_relevance<'a>(cards: &'a [Card], text: &str) -> Vec<(&'a Card, f64)> {
+    let relevant_words = |content: &str| -> HashSet<String> {
+        word_set(content)
+            .into_iter()
+            .filter(|w| w.chars().count() >= MIN_RELEVANCE_WORD_CHARS)
+            .collect()
+    };
+
+    let query = relevant_words(text);
+    let card_words: Vec<HashSet<String>> = cards.iter().map(|c| relevant_words(&c.content)).collect();
+
+    let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+    for words in &card_words {
+        for word in words.iter().filter(|w| query.contains(*w)) {
+            *document_frequency.entry(word.as_str()).or_default() += 1;
+        }
+    }
+
+    let total = cards.len() as f64;
+    let mut ranked: Vec<(&Card, f64)> = cards
+        .iter()
+        .zip(&card_words)
+        .filter_map(|(card, words)| {
+            let score: f64 = words
+                .iter()
+                .filter_map(|w| document_frequency.get(w.as_str()))
+                .map(|df| (1.0 + total / *df as f64).ln())
+                .sum();
+            (score > 0.0).then(|| (card, score / (words.len() as f64).sqrt()))
+        })
+        .collect();
+
+    ranked.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.0.id.cmp(&b.0.id))
+    });
+    ranked
+}
+
+/// The `limit` cards most relevant to a text, excluding the card with `exclude_id`
+pub fn find_related_cards(text: &str, exclude_id: Option<&str>, limit: usize) -> Vec<Card> {
+    let cards: Vec<Card> = lock_cards()
+        .iter()
+        .filter(|c| Some(c.id.as_str()) != exclude_id)
+        .cloned()
+        .collect();
+
+    rank_cards_by_relevance(&cards, text)
+        .into_iter()
+        .take(limit)
+        .map(|(card, _)| card.clone())
+        .collect()
+}
+
+/// Format related cards as a delimited context block, in the given order, within `max_chars`
+/// Cards are cut off to share the budget; returns an empty string when there are none.
+pub fn build_related_context(cards: &[Card], max_chars: usize) -> String {
+    if cards.is_empty() {
+        return String::new();
+    }
+
+    let per_card = max_chars / cards.len();
+    let mut context = String::from("=== Related notes (for reference) ===\n\n");
+    for card in cards {
+        let content = card.content.trim();
+        let mut body: String = content.chars().take(per_card).collect();
+        if body.len() < content.len() {
+            body.push('…');
+        }
+        context.push_str(&format!("## {}\n{}\n\n", extract_title_from_content(content), body));
+    }
+    context.push_str("=== End of related notes ===\n\n");
+    context
+}
+
+/// Estimate the number of LLM tokens in a text (roughly 4 characters per token)
+pub fn estimate_tokens(content: &str) -> usize {
+    content.chars().count().div_ceil(4)
+}
+
+/// Cards with fewer distinct words than this are too short to compare meaningfully
+const MIN_DUPLICATE_WORDS: usize = 5;
+
+/// Distinct lowercase words of a text
+fn word_set(content: &str) -> HashSet<String> {
+    content
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Jaccard similarity of two word sets (shared words / all words)
+fn jaccard_similarity(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let shared = a.intersection(b).count();
+    let total = a.len() + b.len() - shared;
+    if total == 0 {
+        0.0
+    } else {
+        shared as f64 / total as f64
+    }
+}
+
+/// Group cards whose word sets have a Jaccard similarity of at least `threshold`
+/// Similarity is transitive within a group: A~B and B~C put A, B and C together.
+/// Cards with fewer than `MIN_DUPLICATE_WORDS` distinct words are skipped.
+pub fn find_duplicate_groups(cards: &[Card], threshold: f64) -> Vec<Vec<Card>> {
+    let words: Vec<(usize, HashSet<String>)> = cards
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (i, word_set(&c.content)))
+        .filter(|(_, w)| w.len() >= MIN_DUPLICATE_WORDS)
+        .collect();
+
+    // Union-find over card indices
+    let mut parent: Vec<usize> = (0..cards.len()).collect();
+    fn find(parent: &mut [usize], i: usize) -> usize {
+        let mut root = i;
+        while parent[root] != root {
+            root = parent[root];
+        }
+        parent[i] = root;
+        root
+    }
+
+    for (n, (i, a)) in words.iter().enumerate() {
+        for (j, b) in &words[n + 1..] {
+            // Jaccard can't exceed the size ratio, so skip pairs that can't reach the threshold
+            let (small, large) = (a.len().min(b.len()), a.len().max(b.len()));
+            if (small as f64) < threshold * large as f64 {
+                continue;
+            }
+            if jaccard_similarity(a, b) >= threshold {
+                let (root_i, root_j) = (find(&mut parent, *i), find(&mut parent, *j));
+                parent[root_j] = root_i;
+            }
+        }
+    }
+
+    let mut groups: Vec<(usize, Vec<Card>)> = Vec::new();
+    for (i, _) in &words {
+        let root = find(&mut parent, *i);
+        match groups.iter_mut().find(|(r, _)| *r == root) {
+            Some((_, group)) => group.push(cards[*i].clone()),
+            None => groups.push((root, vec![cards[*i].clone()])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(_, group)| group)
+        .filter(|group| group.len() > 1)
+        .collect()
+}
+
+/// Find groups of near-identical cards (`similarity_threshold` between 0 and 1)
+pub fn find_duplicate_cards(similarity_threshold: f64) -> Result<Vec<Vec<Card>>, String> {
+    if !(similarity_threshold > 0.0 && similarity_threshold <= 1.0) {
+        return Err(format!(
+            "Similarity threshold must be between 0 and 1, got {}",
+            similarity_threshold
+        ));
+    }
+
+    let cards = lock_cards().clone();
+    Ok(find_duplicate_groups(&cards, similarity_threshold))
+}
+
+/// Switch the cards storage directory and reload cards from it
+/// Passing None restores the default location. When `migrate` is set, existing
+/// `.md` files are moved from the current directory to the new one.
+pub fn change_cards_directory(new_dir: Option<PathBuf>, migrate: bool) -> Result<Vec<Card>, String> {
+    let old_dir = get_cards_directory()?;
+    let target_dir = match &new_dir {
+        Some(dir) => dir.clone(),
+        None => get_default_cards_directory()?,
+    };
+
+    validate_writable_directory(&target_dir)?;
+
+    // Unsaved edits belong to the files in the old directory
+    flush_pending_saves();
+
+    if migrate && old_dir != target_dir {
+        migrate_card_files(&old_dir, &target_dir)?;
+    }
+
+    set_custom_cards_directory(new_dir);
+    log::info!("Cards directory changed to {:?}", target_dir);
+
+    reload_all_cards()
+}
+
+/// Switch between storing card metadata in front matter and in the sidecar index
+/// Without front matter the `.md` files hold only the markdown, for syncing with
+/// plain markdown apps; the metadata lives in `.index.json`, keyed by file name, so
+/// a file renamed outside the app loses it. Every card file is rewritten in the new
+/// layout. Returns how many cards were rewritten.
+/// The setting only changes once every file is rewritten; after a failure partway the
+/// files are in a mix of both layouts, which still loads since both are always read.
+pub fn change_frontmatter_storage(enabled: bool) -> Result<usize, String> {
+    let mut cards = lock_cards();
+    flush_pending_saves_locked(&cards);
+
+    for card in cards.iter() {
+        write_card_storage_as(&get_card_file_path(&card.id)?, card, enabled)?;
+    }
+    let rewritten = cards.len();
+    set_store_frontmatter(enabled);
+
+    *cards = load_cards_from_files()?;
+    log::info!(
+        "Rewrote {} cards {} front matter",
+        rewritten,
+        if enabled { "with" } else { "without" }
+    );
+    Ok(rewritten)
+}
+
+/// What `reconcile_cards` changed, by filename
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct ReconcileReport {
+    /// Files without an id (or without front matter) that were given one
+    pub ids_assigned: Vec<String>,
+    /// Files whose id was also used by a newer file and were given a new one
+    pub ids_reassigned: Vec<String>,
+    /// Exact copies of a newer file with the same id, removed
+    pub duplicates_removed: Vec<String>,
+    /// Files with unreadable front matter, left untouched
+    pub skipped: Vec<String>,
+    /// Number of cards after reconciling
+    pub total_cards: usize,
+}
+
+/// Rewrite a card file's front matter, keeping fields this app doesn't know about
+/// A file without front matter gets one and keeps its whole text as the content.
+fn edit_front_matter(raw: &str, edit: impl FnOnce(&mut serde_yaml::Mapping)) -> Result<String, String> {
+    let (mut mapping, body) = match raw.strip_prefix("---\n") {
+        None => (serde_yaml::Mapping::new(), raw),
+        Some(rest) => {
+            let end_pos = rest
+                .find("\n---\n")
+                .ok_or("Could not find closing --- for YAML front matter")?;
+            match serde_yaml::from_str(&rest[..end_pos]) {
+                Ok(serde_yaml::Value::Mapping(mapping)) => (mapping, &rest[end_pos + 5..]),
+                _ => return Err("Front matter is not a YAML mapping".to_string()),
+            }
+        }
+    };
+
+    edit(&mut mapping);
+
+    let yaml = serde_yaml::to_string(&mapping)
+        .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
+    Ok(format!("---\n{}---\n{}", yaml, body))
+}
+
+/// Whether a front matter field is missing or empty
+fn is_missing(mapping: &serde_yaml::Mapping, key: &str) -> bool {
+    match mapping.get(key) {
+        None | Some(serde_yaml::Value::Null) => true,
+        Some(serde_yaml::Value::String(s)) => s.trim().is_empty(),
+        Some(_) => false,
+    }
+}
+
+/// Fix id problems in the `.md` files of a directory (see `reconcile_cards`)
+/// Without `frontmatter` files missing an id get an index entry instead of front matter.
+fn reconcile_dir(dir: &Path, now: i64, frontmatter: bool) -> Result<ReconcileReport, String> {
+    let mut report = ReconcileReport::default();
+    let file_name = |path: &Path| path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read cards directory: {}", e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("md"))
+        .collect();
+    paths.sort();
+    let mut index = read_card_index(dir);
+    let mut index_changed = false;
+
+    if !frontmatter {
+        report.ids_assigned = adopt_unindexed_files(dir, &mut index, now);
+        index_changed = !report.ids_assigned.is_empty();
+    }
+
+    // id -> (path, updated_at, content) of every readable card file
+    let mut by_id: HashMap<String, Vec<(PathBuf, i64, String)>> = HashMap::new();
+
+    for path in paths {
+        let Ok(mut raw) = fs::read_to_string(&path) else {
+            report.skipped.push(file_name(&path));
+            continue;
+        };
+
+        // Files stored without front matter have their id in the index
+        let missing_id = !index.contains_key(&index_key(&path)) && lacks_card_id(&raw);
+        if missing_id {
+            let Ok(fixed) = edit_front_matter(&raw, |mapping| {
+                mapping.insert("id".into(), Uuid::new_v4().to_string().into());
+                for key in ["created_at", "updated_at"] {
+                    if is_missing(mapping, key) {
+                        mapping.insert(key.into(), timestamp_value(now));
+                    }
+                }
+            }) else {
+                report.skipped.push(file_name(&path));
+                continue;
+            };
+            write_card_file(&path, &fixed)?;
+            report.ids_assigned.push(file_name(&path));
+            raw = fixed;
+        }
+
+        match parse_card_file(&raw, index.get(&index_key(&path))) {
+            Ok((metadata, content)) => by_id
+                .entry(metadata.id)
+                .or_default()
+                .push((path, metadata.updated_at, content)),
+            Err(_) => report.skipped.push(file_name(&path)),
+        }
+    }
+
+    for (_, mut files) in by_id.into_iter().filter(|(_, files)| files.len() > 1) {
+        // Newest first; the newest file keeps the id
+        files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let (_, _, kept_content) = files[0].clone();
+
+        for (path, _, content) in files.into_iter().skip(1) {
+            if content == kept_content {
+                let size = file_size(&path);
+                note_own_write(&path);
+                fs::remove_file(&path).map_err(|e| format!("Failed to remove {:?}: {}", path, e))?;
+                adjust_storage_used(&path, size, 0);
+                index_changed |= index.remove(&index_key(&path)).is_some();
+                report.duplicates_removed.push(file_name(&path));
+            } else {
+                let raw = fs::read_to_string(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+                match index.get_mut(&index_key(&path)) {
+                    Some(metadata) if parse_markdown_with_frontmatter(&raw).is_err() => {
+                        metadata.id = Uuid::new_v4().to_string();
+                        index_changed = true;
+                    }
+                    _ => {
+                        let fixed = edit_front_matter(&raw, |mapping| {
+                            mapping.insert("id".into(), Uuid::new_v4().to_string().into());
+                        })?;
+                        write_card_file(&path, &fixed)?;
+                    }
+                }
+                report.ids_reassigned.push(file_name(&path));
+            }
+        }
+    }
+
+    if index_changed {
+        write_card_index(dir, &index)?;
+    }
+    Ok(report)
+}
+
+/// Repair the cards directory after external edits (e.g. through the MCP server)
+/// Files without an id get one; when several files share an id the newest keeps it,
+/// exact copies are removed and the rest get new ids. Valid cards are left alone, so
+/// this is safe to run repeatedly. Cards are reloaded afterwards.
+pub fn reconcile_cards() -> Result<ReconcileReport, String> {
+    let mut cards = lock_cards();
+    flush_pending_saves_locked(&cards);
+
+    let mut report = reconcile_dir(
+        &get_cards_directory()?,
+        chrono::Utc::now().timestamp(),
+        store_frontmatter(),
+    )?;
+
+    *cards = load_cards_from_files()?;
+    report.total_cards = cards.len();
+
+    log::info!(
+        "Reconciled cards: {} ids assigned, {} reassigned, {} duplicates removed, {} skipped",
+        report.ids_assigned.len(),
+        report.ids_reassigned.len(),
+        report.duplicates_removed.len(),
+        report.skipped.len()
+    );
+    Ok(report)
+}
+
+/// What `import_markdown_folder` did, by source filename
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct ImportReport {
+    /// Files copied into the cards directory
+    pub imported: Vec<String>,
+    /// Files that were unreadable, had broken front matter, or whose id already exists
+    pub skipped: Vec<String>,
+    /// Number of cards after importing
+    pub total_cards: usize,
+}
+
+/// Copy the `.md` files of `src` into the cards directory `dest` (see `import_markdown_folder`)
+/// `known_ids` holds the ids already in use and is updated with the imported ones.
+fn import_dir(src: &Path, dest: &Path, known_ids: &mut HashSet<String>, now: i64) -> Result<ImportReport, String> {
+    let mut report = ImportReport::default();
+    let file_name = |path: &Path| path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(src)
+        .map_err(|e| format!("Failed to read import directory: {}", e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("md"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let Ok(raw) = fs::read_to_string(&path) else {
+            report.skipped.push(file_name(&path));
+            continue;
+        };
+
+        let card_file = match parse_markdown_with_frontmatter(&raw) {
+            // Already a card; keep the file as it is unless the id is taken
+            Ok((metadata, _)) if known_ids.contains(&metadata.id) => None,
+            Ok(_) => Some(raw),
+            // Plain markdown (or front matter from another app): add the card fields,
+            // dated by the file's modification time
+            Err(_) => {
+                let modified = fs::metadata(&path)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map_or(now, |d| d.as_secs() as i64);
+                edit_front_matter(&raw, |mapping| {
+                    mapping.insert("id".into(), Uuid::new_v4().to_string().into());
+                    for key in ["created_at", "updated_at"] {
+                        if is_missing(mapping, key) {
+                            mapping.insert(key.into(), timestamp_value(modified));
+                        }
+                    }
+                })
+                .ok()
+            }
+        };
+
+        // Only write what will load as a card
+        let Some((card_file, metadata)) = card_file.and_then(|file| {
+            let (metadata, _) = parse_markdown_with_frontmatter(&file).ok()?;
+            Some((file, metadata))
+        }) else {
+            report.skipped.push(file_name(&path));
+            continue;
+        };
+
+        let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let target = dest.join(get_unique_filename(dest, &sanitize_filename(&stem), None));
+        write_card_file(&target, &card_file)?;
+
+        known_ids.insert(metadata.id);
+        report.imported.push(file_name(&path));
+    }
+
+    Ok(report)
+}
+
+/// Import a folder of markdown notes (e.g. an Obsidian vault) as cards
+/// Files that are already cards are copied as they are; other `.md` files get an
+/// id and timestamps added to their front matter, keeping their content. Files are
+/// named after the source file, with a " (N)" suffix on collisions. Cards are
+/// reloaded afterwards.
+pub fn import_markdown_folder(path: &Path) -> Result<ImportReport, String> {
+    let cards_dir = get_cards_directory()?;
+    if !path.is_dir() {
+        return Err(format!("Not a directory: {:?}", path));
+    }
+    if path.canonicalize().ok() == cards_dir.canonicalize().ok() {
+        return Err("Can't import the cards directory into itself".to_string());
+    }
+
+    // Counts every note in the folder, including any that end up skipped
+    let incoming: u64 = fs::read_dir(path)
+        .map_err(|e| format!("Failed to read import directory: {}", e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("md"))
+        .map(|path| file_size(&path))
+        .sum();
+    check_storage_limit(incoming)?;
+
+    let mut cards = lock_cards();
+    flush_pending_saves_locked(&cards);
+
+    let mut known_ids: HashSet<String> = cards.iter().map(|c| c.id.clone()).collect();
+    let mut report = import_dir(path, &cards_dir, &mut known_ids, chrono::Utc::now().timestamp())?;
+
+    *cards = load_cards_from_files()?;
+    report.total_cards = cards.len();
+
+    log::info!(
+        "Imported {} notes from {:?} ({} skipped)",
+        report.imported.len(),
+        path,
+        report.skipped.len()
+    );
+    Ok(report)
+}
+
+/// Why a card file failed to load
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CardFileProblem {
+    /// The file couldn't be read at all (permissions, I/O error)
+    Unreadable,
+    /// Not valid UTF-8, e.g. saved as UTF-16 or a legacy code page
+    BadEncoding,
+    MissingFrontMatter,
+    /// Front matter that isn't valid YAML or lacks/garbles id and timestamps
+    InvalidFrontMatter,
+}
+
+/// A card file that doesn't load, as reported by `diagnose_card_files`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CardFileDiagnosis {
+    pub path: String,
+    pub problem: CardFileProblem,
+    pub message: String,
+}
+
+/// Decode card file bytes to text, replacing what can't be decoded
+/// A UTF-8 byte order mark is dropped, UTF-16 with a byte order mark is decoded,
+/// anything else is read as UTF-8 with invalid bytes replaced. CRLF becomes LF.
+fn decode_card_bytes(bytes: &[u8]) -> String {
+    let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| from_bytes([pair[0], pair[1]])).collect();
+        String::from_utf16_lossy(&units)
+    };
+
+    let text = match bytes {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    };
+    text.replace("\r\n", "\n")
+}
+
+/// Check why a card file wouldn't load, None if it loads fine
+fn diagnose_file(path: &Path, indexed: Option<&CardMetadata>) -> Option<CardFileDiagnosis> {
+    let diagnosis = |problem, message: String| {
+        Some(CardFileDiagnosis {
+            path: path.to_string_lossy().to_string(),
+            problem,
+            message,
+        })
+    };
+
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => return diagnosis(CardFileProblem::Unreadable, e.to_string()),
+    };
+    let text = match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => return diagnosis(CardFileProblem::BadEncoding, e.to_string()),
+    };
+
+    match parse_card_file(&text, indexed) {
+        Ok(_) => None,
+        Err(e) if !text.starts_with("---\n") => diagnosis(CardFileProblem::MissingFrontMatter, e),
+        Err(e) => diagnosis(CardFileProblem::InvalidFrontMatter, e),
+    }
+}
+
+/// Diagnose every `.md` file in a directory, sorted by path
+fn diagnose_dir(dir: &Path) -> Result<Vec<CardFileDiagnosis>, String> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read cards directory: {}", e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("md"))
+        .collect();
+    paths.sort();
+
+    let index = read_card_index(dir);
+    Ok(paths
+        .iter()
+        .filter_map(|path| diagnose_file(path, index.get(&index_key(path))))
+        .collect())
+}
+
+/// List the card files that don't load and why
+/// These cards are missing from the app; `repair_card_file` can usually fix them.
+pub fn diagnose_card_files() -> Result<Vec<CardFileDiagnosis>, String> {
+    diagnose_dir(&get_cards_directory()?)
+}
+
+/// Turn a broken card file's content into one that loads (see `repair_card_file`)
+fn repair_card_text(raw: &str, now: i64) -> Result<String, String> {
+    let fill_missing = |mapping: &mut serde_yaml::Mapping| {
+        if is_missing(mapping, "id") {
+            mapping.insert("id".into(), Uuid::new_v4().to_string().into());
+        }
+        for key in ["created_at", "updated_at"] {
+            let valid = match mapping.get(key) {
+                Some(serde_yaml::Value::Number(n)) => n.is_i64(),
+                Some(serde_yaml::Value::String(text)) => card_timestamp::parse(text).is_some(),
+                _ => false,
+            };
+            if !valid {
+                mapping.insert(key.into(), timestamp_value(now));
+            }
+        }
+    };
+
+    // Front matter that can't be fixed in place stays in the content, below a new one
+    let repaired = edit_front_matter(raw, fill_missing)
+        .or_else(|_| edit_front_matter("", fill_missing).map(|front_matter| front_matter + raw))?;
+
+    parse_markdown_with_frontmatter(&repaired)?;
+    Ok(repaired)
+}
+
+/// Save the original bytes of a card file as `<name>.md.bak` next to it
+/// The backup isn't a `.md` file, so it isn't loaded as a card.
+fn backup_card_file(path: &Path, bytes: &[u8]) -> Result<PathBuf, String> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("Invalid card path: {:?}", path))?;
+    let mut backup_name = name.to_os_string();
+    backup_name.push(".bak");
+    let backup_path = path.with_file_name(backup_name);
+
+    let old_size = file_size(&backup_path);
+    fs::write(&backup_path, bytes).map_err(|e| format!("Failed to back up card file: {}", e))?;
+    adjust_storage_used(&backup_path, old_size, bytes.len() as u64);
+    Ok(backup_path)
+}
+
+/// Repair a card file that doesn't load
+/// The original is kept as `<name>.md.bak`, then the file is re-encoded as UTF-8
+/// (undecodable bytes become U+FFFD) and gets an id and timestamps where they are
+/// missing or invalid. Front matter that isn't valid YAML is kept as text at the top
+/// of the note. Cards are reloaded afterwards and the repaired card is returned.
+pub fn repair_card_file(path: &Path) -> Result<Card, String> {
+    let cards_dir = get_cards_directory()?;
+    let in_cards_dir = path
+        .parent()
+        .and_then(|dir| dir.canonicalize().ok())
+        .is_some_and(|dir| Some(dir) == cards_dir.canonicalize().ok());
+    if !in_cards_dir || path.extension().and_then(|s| s.to_str()) != Some("md") {
+        return Err(format!("Not a card file: {:?}", path));
+    }
+
+    let mut cards = lock_cards();
+    flush_pending_saves_locked(&cards);
+
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let repaired = repair_card_text(&decode_card_bytes(&bytes), chrono::Utc::now().timestamp())?;
+    let backup_path = backup_card_file(path, &bytes)?;
+    write_card_file(path, &repaired)?;
+    log::info!("Repaired card file {:?} (original saved as {:?})", path, backup_path);
+
+    let card = load_card_from_file(path, None)?;
+    *cards = load_cards_from_files()?;
+    Ok(card)
+}
+
 /// Reload all cards from the file system
 /// This is useful when cards are modified externally (e.g., by MCP server)
+/// Unsaved edits are written first so the reload doesn't discard them.
 pub fn reload_all_cards() -> Result<Vec<Card>, String> {
+    let mut cards_lock = lock_cards();
+    flush_pending_saves_locked(&cards_lock);
+
     let cards = load_cards_from_files()?;
 
     // Update the global CARDS state
-    let mut cards_lock = CARDS.lock().unwrap();
     *cards_lock = cards.clone();
 
     log::info!("Reloaded {} cards from file system", cards.len());
     Ok(cards)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn sample_card(color: Option<&str>) -> Card {
+        let mut card = Card {
+            id: "test-id".to_string(),
+            content: "# Title\n\nBody".to_string(),
+            created_at: 1,
+            updated_at: 2,
+            word_count: 0,
+            char_count: 0,
+            pinned: true,
+            color: color.map(|c| c.to_string()),
+            locked: false,
+            archived: false,
+            tags: Vec::new(),
+            content_hash: String::new(),
+        };
+        card.refresh_derived();
+        card
+    }
+
+    #[test]
+    fn test_changed_card_ids() {
+        let unchanged = sample_card(None);
+        let mut edited = sample_card(None);
+        edited.id = "edited".to_string();
+        let mut added = sample_card(None);
+        added.id = "added".to_string();
+
+        let known: HashMap<String, String> = [
+            (unchanged.id.clone(), unchanged.content_hash.clone()),
+            (edited.id.clone(), content_hash("# Title\n\nOlder body")),
+            ("deleted".to_string(), content_hash("gone")),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(unchanged.content_hash, content_hash("# Title\n\nBody"));
+        assert_eq!(
+            changed_card_ids(&[unchanged, edited, added], &known),
+            vec!["added", "deleted", "edited"]
+        );
+    }
+
+    #[test]
+    fn test_frontmatter_roundtrip_with_color() {
+        let card = sample_card(Some("#ffcc00"));
+        let file = create_markdown_with_frontmatter(&card).unwrap();
+        let (metadata, content) = parse_markdown_with_frontmatter(&file).unwrap();
+
+        assert_eq!(metadata.id, card.id);
+        assert_eq!(metadata.color.as_deref(), Some("#ffcc00"));
+        assert!(metadata.pinned);
+        assert_eq!(content, card.content);
+    }
+
+    #[test]
+    fn test_rank_cards_by_relevance() {
+        let make = |id: &str, content: &str| {
+            let mut card = sample_card(None);
+            card.id = id.to_string();
+            card.content = content.to_string();
+            card
+        };
+        let cards = vec![
+            make("garden", "# Garden\n\nPlant tomatoes and basil in the garden bed."),
+            make("shopping", "# Shopping\n\nBuy milk, bread and tomatoes."),
+            make("meeting", "# Meeting\n\nDiscuss the quarterly budget."),
+        ];
+
+        let ranked = rank_cards_by_relevance(&cards, "When should I plant basil in my garden?");
+        let ids: Vec<&str> = ranked.iter().map(|(card, _)| card.id.as_str()).collect();
+        assert_eq!(ids, ["garden"]);
+
+        let ranked = rank_cards_by_relevance(&cards, "tomatoes garden");
+        let ids: Vec<&str> = ranked.iter().map(|(card, _)| card.id.as_str()).collect();
+        assert_eq!(ids, ["garden", "shopping"]);
+    }
+
+    #[test]
+    fn test_build_related_context() {
+        assert_eq!(build_related_context(&[], 1000), "");
+
+        let mut card = sample_card(None);
+        card.content = format!("# Long\n\n{}", "x".repeat(500));
+        let context = build_related_context(&[card], 100);
+        assert!(context.starts_with("=== Related notes"));
+        assert!(context.contains("## Long\n"));
+        assert!(context.contains('…'));
+        assert!(context.len() < 300);
+    }
+
+    #[test]
+    fn test_dir_size_counts_subdirectories() {
+        let dir = TempDir::new("size");
+        fs::create_dir_all(dir.join(".trash")).unwrap();
+        fs::write(dir.join("a.md"), [0u8; 100]).unwrap();
+        fs::write(dir.join(".trash").join("b.md"), [0u8; 50]).unwrap();
+
+        assert_eq!(dir_size(&dir), 150);
+        assert_eq!(dir_size(&dir.join("missing")), 0);
+    }
+
+    #[test]
+    fn test_frontmatter_timestamps() {
+        let mut card = sample_card(None);
+        card.created_at = 1_714_564_800;
+        card.updated_at = 1_714_568_400;
+        let file = create_markdown_with_frontmatter(&card).unwrap();
+        assert!(file.contains("2024-05-01T12:00:00Z"));
+        assert!(file.contains("2024-05-01T13:00:00Z"));
+
+        let (metadata, _) = parse_markdown_with_frontmatter(&file).unwrap();
+        assert_eq!((metadata.created_at, metadata.updated_at), (1_714_564_800, 1_714_568_400));
+
+        // Epoch seconds from older versions and RFC3339 with an offset both parse
+        let mixed = "---\nid: x\ncreated_at: 1714564800\nupdated_at: '2024-05-01T16:00:00+03:00'\n---\nBody";
+        let (metadata, _) = parse_markdown_with_frontmatter(mixed).unwrap();
+        assert_eq!((metadata.created_at, metadata.updated_at), (1_714_564_800, 1_714_568_400));
+
+        let invalid = "---\nid: x\ncreated_at: yesterday\nupdated_at: 1\n---\nBody";
+        assert!(parse_markdown_with_frontmatter(invalid).is_err());
+    }
+
+    #[test]
+    fn test_frontmatter_without_color() {
+        let card = sample_card(None);
+        let file = create_markdown_with_frontmatter(&card).unwrap();
+        assert!(!file.contains("color"));
+
+        let legacy = "---\nid: old\ncreated_at: 1\nupdated_at: 2\n---\nBody";
+        let (metadata, _) = parse_markdown_with_frontmatter(legacy).unwrap();
+        assert_eq!(metadata.color, None);
+        assert!(!metadata.pinned);
+    }
+
+    #[test]
+    fn test_truncation_keeps_multibyte_characters_whole() {
+        assert_eq!(truncate_chars("Hyvää päivää", 4), "Hyvä");
+        assert_eq!(truncate_chars("ab😀cd", 3), "ab😀");
+        assert_eq!(truncate_chars("short", 10), "short");
+
+        // The 50th character is multi-byte and straddles byte 50
+        let long_line = format!("{}ä{}", "a".repeat(49), "ö".repeat(10));
+        assert_eq!(extract_title_from_content(&long_line), format!("{}ä...", "a".repeat(49)));
+        let emoji_line = "😀".repeat(60);
+        assert_eq!(extract_title_from_content(&emoji_line), format!("{}...", "😀".repeat(50)));
+
+        let name = sanitize_filename(&format!("{}ää", "a".repeat(99)));
+        assert_eq!(name, "a".repeat(99));
+        assert!(sanitize_filename(&"😀".repeat(30)).len() <= 100);
+    }
+
+    #[test]
+    fn test_prefix_copy_title() {
+        assert_eq!(prefix_copy_title("# Groceries\n- milk\n"), "# Copy of Groceries\n- milk\n");
+        assert_eq!(prefix_copy_title("intro\n## Plan\ntext"), "intro\n## Copy of Plan\ntext");
+        assert_eq!(prefix_copy_title("just text"), "# Copy of just text\n\njust text");
+    }
+
+    #[test]
+    fn test_card_stats() {
+        let now = 1_700_000_000;
+        let mut old = sample_card(None);
+        old.created_at = now - 30 * 24 * 60 * 60;
+        old.updated_at = now - 10 * 24 * 60 * 60;
+        old.word_count = 10;
+        let mut recent = sample_card(None);
+        recent.created_at = now - 60;
+        recent.updated_at = now - 60;
+        recent.word_count = 4;
+
+        let stats = CardStats::from_cards(&[old, recent], now);
+        assert_eq!(stats.total_cards, 2);
+        assert_eq!(stats.total_words, 14);
+        assert_eq!(stats.average_words, 7.0);
+        assert_eq!(stats.oldest_created_at, Some(now - 30 * 24 * 60 * 60));
+        assert_eq!(stats.newest_created_at, Some(now - 60));
+        assert_eq!(stats.modified_last_7_days, 1);
+
+        let empty = CardStats::from_cards(&[], now);
+        assert_eq!(empty.average_words, 0.0);
+        assert_eq!(empty.oldest_created_at, None);
+    }
+
+    #[test]
+    fn test_sort_cards() {
+        let make = |id: &str, content: &str, created_at: i64, pinned: bool| {
+            let mut card = sample_card(None);
+            card.id = id.to_string();
+            card.content = content.to_string();
+            card.created_at = created_at;
+            card.pinned = pinned;
+            card
+        };
+        let mut cards = vec![
+            make("c", "# banana", 2, false),
+            make("a", "# Apple", 2, false),
+            make("b", "# cherry", 1, false),
+            make("d", "# Zebra", 3, true),
+        ];
+
+        sort_cards(&mut cards, "title", false).unwrap();
+        let ids: Vec<&str> = cards.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["d", "a", "c", "b"]);
+
+        sort_cards(&mut cards, "created", true).unwrap();
+        let ids: Vec<&str> = cards.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["d", "a", "c", "b"]);
+
+        // Equal timestamps stay in id order whichever way the list is sorted
+        sort_cards(&mut cards, "created", false).unwrap();
+        let ids: Vec<&str> = cards.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["d", "b", "a", "c"]);
+
+        assert!(sort_cards(&mut cards, "size", false).is_err());
+    }
+
+    #[test]
+    fn test_locked_roundtrip() {
+        let mut card = sample_card(None);
+        assert!(ensure_unlocked(&card).is_ok());
+        assert!(!create_markdown_with_frontmatter(&card).unwrap().contains("locked"));
+
+        card.locked = true;
+        assert!(ensure_unlocked(&card).unwrap_err().contains("is locked"));
+        let file = create_markdown_with_frontmatter(&card).unwrap();
+        let (metadata, _) = parse_markdown_with_frontmatter(&file).unwrap();
+        assert!(metadata.locked);
+    }
+
+    #[test]
+    fn test_diagnose_and_repair_card_files() {
+        let dir = TempDir::new("diagnose");
+        fs::write(dir.join("ok.md"), "---\nid: ok\ncreated_at: 1\nupdated_at: 2\n---\n# Fine").unwrap();
+        fs::write(dir.join("latin1.md"), b"---\nid: l1\ncreated_at: 1\nupdated_at: 2\n---\nCaf\xe9").unwrap();
+        fs::write(dir.join("plain.md"), "# Just markdown").unwrap();
+        fs::write(dir.join("yaml.md"), "---\nid: [unclosed\n---\nBody").unwrap();
+
+        let problems: Vec<(String, CardFileProblem)> = diagnose_dir(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|d| (Path::new(&d.path).file_name().unwrap().to_string_lossy().to_string(), d.problem))
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                ("latin1.md".to_string(), CardFileProblem::BadEncoding),
+                ("plain.md".to_string(), CardFileProblem::MissingFrontMatter),
+                ("yaml.md".to_string(), CardFileProblem::InvalidFrontMatter),
+            ]
+        );
+
+        let latin1 = repair_card_text(&decode_card_bytes(&fs::read(dir.join("latin1.md")).unwrap()), 100).unwrap();
+        let (metadata, content) = parse_markdown_with_frontmatter(&latin1).unwrap();
+        assert_eq!((metadata.id.as_str(), content.as_str()), ("l1", "Caf\u{FFFD}"));
+
+        let plain = repair_card_text("# Just markdown", 100).unwrap();
+        let (metadata, content) = parse_markdown_with_frontmatter(&plain).unwrap();
+        assert_eq!((metadata.created_at, content.as_str()), (100, "# Just markdown"));
+
+        let yaml = repair_card_text("---\nid: [unclosed\n---\nBody", 100).unwrap();
+        assert!(parse_markdown_with_frontmatter(&yaml).unwrap().1.contains("id: [unclosed"));
+    }
+
+    #[test]
+    fn test_own_writes_are_recognized() {
+        let dir = TempDir::new("own-writes");
+        let path = dir.join(format!("{}.md", Uuid::new_v4()));
+        assert!(!is_own_write(&path));
+
+        write_card_file(&path, "# Mine").unwrap();
+        assert!(is_own_write(&path));
+        assert!(!is_own_write(&dir.join("someone-else.md")));
+    }
+
+    #[test]
+    fn test_backup_card_file() {
+        let dir = TempDir::new("repair-backup");
+        let path = dir.join("broken.md");
+        fs::write(&path, b"Caf\xe9").unwrap();
+
+        let backup = backup_card_file(&path, &fs::read(&path).unwrap()).unwrap();
+        assert_eq!(backup, dir.join("broken.md.bak"));
+        assert_eq!(fs::read(&backup).unwrap(), b"Caf\xe9");
+        assert_eq!(diagnose_dir(&dir).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_decode_card_bytes() {
+        assert_eq!(decode_card_bytes(b"\xEF\xBB\xBF---\r\nid: a\r\n"), "---\nid: a\n");
+        let utf16: Vec<u8> = [0xFF, 0xFE].into_iter().chain("K\u{e4}si".encode_utf16().flat_map(u16::to_le_bytes)).collect();
+        assert_eq!(decode_card_bytes(&utf16), "K\u{e4}si");
+    }
+
+    #[test]
+    fn test_archived_roundtrip() {
+        let mut card = sample_card(None);
+        assert!(!create_markdown_with_frontmatter(&card).unwrap().contains("archived"));
+
+        card.archived = true;
+        card.content = "# Renamed\n\nNew body".to_string();
+        let file = create_markdown_with_frontmatter(&card).unwrap();
+        let (metadata, content) = parse_markdown_with_frontmatter(&file).unwrap();
+        assert!(metadata.archived);
+        assert_eq!(content, "# Renamed\n\nNew body");
+    }
+
+    #[test]
+    fn test_tags_roundtrip_and_other_formats() {
+        let mut card = sample_card(None);
+        assert!(!create_markdown_with_frontmatter(&card).unwrap().contains("tags"));
+
+        card.tags = vec!["work".to_string(), "2024".to_string()];
+        let file = create_markdown_with_frontmatter(&card).unwrap();
+        assert_eq!(parse_markdown_with_frontmatter(&file).unwrap().0.tags, ["work", "2024"]);
+
+        let tags_of = |yaml: &str| {
+            let file = format!("---\nid: a\ncreated_at: 1\nupdated_at: 1\n{}\n---\nBody", yaml);
+            parse_markdown_with_frontmatter(&file).unwrap().0.tags
+        };
+        assert_eq!(tags_of("tags: [\"#idea\", 2024, \" \"]"), ["idea", "2024"]);
+        assert_eq!(tags_of("tags: \"idea, #later\""), ["idea", "later"]);
+        assert!(tags_of("tags:").is_empty());
+    }
+
+    #[test]
+    fn test_cards_without_front_matter_use_index() {
+        let dir = TempDir::new("index");
+        let card = sample_card(Some("#ffcc00"));
+        fs::write(dir.join("Title.md"), &card.content).unwrap();
+        write_card_index(&dir, &BTreeMap::from([("Title.md".to_string(), card_metadata(&card))])).unwrap();
+
+        let index = read_card_index(&dir);
+        let loaded = load_card_from_file(&dir.join("Title.md"), index.get("Title.md")).unwrap();
+        assert_eq!((loaded.id.as_str(), loaded.content.as_str()), ("test-id", "# Title\n\nBody"));
+        assert_eq!((loaded.pinned, loaded.color.as_deref()), (true, Some("#ffcc00")));
+
+        // Indexed files are valid cards, not files missing an id
+        assert_eq!(reconcile_dir(&dir, 100, false).unwrap(), ReconcileReport::default());
+        assert!(diagnose_dir(&dir).unwrap().is_empty());
+
+        // Notes from other apps are adopted into the index and left as they are
+        fs::write(dir.join("Plain.md"), "# From another app").unwrap();
+        fs::write(dir.join("Tagged.md"), "---\ntags: [x]\n---\nBody").unwrap();
+        let report = reconcile_dir(&dir, 100, false).unwrap();
+        assert_eq!(report.ids_assigned, ["Plain.md", "Tagged.md"]);
+        assert_eq!(fs::read_to_string(dir.join("Plain.md")).unwrap(), "# From another app");
+        let index = read_card_index(&dir);
+        assert_eq!(index.keys().collect::<Vec<_>>(), ["Plain.md", "Tagged.md", "Title.md"]);
+        assert_eq!(index["Plain.md"].created_at, 100);
+        assert_eq!(reconcile_dir(&dir, 200, false).unwrap(), ReconcileReport::default());
+        fs::remove_file(dir.join("Plain.md")).unwrap();
+        fs::remove_file(dir.join("Tagged.md")).unwrap();
+        write_card_index(&dir, &BTreeMap::from([("Title.md".to_string(), card_metadata(&card))])).unwrap();
+
+        // Written without front matter, the index entry comes with the file
+        write_card_storage_as(&dir.join("New.md"), &sample_card(None), false).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("New.md")).unwrap(), "# Title\n\nBody");
+        assert!(read_card_index(&dir).contains_key("New.md"));
+        fs::remove_file(dir.join("New.md")).unwrap();
+        write_card_index(&dir, &BTreeMap::from([("Title.md".to_string(), card_metadata(&card))])).unwrap();
+
+        rename_card_file(&dir.join("Title.md"), &dir.join("Renamed.md")).unwrap();
+        let index = read_card_index(&dir);
+        assert_eq!(index.keys().collect::<Vec<_>>(), ["Renamed.md"]);
+
+        write_card_index(&dir, &BTreeMap::new()).unwrap();
+        assert!(!dir.join(CARD_INDEX_FILE).exists());
+    }
+
+    #[test]
+    fn test_reconcile_dir() {
+        let dir = TempDir::new("reconcile");
+        let card_file = |id: &str, updated_at: i64, body: &str| {
+            format!("---\nid: {}\ncreated_at: 1\nupdated_at: {}\n---\n{}", id, updated_at, body)
+        };
+        fs::write(dir.join("a.md"), card_file("same", 5, "# A")).unwrap();
+        fs::write(dir.join("b.md"), card_file("same", 3, "# A")).unwrap();
+        fs::write(dir.join("c.md"), card_file("same", 1, "# C")).unwrap();
+        fs::write(dir.join("d.md"), "# No front matter").unwrap();
+        fs::write(dir.join("e.md"), "---\ntags: [x]\n---\n# No id").unwrap();
+        fs::write(dir.join("f.md"), card_file("valid", 1, "# Valid")).unwrap();
+
+        // Track this directory's size as the app would for the cards directory
+        *lock_or_recover(&STORAGE_USED, "storage usage") = Some((dir.to_path_buf(), dir_size(&dir)));
+
+        let report = reconcile_dir(&dir, 100, true).unwrap();
+        assert_eq!(storage_used(&dir), dir_size(&dir));
+        assert_eq!(report.ids_assigned, ["d.md", "e.md"]);
+        assert_eq!(report.duplicates_removed, ["b.md"]);
+        assert_eq!(report.ids_reassigned, ["c.md"]);
+        assert!(!dir.join("b.md").exists());
+        assert_eq!(fs::read_to_string(dir.join("f.md")).unwrap(), card_file("valid", 1, "# Valid"));
+
+        let (metadata, content) =
+            parse_markdown_with_frontmatter(&fs::read_to_string(dir.join("e.md")).unwrap()).unwrap();
+        assert_eq!(metadata.updated_at, 100);
+        assert_eq!(content, "# No id");
+        assert!(fs::read_to_string(dir.join("e.md")).unwrap().contains("tags"));
+
+        // A second run finds nothing left to fix
+        assert_eq!(reconcile_dir(&dir, 200, true).unwrap(), ReconcileReport::default());
+    }
+
+    #[test]
+    fn test_import_dir() {
+        let root = TempDir::new("import");
+        let (src, dest) = (root.join("vault"), root.join("cards"));
+        fs::create_dir_all(&src).unwrap();
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(src.join("a.md"), "# Plain note").unwrap();
+        fs::write(src.join("b.md"), "---\ntags: [x]\n---\nObsidian note").unwrap();
+        fs::write(src.join("c.md"), "---\nid: card-c\ncreated_at: 1\nupdated_at: 2\n---\n# C").unwrap();
+        fs::write(src.join("d.md"), "---\nid: taken\ncreated_at: 1\nupdated_at: 2\n---\n# D").unwrap();
+        fs::write(src.join("e.md"), "---\ntags: [unclosed\n---\nBroken").unwrap();
+        fs::write(src.join("notes.txt"), "not markdown").unwrap();
+        fs::write(dest.join("a.md"), "existing").unwrap();
+
+        let mut known_ids = HashSet::from(["taken".to_string()]);
+        let report = import_dir(&src, &dest, &mut known_ids, 100).unwrap();
+        assert_eq!(report.imported, ["a.md", "b.md", "c.md"]);
+        assert_eq!(report.skipped, ["d.md", "e.md"]);
+        assert!(known_ids.contains("card-c"));
+
+        // The existing a.md is kept; the import gets a suffix
+        assert_eq!(fs::read_to_string(dest.join("a.md")).unwrap(), "existing");
+        let (_, content) = parse_markdown_with_frontmatter(&fs::read_to_string(dest.join("a (2).md")).unwrap()).unwrap();
+        assert_eq!(content, "# Plain note");
+
+        let b = fs::read_to_string(dest.join("b.md")).unwrap();
+        assert!(b.contains("tags"));
+        assert!(parse_markdown_with_frontmatter(&b).is_ok());
+        assert_eq!(
+            fs::read_to_string(dest.join("c.md")).unwrap(),
+            fs::read_to_string(src.join("c.md")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_raw_card() {
+        let raw = "---\nid: card-1\ncreated_at: 1\nupdated_at: 2\npinned: true\ncolor: '#ffcc00'\n---\n# Title\n";
+        let (metadata, content) = parse_raw_card("card-1", raw).unwrap();
+        assert!(metadata.pinned);
+        assert_eq!(content, "# Title\n");
+
+        assert!(parse_raw_card("card-2", raw).is_err());
+        assert!(parse_raw_card("card-1", "# No front matter").is_err());
+        assert!(parse_raw_card("card-1", "---\nid: [unclosed\n---\nbody").is_err());
+        assert!(parse_raw_card(
+            "card-1",
+            "---\nid: card-1\ncreated_at: 1\nupdated_at: 2\ncolor: 'not a color!'\n---\nbody"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_find_duplicate_groups() {
+        let make = |id: &str, content: &str| {
+            let mut card = sample_card(None);
+            card.id = id.to_string();
+            card.content = content.to_string();
+            card
+        };
+        let cards = vec![
+            make("a", "# Shopping\nmilk eggs bread butter cheese apples"),
+            make("b", "Short note"),
+            make("c", "# Meeting notes\nDiscuss budget, hiring and the roadmap"),
+            make("d", "# shopping\nMilk, eggs, bread, butter, cheese, apples, coffee"),
+            make("e", "Short note"),
+        ];
+
+        let groups = find_duplicate_groups(&cards, 0.8);
+        assert_eq!(groups.len(), 1);
+        let ids: Vec<&str> = groups[0].iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["a", "d"]);
+
+        assert!(find_duplicate_groups(&cards, 1.0).is_empty());
+    }
+
+    #[test]
+    fn test_title_from_atx_heading() {
+        assert_eq!(extract_title_from_content("intro\n# Shopping list\n- milk"), "Shopping list");
+        assert_eq!(extract_title_from_content("#NoSpace\ntext"), "NoSpace");
+    }
+
+    #[test]
+    fn test_title_from_setext_heading() {
+        assert_eq!(extract_title_from_content("Meeting notes\n=============\nbody"), "Meeting notes");
+        assert_eq!(extract_title_from_content("Subtitle\n---\nbody"), "Subtitle");
+        // The first heading wins, whichever style it uses
+        assert_eq!(extract_title_from_content("First\n===\n# Second"), "First");
+        assert_eq!(extract_title_from_content("# First\nSecond\n==="), "First");
+    }
+
+    #[test]
+    fn test_title_from_front_matter() {
+        let content = "---\ntitle: Explicit title\n---\n# Heading\nbody";
+        assert_eq!(extract_title_from_content(content), "Explicit title");
+
+        // Front matter without a title falls through to the heading, not the YAML lines
+        let content = "---\ntags: [a]\n---\n# Heading\nbody";
+        assert_eq!(extract_title_from_content(content), "Heading");
+    }
+
+    #[test]
+    fn test_title_first_line_fallback() {
+        assert_eq!(extract_title_from_content("\n  just some text\nmore"), "just some text");
+        assert_eq!(extract_title_from_content(""), "Note");
+        assert_eq!(extract_title("  \n\n"), None);
+    }
+
+    #[test]
+    fn test_rename_with_shared_title() {
+        let dir = TempDir::new("rename");
+        let first = dir.join("Same.md");
+        let second = dir.join("Same (2).md");
+        fs::write(&first, "").unwrap();
+        fs::write(&second, "").unwrap();
+
+        // Editing either card without changing the title keeps its file
+        assert_eq!(get_renamed_card_path(&dir, &first, "# Same\nedited a"), None);
+        assert_eq!(get_renamed_card_path(&dir, &second, "# Same\nedited b"), None);
+
+        // A real title change moves the file, and its own old name isn't a collision
+        assert_eq!(
+            get_renamed_card_path(&dir, &second, "# Other\nb"),
+            Some(dir.join("Other.md"))
+        );
+        assert_eq!(get_unique_filename(&dir, "Same", Some(&first)), "Same.md");
+        assert_eq!(get_unique_filename(&dir, "Same", None), "Same (3).md");
+    }
+
+    #[test]
+    fn test_strip_duplicate_suffix() {
+        assert_eq!(strip_duplicate_suffix("Same (2)"), "Same");
+        assert_eq!(strip_duplicate_suffix("Same"), "Same");
+        assert_eq!(strip_duplicate_suffix("Plan (draft)"), "Plan (draft)");
+    }
+
+    #[test]
+    fn test_notes_digest_respects_budget() {
+        let mut old = sample_card(None);
+        old.content = "# Old\nolder note".to_string();
+        old.updated_at = 1;
+        let mut new = sample_card(None);
+        new.content = format!("# New\n{}", "x".repeat(1000));
+        new.updated_at = 2;
+
+        let digest = build_notes_digest(&[old.clone(), new.clone()], 10_000);
+        assert!(digest.find("## New").unwrap() < digest.find("## Old").unwrap());
+        assert!(!digest.contains("omitted"));
+
+        // Tight budget: the long card is truncated and the older one left out
+        let digest = build_notes_digest(&[old, new], 500);
+        assert!(digest.contains("## New"));
+        assert!(digest.contains('…'));
+        assert!(!digest.contains("## Old"));
+        assert!(digest.contains("(1 more notes omitted)"));
+    }
+
+    #[test]
+    fn test_bulk_delete_reports_per_card() {
+        let dir = TempDir::new("bulk");
+        let card = |id: &str, locked: bool| Card {
+            id: id.to_string(),
+            locked,
+            ..sample_card(None)
+        };
+        let mut cards = vec![card("a", false), card("locked", true), card("stuck", false)];
+        fs::write(dir.join("a.md"), "a").unwrap();
+        fs::write(dir.join("locked.md"), "locked").unwrap();
+        // A directory can't be removed as a file, so deleting this card's file fails
+        fs::create_dir_all(dir.join("stuck.md")).unwrap();
+        let paths: HashMap<String, PathBuf> = ["a", "locked", "stuck"]
+            .into_iter()
+            .map(|id| (id.to_string(), dir.join(format!("{}.md", id))))
+            .collect();
+
+        let ids: Vec<String> = ["a", "missing", "locked", "a", "stuck"].map(String::from).to_vec();
+        let report = bulk_apply_to(&mut cards, &dir, &paths, &ids, &mut delete_card_locked);
+
+        assert_eq!(report.succeeded, ["a"]);
+        assert_eq!(report.not_found, ["missing"]);
+        let failed: Vec<&str> = report.failed.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(failed, ["locked", "stuck"]);
+        assert!(!dir.join("a.md").exists());
+        assert!(dir.join("locked.md").exists());
+
+        // Cards whose file couldn't be deleted are still listed
+        let left: Vec<&str> = cards.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(left, ["locked", "stuck"]);
+    }
+
+    #[test]
+    fn test_save_retry_delay_backs_off() {
+        assert_eq!(save_retry_delay(1), Duration::from_secs(1));
+        assert_eq!(save_retry_delay(3), Duration::from_secs(4));
+        assert_eq!(save_retry_delay(10), SAVE_RETRY_MAX);
+        assert_eq!(save_retry_delay(u32::MAX), SAVE_RETRY_MAX);
+    }
+
+    #[test]
+    fn test_lock_recovers_from_poison() {
+        let mutex = std::sync::Arc::new(Mutex::new(vec![1, 2]));
+        let poisoner = mutex.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poison the lock");
+        })
+        .join();
+        assert!(mutex.is_poisoned());
+
+        assert_eq!(*lock_or_recover(&mutex, "test"), vec![1, 2]);
+        assert!(!mutex.is_poisoned());
+    }
+
+    #[test]
+    fn test_attachment_names() {
+        assert_eq!(sanitize_attachment_filename("shot.png").unwrap(), "shot.png");
+        assert_eq!(sanitize_attachment_filename("my shot.png").unwrap(), "my-shot.png");
+        assert_eq!(sanitize_attachment_filename("../../etc/passwd").unwrap(), "passwd");
+        assert!(sanitize_attachment_filename("..").is_err());
+        assert!(sanitize_attachment_filename(".hidden").is_err());
+
+        let dir = TempDir::new("att");
+        assert_eq!(unique_attachment_name(&dir, "shot.png"), "shot.png");
+        fs::write(dir.join("shot.png"), b"x").unwrap();
+        assert_eq!(unique_attachment_name(&dir, "shot.png"), "shot-1.png");
+    }
+
+    #[test]
+    fn test_attachment_dir_name_rejects_paths() {
+        assert_eq!(attachment_dir_name("test-id").unwrap(), "test-id");
+        for id in ["", ".", "..", "a/b", "a\\b", "/etc", "../cards"] {
+            assert!(attachment_dir_name(id).is_err(), "{:?}", id);
+        }
+
+        // Deleting a card with a bad id must not touch the cards directory
+        let dir = TempDir::new("att-delete");
+        fs::write(dir.join("keep.md"), "x").unwrap();
+        delete_card_attachments(&dir, "..").unwrap();
+        assert!(dir.join("keep.md").exists());
+    }
+
+    #[test]
+    fn test_move_card_attachments() {
+        let dir = TempDir::new("att-move");
+        fs::create_dir_all(dir.join("attachments/a")).unwrap();
+        fs::create_dir_all(dir.join("attachments/m")).unwrap();
+        fs::write(dir.join("attachments/a/shot.png"), b"a").unwrap();
+        fs::write(dir.join("attachments/m/shot.png"), b"m").unwrap();
+
+        let mut content = "![](attachments/a/shot.png)".to_string();
+        move_card_attachments(&dir, "a", "m", &mut content).unwrap();
+        assert_eq!(content, "![](attachments/m/shot-1.png)");
+        assert_eq!(fs::read(dir.join("attachments/m/shot-1.png")).unwrap(), b"a");
+        assert!(!dir.join("attachments/a/shot.png").exists());
+
+        // Cards without attachments are left alone
+        move_card_attachments(&dir, "none", "m", &mut content).unwrap();
+        assert_eq!(content, "![](attachments/m/shot-1.png)");
+    }
+
+    #[test]
+    fn test_render_card_html() {
+        let mut card = sample_card(None);
+        card.content = "# Plans <draft>\n\n- [x] **done**\n".to_string();
+
+        let html = render_card_html(&card);
+        assert!(html.contains("<title>Plans &lt;draft&gt;</title>"));
+        assert!(html.contains("<strong>done</strong>"));
+        assert!(html.contains("<style>"));
+    }
+
+    #[test]
+    fn test_color_validation() {
+        assert!(is_valid_color("#fff"));
+        assert!(is_valid_color("#FFCC00"));
+        assert!(is_valid_color("yellow"));
+        assert!(!is_valid_color("#ggg"));
+        assert!(!is_valid_color("red; drop"));
+    }
+}
@@ -0,0 +1,85 @@
+//! Card Watcher - Reloads cards when their files change on disk
+//!
+//! Watches the cards directory so edits made outside the app (e.g. by Claude
+//! Desktop through the MCP server) show up without a manual reload. Events for files
+//! the app just wrote itself are ignored.
+
+use crate::card_manager;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use once_cell::sync::Lazy;
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// Quiet period to wait for before reloading after a burst of file events
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// The active watcher; replacing it stops watching the previous directory
+static WATCHER: Lazy<Mutex<Option<RecommendedWatcher>>> = Lazy::new(|| Mutex::new(None));
+
+/// Returns true if the event concerns a card file outside the `.trash` directory
+/// that wasn't just written by the app
+fn is_card_event(event: &Event) -> bool {
+    let relevant_kind = matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    );
+
+    relevant_kind
+        && event
+            .paths
+            .iter()
+            .any(|path| is_card_path(path) && !card_manager::is_own_write(path))
+}
+
+fn is_card_path(path: &Path) -> bool {
+    let in_trash = path.components().any(|c| c.as_os_str() == ".trash");
+    !in_trash && path.extension().and_then(|s| s.to_str()) == Some("md")
+}
+
+/// Start watching the current cards directory
+/// Calling this again (e.g. after the cards directory changed) replaces the previous watcher.
+pub fn start(app: &AppHandle) -> Result<(), String> {
+    let cards_dir = card_manager::get_cards_directory()?;
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+
+    let mut watcher = notify::recommended_watcher(tx)
+        .map_err(|e| format!("Failed to create file watcher: {}", e))?;
+    watcher
+        .watch(&cards_dir, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("Failed to watch cards directory: {}", e))?;
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        // Exits once the watcher is dropped and the channel disconnects
+        while let Ok(result) = rx.recv() {
+            match result {
+                Ok(event) if is_card_event(&event) => {}
+                Ok(_) => continue,
+                Err(e) => {
+                    log::warn!("File watcher error: {}", e);
+                    continue;
+                }
+            }
+
+            // Swallow follow-up events until the directory has been quiet for DEBOUNCE
+            while rx.recv_timeout(DEBOUNCE).is_ok() {}
+
+            match card_manager::reload_all_cards() {
+                Ok(cards) => {
+                    app.emit("cards-changed", cards).ok();
+                }
+                Err(e) => log::warn!("Failed to reload cards after file change: {}", e),
+            }
+        }
+        log::debug!("Card watcher thread stopped");
+    });
+
+    let mut active = WATCHER.lock().map_err(|e| e.to_string())?;
+    *active = Some(watcher);
+
+    log::info!("Watching cards directory: {:?}", cards_dir);
+    Ok(())
+}
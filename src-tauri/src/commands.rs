@@ -2,15 +2,27 @@
 //!
 //! These commands are exposed to the frontend via the invoke() function.
 
-use crate::ai_manager::AiManager;
-use crate::card_manager::{self, Card};
-use crate::claude_mcp;
-use crate::keyring_store::{AiProvider, KeyringStore};
-use crate::local_model::{self, ModelStatus};
+use crate::ai_manager::{
+    AiManager, LocalBenchmarkResult, LocalModelTestResult, ProviderConnectivity,
+    ProviderLatencyStats, StreamMode, UsageStats,
+};
+use crate::app_log;
+use crate::card_manager::{
+    self, BulkReport, Card, CardFileDiagnosis, CardStats, ImportReport, ReconcileReport,
+};
+use crate::card_watcher;
+use crate::conversation_log;
+use crate::gpu_detect::{self, GpuBackendInfo};
+use crate::keyring_store::{AiProvider, KeyringStore, DEFAULT_PROFILE};
+use crate::local_inference;
+use crate::local_model::{
+    self, LocalModelList, ModelRequirements, ModelStatus, QuantizationInfo, TempCleanupResult,
+};
+use crate::mcp_integration::{self, McpTarget};
 use crate::settings_manager::SettingsManager;
-use crate::window_state::{WindowState};
+use crate::window_state::{MonitorBounds, WindowState};
 use serde::{Deserialize, Serialize};
-use tauri::State;
+use tauri::{Emitter, Manager, State};
 
 // ============================================================================
 // Types
@@ -23,6 +35,44 @@ pub struct ProviderInfo {
     pub configured: bool,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderCapabilities {
+    pub id: String,
+    pub supports_tools: bool,
+    pub supports_streaming: bool,
+}
+
+/// Everything the Settings screen shows about one provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderOverview {
+    pub id: String,
+    pub name: String,
+    /// Cloud: an API key is stored. Local: the model file is downloaded.
+    pub configured: bool,
+    pub active: bool,
+    /// Cloud: the selected model id. Local: the model file name.
+    pub model: Option<String>,
+    /// Local models only
+    pub model_status: Option<local_model::ModelStatus>,
+    /// Local models only: a download is running
+    pub downloading: bool,
+}
+
+/// Emitted as 'local-model-ready' when `preload_local_model` has the model in memory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalModelReady {
+    pub provider: String,
+    /// GPU layers in use, 0 when running on CPU
+    pub gpu_layers: u32,
+}
+
+/// Emitted as 'local-model-error' when `preload_local_model` couldn't load the model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalModelLoadError {
+    pub provider: String,
+    pub error: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct CommandError {
     pub message: String,
@@ -47,49 +97,163 @@ impl From<&str> for CommandError {
 // ============================================================================
 
 /// Save an API key securely to the OS credential store
+/// Keys are stored under the given profile name (or the default profile)
 #[tauri::command]
-pub async fn save_api_key(provider: String, key: String) -> Result<(), String> {
+pub async fn save_api_key(provider: String, key: String, profile: Option<String>) -> Result<(), String> {
     let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    let profile = profile.unwrap_or_else(|| DEFAULT_PROFILE.to_string());
 
-    KeyringStore::save_api_key(provider, &key).map_err(|e| e.to_string())?;
+    KeyringStore::save_api_key(provider, &profile, &key).map_err(|e| e.to_string())?;
 
     Ok(())
 }
 
 /// Delete an API key from the credential store
+/// Deleting the active profile switches the provider back to the default profile
 #[tauri::command]
-pub async fn delete_api_key(provider: String) -> Result<(), String> {
+pub async fn delete_api_key(
+    provider: String,
+    profile: Option<String>,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
     let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    let profile = profile.unwrap_or_else(|| DEFAULT_PROFILE.to_string());
 
-    KeyringStore::delete_api_key(provider).map_err(|e| e.to_string())?;
+    KeyringStore::delete_api_key(provider, &profile).map_err(|e| e.to_string())?;
+
+    if settings.get_active_profile(provider) == profile {
+        settings
+            .set_active_profile(provider, DEFAULT_PROFILE.to_string())
+            .map_err(|e| e.to_string())?;
+    }
 
     Ok(())
 }
 
+/// List the API key profiles stored for a provider
+#[tauri::command]
+pub async fn list_api_key_profiles(provider: String) -> Result<Vec<String>, String> {
+    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    Ok(KeyringStore::list_profiles(provider))
+}
+
+/// Select which API key profile a provider uses
+#[tauri::command]
+pub async fn set_active_api_key_profile(
+    provider: String,
+    profile: String,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
+    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+
+    if !KeyringStore::list_profiles(provider).contains(&profile) {
+        return Err(format!(
+            "No API key stored for {} under profile '{}'",
+            provider.display_name(),
+            profile
+        ));
+    }
+
+    settings
+        .set_active_profile(provider, profile)
+        .map_err(|e| e.to_string())
+}
+
 /// Get list of all providers with their configuration status
+/// A cloud provider counts as configured when its active profile has a key.
 #[tauri::command]
-pub async fn get_providers() -> Vec<ProviderInfo> {
-    AiProvider::all()
+pub async fn get_providers(
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<Vec<ProviderInfo>, String> {
+    Ok(AiProvider::all()
         .into_iter()
         .map(|p| ProviderInfo {
             id: p.as_str().to_string(),
             name: p.display_name().to_string(),
-            configured: KeyringStore::has_api_key(p),
+            configured: KeyringStore::has_api_key(p, &settings.get_active_profile(p)),
+        })
+        .collect())
+}
+
+/// Get every provider with its key/download state, active flag and selected model
+/// One call instead of combining get_providers, get_active_provider and get_all_settings.
+#[tauri::command]
+pub async fn get_provider_overview(
+    ai_manager: State<'_, AiManager>,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<Vec<ProviderOverview>, String> {
+    let active = ai_manager.get_active_provider().await;
+
+    Ok(AiProvider::all()
+        .into_iter()
+        .map(|p| {
+            let overview = ProviderOverview {
+                id: p.as_str().to_string(),
+                name: p.display_name().to_string(),
+                configured: false,
+                active: active == Some(p),
+                model: None,
+                model_status: None,
+                downloading: false,
+            };
+
+            if p.requires_api_key() {
+                return ProviderOverview {
+                    configured: KeyringStore::has_api_key(p, &settings.get_active_profile(p)),
+                    model: Some(settings.get_provider_model(p)),
+                    ..overview
+                };
+            }
+
+            let model_status = local_model::get_model_status(p, Some(&settings)).ok();
+            ProviderOverview {
+                configured: model_status.as_ref().is_some_and(|s| s.is_downloaded),
+                model: local_model::get_model_path(p, Some(&settings))
+                    .ok()
+                    .and_then(|path| path.file_name().map(|f| f.to_string_lossy().to_string())),
+                downloading: local_model::is_downloading(p, Some(&settings)).unwrap_or(false),
+                model_status,
+                ..overview
+            }
+        })
+        .collect())
+}
+
+/// Get the capability flags of every provider (e.g. to hide tool features)
+#[tauri::command]
+pub async fn get_provider_capabilities() -> Vec<ProviderCapabilities> {
+    AiProvider::all()
+        .into_iter()
+        .map(|p| ProviderCapabilities {
+            id: p.as_str().to_string(),
+            supports_tools: p.supports_tools(),
+            supports_streaming: p.supports_streaming(),
         })
         .collect()
 }
 
 /// Set the active AI provider
+/// Cloud providers need an API key, local models a downloaded model file.
 #[tauri::command]
 pub async fn set_active_provider(
     provider: String,
     ai_manager: State<'_, AiManager>,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
 ) -> Result<(), String> {
     let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
 
-    if !KeyringStore::has_api_key(provider) {
+    if provider.requires_api_key() {
+        let profile = settings.get_active_profile(provider);
+        if !KeyringStore::has_api_key(provider, &profile) {
+            return Err(format!(
+                "No API key configured for {} (profile: {}). Please add your API key in Settings.",
+                provider.display_name(),
+                profile
+            ));
+        }
+    } else if !local_model::is_model_downloaded(provider, Some(&settings)).map_err(|e| e.to_string())? {
         return Err(format!(
-            "No API key configured for {}. Please add your API key in Settings.",
+            "Model not downloaded for {}. Please download it first in Settings.",
             provider.display_name()
         ));
     }
@@ -99,160 +263,965 @@ pub async fn set_active_provider(
     Ok(())
 }
 
-/// Get the currently active provider
+/// Check which providers are usable right now
+/// Cloud providers get a quick authenticated request, local providers a model-file check.
+#[tauri::command]
+pub async fn check_provider_connectivity(
+    ai_manager: State<'_, AiManager>,
+) -> Result<Vec<ProviderConnectivity>, String> {
+    Ok(ai_manager.check_connectivity().await)
+}
+
+/// Get the currently active provider
+#[tauri::command]
+pub async fn get_active_provider(ai_manager: State<'_, AiManager>) -> Result<Option<String>, String> {
+    let provider = ai_manager.get_active_provider().await;
+    Ok(provider.map(|p| p.as_str().to_string()))
+}
+
+// ============================================================================
+// AI Streaming Commands
+// ============================================================================
+
+/// Related notes added to the context when the caller doesn't say how many
+const DEFAULT_RELATED_LIMIT: usize = 3;
+const MAX_RELATED_LIMIT: usize = 10;
+
+/// Options for adding related notes to a prompt's context
+#[derive(Debug, Clone, Deserialize)]
+pub struct RelatedNotesOptions {
+    /// The card being asked about, left out of the related notes
+    #[serde(default)]
+    pub card_id: Option<String>,
+    /// How many notes to add (default 3, at most 10)
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Invoke AI with streaming response
+/// An 'ai-stream-start' event carries the mode, then results are emitted as 'ai-stream-chunk'
+/// events. `mode` defaults to replacing the card content. With `target_window` (a window
+/// label such as "orb") the stream events go only to that window instead of all of them.
+/// With `related` the notes most relevant to the context (excluding the given card)
+/// are added to it.
+#[tauri::command]
+pub async fn invoke_ai_stream(
+    prompt: String,
+    context: String,
+    mode: Option<StreamMode>,
+    target_window: Option<String>,
+    related: Option<RelatedNotesOptions>,
+    app: tauri::AppHandle,
+    ai_manager: State<'_, AiManager>,
+) -> Result<(), String> {
+    let context = match related {
+        Some(related) => {
+            let limit = related.limit.unwrap_or(DEFAULT_RELATED_LIMIT).clamp(1, MAX_RELATED_LIMIT);
+            ai_manager
+                .with_related_notes(&context, related.card_id.as_deref(), limit)
+                .await
+        }
+        None => context,
+    };
+
+    ai_manager
+        .invoke_stream(
+            &app,
+            &prompt,
+            &context,
+            mode.unwrap_or_default(),
+            target_window.as_deref(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Show what the active provider would be sent for a prompt, without sending it
+/// Cloud providers get the request body (messages, system prompt, tools) as pretty
+/// JSON; local models get the formatted prompt text.
+#[tauri::command]
+pub async fn preview_prompt(
+    prompt: String,
+    context: String,
+    mode: Option<StreamMode>,
+    ai_manager: State<'_, AiManager>,
+) -> Result<String, String> {
+    ai_manager
+        .preview_prompt(&prompt, &context, mode.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Continue a card's AI response that was cut off by the token limit
+/// Offered after an 'ai-stream-truncated' event; the continuation streams as
+/// 'ai-stream-chunk' events in append mode.
+#[tauri::command]
+pub async fn continue_generation(
+    card_id: String,
+    target_window: Option<String>,
+    app: tauri::AppHandle,
+    ai_manager: State<'_, AiManager>,
+) -> Result<(), String> {
+    let card = card_manager::get_card_by_id(&card_id)?;
+    if card.content.trim().is_empty() {
+        return Err("Nothing to continue: the card is empty".to_string());
+    }
+
+    ai_manager
+        .continue_generation(&app, &card.content, target_window.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Summarize notes (optionally only those updated since a timestamp) into a new card
+/// The summary streams as 'ai-stream-chunk' events while it is generated.
+#[tauri::command]
+pub async fn summarize_notes(
+    since: Option<i64>,
+    app: tauri::AppHandle,
+    ai_manager: State<'_, AiManager>,
+) -> Result<Card, String> {
+    let cards: Vec<Card> = card_manager::get_all_cards()?
+        .into_iter()
+        .filter(|card| since.is_none_or(|cutoff| card.updated_at >= cutoff))
+        .collect();
+
+    if cards.is_empty() {
+        return Err("No notes to summarize".to_string());
+    }
+
+    let summary = ai_manager
+        .summarize_notes(&app, &cards)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let card = card_manager::create_card(summary.trim().to_string())?;
+    app.emit("refresh-required", ()).ok();
+    Ok(card)
+}
+
+/// Ask the active provider for a new note: creates an empty card and streams into it
+/// Returns the new card's id right away; the response is appended to the card in the
+/// background (and saved) even if the window closes. 'ai-stream-start' carries the
+/// card id, the text follows as 'ai-card-stream-chunk' events and 'refresh-required'
+/// when the response is complete.
+#[tauri::command]
+pub async fn invoke_ai_to_new_card(
+    prompt: String,
+    context: String,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    let card = card_manager::create_card(String::new())?;
+    app.emit("refresh-required", ()).ok();
+
+    let card_id = card.id.clone();
+    tauri::async_runtime::spawn(async move {
+        let ai_manager = app.state::<AiManager>();
+        if let Err(e) = ai_manager.stream_to_card(&app, &card.id, &prompt, &context).await {
+            log::warn!("Failed to generate new card {}: {}", card.id, e);
+        }
+    });
+
+    Ok(card_id)
+}
+
+/// Get accumulated token usage per provider since app start
+#[tauri::command]
+pub async fn get_usage_stats(
+    ai_manager: State<'_, AiManager>,
+) -> Result<std::collections::HashMap<String, UsageStats>, String> {
+    Ok(ai_manager.get_usage_stats().await)
+}
+
+/// Get min/avg/p95 time to first chunk and total response time per provider
+/// Covers successful generations since app start (or the last session reset).
+#[tauri::command]
+pub async fn get_provider_latency_stats(
+    ai_manager: State<'_, AiManager>,
+) -> Result<std::collections::HashMap<String, ProviderLatencyStats>, String> {
+    Ok(ai_manager.get_latency_stats().await)
+}
+
+/// Clear accumulated token usage for one provider (all when None)
+/// Returns the cleared totals. Pending tool confirmations are left alone.
+#[tauri::command]
+pub async fn reset_usage_stats(
+    provider: Option<String>,
+    ai_manager: State<'_, AiManager>,
+) -> Result<std::collections::HashMap<String, UsageStats>, String> {
+    let provider = provider
+        .map(|p| AiProvider::from_str(&p))
+        .transpose()
+        .map_err(|e| e.to_string())?;
+    Ok(ai_manager.reset_usage_stats(provider).await)
+}
+
+/// Get how many requests are waiting for each provider's rate limit
+#[tauri::command]
+pub async fn get_request_queue_status(
+    ai_manager: State<'_, AiManager>,
+) -> Result<std::collections::HashMap<String, usize>, String> {
+    Ok(ai_manager.get_request_queue_status())
+}
+
+/// Clear AI session state (usage and latency stats, pending tool confirmations)
+/// Does not touch cards or API keys; safe to call at any time.
+#[tauri::command]
+pub async fn reset_ai_session(ai_manager: State<'_, AiManager>) -> Result<(), String> {
+    ai_manager.reset_session().await;
+    Ok(())
+}
+
+/// Approve a tool call that is waiting for confirmation
+#[tauri::command]
+pub async fn confirm_tool_call(call_id: String, ai_manager: State<'_, AiManager>) -> Result<(), String> {
+    ai_manager.resolve_tool_call(&call_id, true).await
+}
+
+/// Reject a tool call that is waiting for confirmation
+#[tauri::command]
+pub async fn reject_tool_call(call_id: String, ai_manager: State<'_, AiManager>) -> Result<(), String> {
+    ai_manager.resolve_tool_call(&call_id, false).await
+}
+
+// ============================================================================
+// Card Storage Commands (In-Memory for now, can be extended to SQLite)
+// ============================================================================
+
+/// Emit 'storage-limit-reached' when the cards directory is over its size cap
+fn warn_if_over_storage_limit(app: &tauri::AppHandle) {
+    if let Some(usage) = card_manager::storage_limit_exceeded() {
+        log::warn!(
+            "Cards directory is over its storage limit ({} of {} bytes)",
+            usage.used_bytes,
+            usage.max_bytes
+        );
+        app.emit("storage-limit-reached", usage).ok();
+    }
+}
+
+/// Create a new card
+#[tauri::command]
+pub async fn create_card(content: String, app: tauri::AppHandle) -> Result<Card, String> {
+    let card = card_manager::create_card(content)?;
+    warn_if_over_storage_limit(&app);
+    Ok(card)
+}
+
+/// Get all cards
+#[tauri::command]
+pub async fn get_cards() -> Result<Vec<Card>, String> {
+    card_manager::get_all_cards()
+}
+
+/// Get a single card by id
+#[tauri::command]
+pub async fn get_card(id: String) -> Result<Card, String> {
+    card_manager::get_card_by_id(&id)
+}
+
+/// Ids of cards changed since the frontend last saw them (id -> `content_hash`)
+/// New and removed cards are included, so only those need to be fetched again.
+#[tauri::command]
+pub async fn get_changed_cards(
+    since_hashes: std::collections::HashMap<String, String>,
+) -> Result<Vec<String>, String> {
+    Ok(card_manager::get_changed_cards(&since_hashes))
+}
+
+/// Find groups of near-identical cards by word overlap
+/// `similarity_threshold` is 0-1 (e.g. 0.8); very short cards are ignored.
+#[tauri::command]
+pub async fn find_duplicate_cards(similarity_threshold: f64) -> Result<Vec<Vec<Card>>, String> {
+    card_manager::find_duplicate_cards(similarity_threshold)
+}
+
+/// Get all cards sorted by "created", "updated" or "title" (pinned cards first)
+#[tauri::command]
+pub async fn get_cards_sorted(sort_by: String, descending: bool) -> Result<Vec<Card>, String> {
+    card_manager::get_cards_sorted(&sort_by, descending)
+}
+
+/// Update a card (written to disk after a short idle delay)
+#[tauri::command]
+pub async fn save_card(card: Card, app: tauri::AppHandle) -> Result<(), String> {
+    card_manager::update_card(&card.id, Some(card.content))?;
+    warn_if_over_storage_limit(&app);
+    Ok(())
+}
+
+/// Write all card edits that are still waiting for the autosave delay
+/// Returns how many cards were written.
+#[tauri::command]
+pub async fn flush_pending_saves() -> Result<usize, String> {
+    Ok(card_manager::flush_pending_saves())
+}
+
+/// Store a file (e.g. a pasted screenshot) alongside a card
+/// Returns the relative path to embed in the card's markdown.
+#[tauri::command]
+pub async fn save_attachment(
+    card_id: String,
+    filename: String,
+    bytes: Vec<u8>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    let path = card_manager::save_attachment(&card_id, &filename, &bytes)?;
+    warn_if_over_storage_limit(&app);
+    Ok(path)
+}
+
+/// List the attachment file names of a card
+#[tauri::command]
+pub async fn list_attachments(card_id: String) -> Result<Vec<String>, String> {
+    card_manager::list_attachments(&card_id)
+}
+
+/// Delete an attachment of a card
+#[tauri::command]
+pub async fn delete_attachment(card_id: String, filename: String) -> Result<(), String> {
+    card_manager::delete_attachment(&card_id, &filename)
+}
+
+/// Export a card as a standalone HTML file
+#[tauri::command]
+pub async fn export_card_html(id: String, dest_path: String) -> Result<(), String> {
+    card_manager::export_card_html(&id, std::path::Path::new(&dest_path))
+}
+
+/// Pin or unpin a card (pinned cards are listed first)
+#[tauri::command]
+pub async fn set_card_pinned(id: String, pinned: bool) -> Result<Card, String> {
+    card_manager::set_card_pinned(&id, pinned)
+}
+
+/// Set or clear a card's color (hex like #ffcc00 or a named color)
+#[tauri::command]
+pub async fn set_card_color(id: String, color: Option<String>) -> Result<Card, String> {
+    card_manager::set_card_color(&id, color)
+}
+
+/// Archive a card: it leaves the main list but is kept, unlike deleted cards
+#[tauri::command]
+pub async fn archive_card(id: String) -> Result<Card, String> {
+    card_manager::set_card_archived(&id, true)
+}
+
+/// Move an archived card back to the main list
+#[tauri::command]
+pub async fn unarchive_card(id: String) -> Result<Card, String> {
+    card_manager::set_card_archived(&id, false)
+}
+
+/// Get the archived cards, most recently updated first
+#[tauri::command]
+pub async fn get_archived_cards() -> Result<Vec<Card>, String> {
+    Ok(card_manager::get_archived_cards())
+}
+
+/// Lock or unlock a card; locked cards can't be edited or deleted, including by AI tools
+#[tauri::command]
+pub async fn set_card_locked(id: String, locked: bool) -> Result<Card, String> {
+    card_manager::set_card_locked(&id, locked)
+}
+
+/// Get a card's file as stored on disk, including the YAML front matter
+#[tauri::command]
+pub async fn get_card_raw(id: String) -> Result<String, String> {
+    card_manager::get_card_raw(&id)
+}
+
+/// Replace a card's file with edited raw content
+/// The front matter must parse and keep the card's id; malformed input is rejected.
+#[tauri::command]
+pub async fn save_card_raw(id: String, content: String) -> Result<Card, String> {
+    card_manager::save_card_raw(&id, &content)
+}
+
+/// Duplicate a card, optionally prefixing its title with "Copy of"
+#[tauri::command]
+pub async fn duplicate_card(
+    id: String,
+    prefix_title: Option<bool>,
+    app: tauri::AppHandle,
+) -> Result<Card, String> {
+    let card = card_manager::duplicate_card(&id, prefix_title.unwrap_or(true))?;
+    warn_if_over_storage_limit(&app);
+    Ok(card)
+}
+
+/// Merge cards into a new card (contents joined by `---` in the given order)
+#[tauri::command]
+pub async fn merge_cards(ids: Vec<String>, delete_sources: bool) -> Result<Card, String> {
+    card_manager::merge_cards(&ids, delete_sources)
+}
+
+/// Delete a card
+#[tauri::command]
+pub async fn delete_card(id: String) -> Result<(), String> {
+    card_manager::delete_card(&id)
+}
+
+/// Delete several cards at once
+/// Unknown ids are skipped and listed in `not_found`; locked cards are listed in `failed`.
+#[tauri::command]
+pub async fn bulk_delete_cards(ids: Vec<String>, app: tauri::AppHandle) -> Result<BulkReport, String> {
+    let report = card_manager::bulk_delete_cards(&ids)?;
+    app.emit("refresh-required", ()).ok();
+    Ok(report)
+}
+
+/// Add a tag to several cards (`add` true) or remove it from them
+/// Unknown ids are skipped and listed in `not_found`.
+#[tauri::command]
+pub async fn bulk_set_tag(
+    ids: Vec<String>,
+    tag: String,
+    add: bool,
+    app: tauri::AppHandle,
+) -> Result<BulkReport, String> {
+    let report = card_manager::bulk_set_tag(&ids, &tag, add)?;
+    app.emit("refresh-required", ()).ok();
+    Ok(report)
+}
+
+/// Get aggregate card statistics (computed from memory, cheap to poll)
+#[tauri::command]
+pub async fn get_card_stats() -> Result<CardStats, String> {
+    card_manager::get_card_stats()
+}
+
+/// Estimate the LLM token count of a text (heuristic: ~4 characters per token)
+#[tauri::command]
+pub async fn estimate_tokens(content: String) -> usize {
+    card_manager::estimate_tokens(&content)
+}
+
+/// Reload all cards from file system
+/// Useful when cards are modified externally (e.g., by Claude Desktop MCP)
+#[tauri::command]
+pub async fn reload_cards() -> Result<Vec<Card>, String> {
+    card_manager::reload_all_cards()
+}
+
+/// Repair id problems in the cards directory (missing or duplicate ids) and reload
+/// Returns what was changed; running it again on a clean directory changes nothing.
+#[tauri::command]
+pub async fn reconcile_cards() -> Result<ReconcileReport, String> {
+    card_manager::reconcile_cards()
+}
+
+/// List card files that fail to load (bad encoding, missing or broken front matter)
+#[tauri::command]
+pub async fn diagnose_card_files() -> Result<Vec<CardFileDiagnosis>, String> {
+    card_manager::diagnose_card_files()
+}
+
+/// Repair a card file reported by `diagnose_card_files` and return the loaded card
+#[tauri::command]
+pub async fn repair_card_file(path: String, app: tauri::AppHandle) -> Result<Card, String> {
+    let card = card_manager::repair_card_file(std::path::Path::new(&path))?;
+    app.emit("refresh-required", ()).ok();
+    Ok(card)
+}
+
+/// Import a folder of markdown notes (e.g. an Obsidian vault) as cards
+/// Returns which files were imported and which were skipped.
+#[tauri::command]
+pub async fn import_markdown_folder(path: String, app: tauri::AppHandle) -> Result<ImportReport, String> {
+    let report = card_manager::import_markdown_folder(std::path::Path::new(&path))?;
+    app.emit("refresh-required", ()).ok();
+    Ok(report)
+}
+
+/// Change the directory where cards are stored
+/// An empty path restores the default location. When `migrate` is true,
+/// existing card files are moved into the new directory.
+#[tauri::command]
+pub async fn set_cards_directory(
+    path: String,
+    migrate: bool,
+    app: tauri::AppHandle,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<Vec<Card>, String> {
+    let trimmed = path.trim();
+    let new_dir = if trimmed.is_empty() {
+        None
+    } else {
+        Some(std::path::PathBuf::from(trimmed))
+    };
+
+    let cards = card_manager::change_cards_directory(new_dir, migrate)?;
+    settings
+        .set_cards_directory((!trimmed.is_empty()).then(|| trimmed.to_string()))
+        .map_err(|e| e.to_string())?;
+
+    // Point the file watcher at the new directory
+    if let Err(e) = card_watcher::start(&app) {
+        log::warn!("Failed to restart card file watcher: {}", e);
+    }
+
+    Ok(cards)
+}
+
+/// Choose whether card files carry YAML front matter
+/// When off, files hold only the markdown and metadata goes to `.index.json` in the
+/// cards directory. Existing cards are rewritten in the new layout.
+#[tauri::command]
+pub async fn set_store_frontmatter(
+    enabled: bool,
+    app: tauri::AppHandle,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<usize, String> {
+    let rewritten = card_manager::change_frontmatter_storage(enabled)?;
+    settings
+        .set_store_frontmatter(enabled)
+        .map_err(|e| e.to_string())?;
+    app.emit("refresh-required", ()).ok();
+    Ok(rewritten)
+}
+
+// ============================================================================
+// Window State Commands
+// ============================================================================
+
+/// Get the bounds of all connected monitors, primary monitor first
+fn get_monitor_bounds(app: &tauri::AppHandle) -> Vec<MonitorBounds> {
+    let mut monitors: Vec<MonitorBounds> = app
+        .available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .map(MonitorBounds::from)
+        .collect();
+
+    if let Some(primary) = app.primary_monitor().ok().flatten() {
+        let primary = MonitorBounds::from(&primary);
+        if let Some(index) = monitors.iter().position(|m| *m == primary) {
+            monitors.swap(0, index);
+        }
+    }
+
+    monitors
+}
+
+/// Load window positions from disk
+/// Positions that would be off-screen on the current monitor layout are reset.
+#[tauri::command]
+pub async fn load_window_state(app: tauri::AppHandle) -> Result<WindowState, String> {
+    let mut state = WindowState::load()?;
+    state.clamp_to_visible(&get_monitor_bounds(&app));
+    Ok(state)
+}
+
+/// Save main window position
+#[tauri::command]
+pub async fn save_main_window_position(x: i32, y: i32) -> Result<(), String> {
+    let mut state = WindowState::load().unwrap_or_default();
+    state.set_main_position(x, y);
+    state.save()
+}
+
+/// Save orb window position
+#[tauri::command]
+pub async fn save_orb_window_position(x: i32, y: i32) -> Result<(), String> {
+    let mut state = WindowState::load().unwrap_or_default();
+    state.set_orb_position(x, y);
+    state.save()
+}
+
+/// Keep the orb window above other windows or not, and remember the choice
+#[tauri::command]
+pub async fn set_orb_always_on_top(enabled: bool, app: tauri::AppHandle) -> Result<(), String> {
+    let orb = app
+        .get_webview_window("orb")
+        .ok_or_else(|| "Orb window not found".to_string())?;
+    orb.set_always_on_top(enabled).map_err(|e| e.to_string())?;
+
+    let mut state = WindowState::load().unwrap_or_default();
+    state.orb_always_on_top = enabled;
+    state.save()
+}
+
+/// Save main window size
+#[tauri::command]
+pub async fn save_main_window_size(width: u32, height: u32) -> Result<(), String> {
+    let mut state = WindowState::load().unwrap_or_default();
+    state.set_main_size(width, height);
+    state.save()
+}
+
+/// Save orb window size
+#[tauri::command]
+pub async fn save_orb_window_size(width: u32, height: u32) -> Result<(), String> {
+    let mut state = WindowState::load().unwrap_or_default();
+    state.set_orb_size(width, height);
+    state.save()
+}
+
+// ============================================================================
+// Settings Commands
+// ============================================================================
+
+/// Get all application settings (model configurations, etc.)
+#[tauri::command]
+pub async fn get_all_settings(
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<serde_json::Value, String> {
+    let app_settings = settings.get_all_settings();
+    serde_json::to_value(app_settings).map_err(|e| e.to_string())
+}
+
+/// Set model for a cloud provider
+#[tauri::command]
+pub async fn set_provider_model(
+    provider: String,
+    model: String,
+    is_custom: bool,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
+    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    settings
+        .set_provider_model(provider, model, is_custom)
+        .map_err(|e| e.to_string())
+}
+
+/// Set local model configuration
+#[tauri::command]
+pub async fn set_local_model_config(
+    provider: String,
+    repo: String,
+    filename: String,
+    custom_url: Option<String>,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
+    use crate::settings_manager::LocalModelConfig;
+
+    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    let config = LocalModelConfig {
+        repo,
+        filename,
+        custom_url,
+        // Keep the system prompt, stop sequences, language and raw mode, they're set separately
+        system_prompt: settings.get_system_prompt(provider),
+        stop_sequences: settings.get_stop_sequences(provider),
+        response_language: settings.get_response_language(provider),
+        raw_prompt: settings.get_raw_prompt(provider),
+    };
+    settings
+        .set_local_model_config(provider, config)
+        .map_err(|e| e.to_string())
+}
+
+/// Set a custom system prompt for a provider (empty or null restores the default)
+#[tauri::command]
+pub async fn set_system_prompt(
+    provider: String,
+    prompt: Option<String>,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
+    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    settings
+        .set_system_prompt(provider, prompt)
+        .map_err(|e| e.to_string())
+}
+
+/// Set custom stop sequences for a local model (null restores the defaults)
+#[tauri::command]
+pub async fn set_stop_sequences(
+    provider: String,
+    sequences: Option<Vec<String>>,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
+    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    if provider.requires_api_key() {
+        return Err("Stop sequences can only be set for local models".to_string());
+    }
+    settings
+        .set_stop_sequences(provider, sequences)
+        .map_err(|e| e.to_string())
+}
+
+/// Report which GPU backends are compiled in and appear usable on this machine
+#[tauri::command]
+pub async fn detect_gpu_backends() -> Result<Vec<GpuBackendInfo>, String> {
+    Ok(gpu_detect::detect_gpu_backends())
+}
+
+/// Limit how many requests per minute are sent to a cloud provider (null = unlimited)
+#[tauri::command]
+pub async fn set_requests_per_minute(
+    provider: String,
+    requests_per_minute: Option<u32>,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
+    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    if !provider.requires_api_key() {
+        return Err("Rate limits only apply to cloud providers".to_string());
+    }
+    settings
+        .set_requests_per_minute(provider, requests_per_minute)
+        .map_err(|e| e.to_string())
+}
+
+/// Set the instruction language of a local model: "fi", "en", or null to detect it per note
+#[tauri::command]
+pub async fn set_response_language(
+    provider: String,
+    language: Option<String>,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
+    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    if provider.requires_api_key() {
+        return Err("Response language can only be set for local models".to_string());
+    }
+    let language = language
+        .map(|l| l.trim().to_lowercase())
+        .filter(|l| !l.is_empty());
+    if let Some(l) = language.as_deref() {
+        if l != "fi" && l != "en" {
+            return Err(format!("Unsupported response language: {} (expected fi or en)", l));
+        }
+    }
+    settings
+        .set_response_language(provider, language)
+        .map_err(|e| e.to_string())
+}
+
+/// Send a local model the context and prompt verbatim, without the instruct template
+/// For base models or GGUFs with a different chat template; off by default.
+#[tauri::command]
+pub async fn set_raw_prompt(
+    provider: String,
+    enabled: bool,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
+    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    if provider.requires_api_key() {
+        return Err("Raw prompting can only be set for local models".to_string());
+    }
+    settings
+        .set_raw_prompt(provider, enabled)
+        .map_err(|e| e.to_string())
+}
+
+/// Set GPU acceleration type
 #[tauri::command]
-pub async fn get_active_provider(ai_manager: State<'_, AiManager>) -> Result<Option<String>, String> {
-    let provider = ai_manager.get_active_provider().await;
-    Ok(provider.map(|p| p.as_str().to_string()))
+pub async fn set_gpu_type(
+    gpu_type: String,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
+    use crate::keyring_store::GpuType;
+    let gpu = GpuType::from_str(&gpu_type);
+    settings.set_gpu_type(gpu).map_err(|e| e.to_string())
 }
 
-// ============================================================================
-// AI Streaming Commands
-// ============================================================================
-
-/// Invoke AI with streaming response
-/// Results are emitted as 'ai-stream-chunk' events
+/// Set how many model layers are offloaded to the GPU (-1 = all, null = default)
 #[tauri::command]
-pub async fn invoke_ai_stream(
-    prompt: String,
-    context: String,
-    app: tauri::AppHandle,
-    ai_manager: State<'_, AiManager>,
+pub async fn set_gpu_layers(
+    layers: Option<i32>,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
 ) -> Result<(), String> {
-    ai_manager
-        .invoke_stream(&app, &prompt, &context)
-        .await
-        .map_err(|e| e.to_string())?;
-
-    Ok(())
+    settings.set_gpu_layers(layers).map_err(|e| e.to_string())
 }
 
-// ============================================================================
-// Card Storage Commands (In-Memory for now, can be extended to SQLite)
-// ============================================================================
-
-/// Create a new card
+/// Enable or disable confirmation before AI tool calls modify notes
 #[tauri::command]
-pub async fn create_card(content: String) -> Result<Card, String> {
-    card_manager::create_card(content)
+pub async fn set_require_tool_confirmation(
+    required: bool,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
+    settings
+        .set_require_tool_confirmation(required)
+        .map_err(|e| e.to_string())
 }
 
-/// Get all cards
+/// Set the title given to cards without a heading or text (null restores "Note")
+/// Only affects new files; existing untitled cards keep their filenames.
 #[tauri::command]
-pub async fn get_cards() -> Result<Vec<Card>, String> {
-    card_manager::get_all_cards()
+pub async fn set_default_card_title(
+    title: Option<String>,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
+    let title = title.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+    settings
+        .set_default_card_title(title.clone())
+        .map_err(|e| e.to_string())?;
+    card_manager::set_default_card_title(title);
+    Ok(())
 }
 
-/// Update a card
+/// Cap the size of the cards directory, attachments included (null or 0 = no cap)
+/// Over the cap, writes either fail (`block_writes`) or go through with a
+/// 'storage-limit-reached' event.
 #[tauri::command]
-pub async fn save_card(card: Card) -> Result<(), String> {
-    card_manager::update_card(&card.id, Some(card.content))?;
+pub async fn set_max_cards_storage_mb(
+    max_mb: Option<u64>,
+    block_writes: bool,
+    app: tauri::AppHandle,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
+    let max_mb = max_mb.filter(|mb| *mb > 0);
+    settings
+        .set_max_cards_storage(max_mb, block_writes)
+        .map_err(|e| e.to_string())?;
+    card_manager::set_storage_limit(max_mb, block_writes);
+    warn_if_over_storage_limit(&app);
     Ok(())
 }
 
-/// Delete a card
+/// Set the proxy for cloud API calls and model downloads (empty or null = no proxy)
+/// The URL is validated before it's saved; e.g. "http://proxy.example.com:8080".
 #[tauri::command]
-pub async fn delete_card(id: String) -> Result<(), String> {
-    card_manager::delete_card(&id)
+pub async fn set_http_proxy(
+    url: Option<String>,
+    ai_manager: State<'_, AiManager>,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
+    let url = url.map(|u| u.trim().to_string()).filter(|u| !u.is_empty());
+    settings.set_http_proxy(url).map_err(|e| e.to_string())?;
+    ai_manager.reload_http_client();
+    Ok(())
 }
 
-/// Reload all cards from file system
-/// Useful when cards are modified externally (e.g., by Claude Desktop MCP)
+/// Allow API keys to be stored in an encrypted file when no OS keyring is available
 #[tauri::command]
-pub async fn reload_cards() -> Result<Vec<Card>, String> {
-    card_manager::reload_all_cards()
+pub async fn set_keyring_file_fallback(
+    enabled: bool,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
+    settings
+        .set_keyring_file_fallback(enabled)
+        .map_err(|e| e.to_string())?;
+    KeyringStore::set_file_fallback_enabled(enabled);
+    Ok(())
 }
 
-// ============================================================================
-// Window State Commands
-// ============================================================================
-
-/// Load window positions from disk
+/// Enable or disable logging every prompt and response to conversation_log.jsonl
+/// The log stores note content in plaintext; it's meant for bug reports.
 #[tauri::command]
-pub async fn load_window_state() -> Result<WindowState, String> {
-    WindowState::load()
+pub async fn set_log_conversations(
+    enabled: bool,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
+    settings
+        .set_log_conversations(enabled)
+        .map_err(|e| e.to_string())
 }
 
-/// Save main window position
+/// Get the path of the conversation log (it may not exist yet)
 #[tauri::command]
-pub async fn save_main_window_position(x: i32, y: i32) -> Result<(), String> {
-    let mut state = WindowState::load().unwrap_or_default();
-    state.set_main_position(x, y);
-    state.save()
+pub async fn get_conversation_log_path() -> Result<String, String> {
+    conversation_log::get_log_path()
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| e.to_string())
 }
 
-/// Save orb window position
+/// Delete the conversation log
 #[tauri::command]
-pub async fn save_orb_window_position(x: i32, y: i32) -> Result<(), String> {
-    let mut state = WindowState::load().unwrap_or_default();
-    state.set_orb_position(x, y);
-    state.save()
+pub async fn clear_conversation_log() -> Result<(), String> {
+    conversation_log::clear().map_err(|e| e.to_string())
 }
 
-// ============================================================================
-// Settings Commands
-// ============================================================================
-
-/// Get all application settings (model configurations, etc.)
+/// Change the log level now and for future starts ("error", "warn", "info", "debug", "trace")
+/// Fails when RUST_LOG is set, since it controls logging then.
 #[tauri::command]
-pub async fn get_all_settings(
+pub async fn set_log_level(
+    level: String,
     settings: State<'_, std::sync::Arc<SettingsManager>>,
-) -> Result<serde_json::Value, String> {
-    let app_settings = settings.get_all_settings();
-    serde_json::to_value(app_settings).map_err(|e| e.to_string())
+) -> Result<(), String> {
+    let filter = app_log::parse_level(&level).map_err(|e| e.to_string())?;
+    app_log::set_level(filter).map_err(|e| e.to_string())?;
+    settings
+        .set_log_level(Some(filter.to_string().to_lowercase()))
+        .map_err(|e| e.to_string())
 }
 
-/// Set model for a cloud provider
+/// Get the path of the application log file
 #[tauri::command]
-pub async fn set_provider_model(
-    provider: String,
-    model: String,
-    is_custom: bool,
-    settings: State<'_, std::sync::Arc<SettingsManager>>,
-) -> Result<(), String> {
-    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
-    settings
-        .set_provider_model(provider, model, is_custom)
+pub async fn get_log_file_path() -> Result<String, String> {
+    app_log::get_log_path()
+        .map(|path| path.to_string_lossy().to_string())
         .map_err(|e| e.to_string())
 }
 
-/// Set local model configuration
+/// Export settings to a file (API keys are not included)
 #[tauri::command]
-pub async fn set_local_model_config(
-    provider: String,
-    repo: String,
-    filename: String,
-    custom_url: Option<String>,
+pub async fn export_settings(
+    dest_path: String,
     settings: State<'_, std::sync::Arc<SettingsManager>>,
 ) -> Result<(), String> {
-    use crate::settings_manager::LocalModelConfig;
-
-    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
-    let config = LocalModelConfig {
-        repo,
-        filename,
-        custom_url,
-    };
     settings
-        .set_local_model_config(provider, config)
+        .export_to(std::path::Path::new(&dest_path))
         .map_err(|e| e.to_string())
 }
 
-/// Set GPU acceleration type
+/// Import settings from a file and apply them immediately
 #[tauri::command]
-pub async fn set_gpu_type(
-    gpu_type: String,
+pub async fn import_settings(
+    src_path: String,
+    ai_manager: State<'_, AiManager>,
     settings: State<'_, std::sync::Arc<SettingsManager>>,
-) -> Result<(), String> {
-    use crate::keyring_store::GpuType;
-    let gpu = GpuType::from_str(&gpu_type);
-    settings.set_gpu_type(gpu).map_err(|e| e.to_string())
+) -> Result<serde_json::Value, String> {
+    let imported = settings
+        .import_from(std::path::Path::new(&src_path))
+        .map_err(|e| e.to_string())?;
+    card_manager::set_default_card_title(settings.get_default_card_title());
+    card_manager::set_storage_limit(
+        settings.get_max_cards_storage_mb(),
+        settings.get_block_writes_over_storage_limit(),
+    );
+    KeyringStore::set_file_fallback_enabled(settings.get_keyring_file_fallback());
+    app_log::apply_saved_level(settings.get_log_level().as_deref());
+    ai_manager.reload_http_client();
+    serde_json::to_value(imported).map_err(|e| e.to_string())
 }
 
 /// Get recommended models for each provider
 #[tauri::command]
 pub async fn get_recommended_models() -> Result<serde_json::Value, String> {
-    let models = serde_json::json!({
+    Ok(recommended_models())
+}
+
+/// Model ids for a provider's dropdown
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderModelList {
+    pub models: Vec<String>,
+    /// False when the list is the hardcoded recommendations (no key or the request failed)
+    pub live: bool,
+}
+
+/// Fetch the models a provider currently offers from its API
+/// Falls back to the recommended models when there is no API key or the call fails.
+#[tauri::command]
+pub async fn fetch_provider_models(
+    provider: String,
+    ai_manager: State<'_, AiManager>,
+) -> Result<ProviderModelList, String> {
+    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+
+    match ai_manager.fetch_models(provider).await {
+        Ok(models) if !models.is_empty() => Ok(ProviderModelList { models, live: true }),
+        result => {
+            if let Err(e) = result {
+                log::warn!("Failed to fetch models for {}: {}", provider.as_str(), e);
+            }
+            let models = recommended_models()[provider.as_str()]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|model| model["id"].as_str())
+                .map(|id| id.to_string())
+                .collect();
+            Ok(ProviderModelList { models, live: false })
+        }
+    }
+}
+
+/// Hardcoded model recommendations per provider
+fn recommended_models() -> serde_json::Value {
+    serde_json::json!({
         "openai": [
             { "id": "gpt-5.2-codex", "name": "GPT-5.2 Codex (Recommended for coding)" },
             { "id": "o3", "name": "o3 (Deep reasoning)" },
@@ -272,8 +1241,25 @@ pub async fn get_recommended_models() -> Result<serde_json::Value, String> {
             { "id": "gemini-2.5-pro", "name": "Gemini 2.5 Pro (Large context)" },
             { "id": "gemini-2.5-flash", "name": "Gemini 2.5 Flash (Fast)" },
         ],
-    });
-    Ok(models)
+        "groq": [
+            { "id": "llama-3.3-70b-versatile", "name": "Llama 3.3 70B Versatile (Recommended)" },
+            { "id": "llama-3.1-8b-instant", "name": "Llama 3.1 8B Instant (Fastest)" },
+            { "id": "mixtral-8x7b-32768", "name": "Mixtral 8x7B (32k context)" },
+            { "id": "gemma2-9b-it", "name": "Gemma 2 9B" },
+        ],
+        "grok": [
+            { "id": "grok-2-latest", "name": "Grok 2 (Recommended)" },
+            { "id": "grok-beta", "name": "Grok Beta" },
+        ],
+        "mistral": [
+            { "id": "mistral-large-latest", "name": "Mistral Large (Recommended)" },
+            { "id": "mistral-small-latest", "name": "Mistral Small (Faster)" },
+        ],
+        "cohere": [
+            { "id": "command-r-plus", "name": "Command R+ (Recommended)" },
+            { "id": "command-r", "name": "Command R (Faster)" },
+        ],
+    })
 }
 
 // ============================================================================
@@ -290,6 +1276,38 @@ pub async fn get_local_model_status(
     local_model::get_model_status(provider, Some(&settings)).map_err(|e| e.to_string())
 }
 
+/// List all downloaded model files with size, age and the providers using them
+#[tauri::command]
+pub async fn list_local_models(
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<LocalModelList, String> {
+    local_model::list_local_models(Some(&settings)).map_err(|e| e.to_string())
+}
+
+/// Estimate RAM/VRAM needed for a local model and whether it fits in available memory
+/// Check `warning` before calling download_local_model.
+#[tauri::command]
+pub async fn estimate_model_requirements(
+    provider: String,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<ModelRequirements, String> {
+    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    local_model::estimate_requirements(provider, Some(&settings)).map_err(|e| e.to_string())
+}
+
+/// List the GGUF quantization variants available for a local model
+/// Switch variants with set_local_model_config using the returned filename
+#[tauri::command]
+pub async fn get_available_quantizations(
+    provider: String,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<Vec<QuantizationInfo>, String> {
+    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    local_model::get_available_quantizations(provider, Some(&settings))
+        .await
+        .map_err(|e| e.to_string())
+}
+
 /// Download a local model from HuggingFace
 /// Progress is emitted as 'local-model-download-progress' events
 /// Completion is emitted as 'local-model-download-complete' event
@@ -305,6 +1323,71 @@ pub async fn download_local_model(
         .map_err(|e| e.to_string())
 }
 
+/// Run a short canned prompt through a local model and return the result directly
+/// A quick "does inference work" check for Settings; nothing is streamed to the UI.
+#[tauri::command]
+pub async fn test_local_model(
+    provider: String,
+    app: tauri::AppHandle,
+    ai_manager: State<'_, AiManager>,
+) -> Result<LocalModelTestResult, String> {
+    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    Ok(ai_manager.test_local_model(&app, provider).await)
+}
+
+/// Benchmark a local model's prompt evaluation and generation speed
+/// Uses the current GPU type and layer settings, so running it before and after a
+/// change compares the two configurations.
+#[tauri::command]
+pub async fn benchmark_local_model(
+    provider: String,
+    ai_manager: State<'_, AiManager>,
+) -> Result<LocalBenchmarkResult, String> {
+    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    ai_manager
+        .benchmark_local_model(provider)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Load a local model into memory in the background so the first prompt starts fast
+/// Returns right away and emits 'local-model-ready' once loaded, or 'local-model-error'
+/// if loading failed; does nothing more than emit the event if the model is already
+/// in memory.
+#[tauri::command]
+pub async fn preload_local_model(
+    provider: String,
+    app: tauri::AppHandle,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<(), String> {
+    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    if provider.requires_api_key() {
+        return Err(format!("{} is not a local model", provider.as_str()));
+    }
+
+    let settings = settings.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        match local_inference::preload_model(provider, Some(&settings)) {
+            Ok(gpu_layers) => {
+                app.emit("local-model-ready", LocalModelReady {
+                    provider: provider.as_str().to_string(),
+                    gpu_layers,
+                })
+                .ok();
+            }
+            Err(e) => {
+                log::warn!("Failed to preload {} model: {}", provider.as_str(), e);
+                app.emit("local-model-error", LocalModelLoadError {
+                    provider: provider.as_str().to_string(),
+                    error: e.to_string(),
+                })
+                .ok();
+            }
+        }
+    });
+    Ok(())
+}
+
 /// Delete a downloaded local model
 #[tauri::command]
 pub async fn delete_local_model(
@@ -312,42 +1395,95 @@ pub async fn delete_local_model(
     settings: State<'_, std::sync::Arc<SettingsManager>>,
 ) -> Result<(), String> {
     let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    // Release the memory-mapped file before deleting it
+    local_inference::unload_model();
     local_model::delete_model(provider, Some(&settings))
         .await
         .map_err(|e| e.to_string())
 }
 
+/// Cancel a running or stuck download of a provider's model and delete its temp file
+/// Returns the model status afterwards, for recovering after an interrupted download.
+#[tauri::command]
+pub async fn reset_download_state(
+    provider: String,
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<ModelStatus, String> {
+    let provider = AiProvider::from_str(&provider).map_err(|e| e.to_string())?;
+    local_model::reset_download_state(provider, Some(&settings)).map_err(|e| e.to_string())
+}
+
+/// Delete temp files left behind by failed or cancelled model downloads
+#[tauri::command]
+pub async fn cleanup_model_temp_files() -> Result<TempCleanupResult, String> {
+    local_model::cleanup_temp_files().map_err(|e| e.to_string())
+}
+
 // ============================================================================
 // Application Control Commands
 // ============================================================================
 
 /// Exit the entire application (all windows)
+/// Pending card edits and the window layout are saved first.
 #[tauri::command]
 pub async fn exit_app(app: tauri::AppHandle) -> Result<(), String> {
+    save_before_exit(&app);
     app.exit(0);
     Ok(())
 }
 
+/// Write card edits still waiting for the autosave delay and the current window layout
+/// Used by `exit_app` and when the main window is closed.
+pub fn save_before_exit(app: &tauri::AppHandle) {
+    card_manager::flush_pending_saves();
+
+    let mut state = WindowState::load().unwrap_or_default();
+    state.capture(app);
+    if let Err(e) = state.save() {
+        log::warn!("Failed to save window state on exit: {}", e);
+    }
+}
+
 // ============================================================================
-// Claude Desktop MCP Commands
+// MCP Integration Commands
 // ============================================================================
 
-/// Check Claude Desktop MCP integration status
+/// Check MCP integration status for an AI client (claude_desktop, cursor, windsurf)
+#[tauri::command]
+pub async fn check_mcp(
+    target: String,
+    app: tauri::AppHandle,
+) -> Result<mcp_integration::McpStatus, String> {
+    let target = McpTarget::from_str(&target)?;
+    mcp_integration::check_status(&app, target)
+}
+
+/// Register the HexStickyNote MCP server with an AI client
+#[tauri::command]
+pub async fn setup_mcp(target: String, app: tauri::AppHandle) -> Result<(), String> {
+    let target = McpTarget::from_str(&target)?;
+    mcp_integration::setup(&app, target)
+}
+
+/// Remove the HexStickyNote MCP server from an AI client
 #[tauri::command]
-pub async fn check_claude_mcp(app: tauri::AppHandle) -> Result<claude_mcp::ClaudeMcpStatus, String> {
-    claude_mcp::check_status(&app)
+pub async fn remove_mcp(target: String) -> Result<(), String> {
+    let target = McpTarget::from_str(&target)?;
+    mcp_integration::remove(target)
 }
 
-/// Setup Claude Desktop MCP integration
+/// List the config backups made before HexStickyNote changed an AI client's config
 #[tauri::command]
-pub async fn setup_claude_mcp(app: tauri::AppHandle) -> Result<(), String> {
-    claude_mcp::setup(&app)
+pub async fn list_mcp_backups(target: String) -> Result<Vec<mcp_integration::McpBackup>, String> {
+    let target = McpTarget::from_str(&target)?;
+    mcp_integration::list_backups(target)
 }
 
-/// Remove Claude Desktop MCP integration
+/// Restore an AI client's config from one of its backups (by file name)
 #[tauri::command]
-pub async fn remove_claude_mcp() -> Result<(), String> {
-    claude_mcp::remove()
+pub async fn restore_mcp_backup(target: String, backup: String) -> Result<(), String> {
+    let target = McpTarget::from_str(&target)?;
+    mcp_integration::restore_backup(target, &backup)
 }
 
 /// Open cards directory in file explorer
@@ -383,3 +1519,59 @@ pub async fn open_cards_directory() -> Result<(), String> {
     log::info!("Opened cards directory: {:?}", cards_dir);
     Ok(())
 }
+
+/// Open a single card's markdown file
+/// Windows selects the file in Explorer; macOS and Linux open it with the default handler.
+#[tauri::command]
+pub async fn open_card_file(id: String) -> Result<(), String> {
+    let file_path = card_manager::get_card_path(&id)?;
+
+    #[cfg(target_os = "windows")]
+    {
+        std::process::Command::new("explorer")
+            .arg(format!("/select,{}", file_path.display()))
+            .spawn()
+            .map_err(|e| format!("Failed to open explorer: {}", e))?;
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open")
+            .arg(&file_path)
+            .spawn()
+            .map_err(|e| format!("Failed to open card file: {}", e))?;
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        std::process::Command::new("xdg-open")
+            .arg(&file_path)
+            .spawn()
+            .map_err(|e| format!("Failed to open card file: {}", e))?;
+    }
+
+    log::info!("Opened card file: {:?}", file_path);
+    Ok(())
+}
+
+/// Get the directory where cards are stored
+#[tauri::command]
+pub async fn get_cards_directory_path() -> Result<String, String> {
+    card_manager::get_cards_directory().map(|path| path.to_string_lossy().to_string())
+}
+
+/// Get the directory where local models are stored
+#[tauri::command]
+pub async fn get_models_directory_path() -> Result<String, String> {
+    local_model::get_models_dir()
+        .map(|path| path.to_string_lossy().to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Get the path of the settings file
+#[tauri::command]
+pub async fn get_settings_path(
+    settings: State<'_, std::sync::Arc<SettingsManager>>,
+) -> Result<String, String> {
+    Ok(settings.settings_path().to_string_lossy().to_string())
+}
@@ -0,0 +1,157 @@
+//! Conversation Log - Opt-in prompt/response log for debugging
+//!
+//! When `log_conversations` is enabled, every generation is appended as one JSON
+//! line to `conversation_log.jsonl` in the data directory. This stores prompts,
+//! note content and responses in PLAINTEXT; it's meant to be enabled briefly to
+//! capture a bug report and cleared afterwards.
+
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ConversationLogError {
+    #[error("Failed to access conversation log: {0}")]
+    IoError(String),
+    #[error("Failed to serialize log entry: {0}")]
+    SerializeError(String),
+}
+
+/// One generation as written to the log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationLogEntry {
+    /// RFC 3339 timestamp
+    pub timestamp: String,
+    pub provider: String,
+    pub model: String,
+    pub prompt: String,
+    pub context: String,
+    pub response: String,
+    /// Set when the generation failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Prefixes of API keys that could end up in a note, and the shortest run treated as a key
+const SECRET_PREFIXES: &[&str] = &["sk-", "gsk_", "AIza", "xai-"];
+const MIN_SECRET_LEN: usize = 20;
+
+/// Get the path of the conversation log
+pub fn get_log_path() -> Result<PathBuf, ConversationLogError> {
+    let proj_dirs = ProjectDirs::from("com", "HexStickyNote", "HexStickyNote").ok_or_else(|| {
+        ConversationLogError::IoError("Failed to determine project directories".to_string())
+    })?;
+
+    let data_dir = proj_dirs.data_dir();
+    fs::create_dir_all(data_dir).map_err(|e| ConversationLogError::IoError(e.to_string()))?;
+
+    Ok(data_dir.join("conversation_log.jsonl"))
+}
+
+/// Replace anything that looks like an API key with "[REDACTED]"
+fn redact_secrets(text: &str) -> String {
+    let is_key_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        // Take a whole run of key characters, or a single other character
+        let token_len = match rest.find(|c: char| !is_key_char(c)) {
+            Some(0) => rest.chars().next().map_or(1, char::len_utf8),
+            Some(len) => len,
+            None => rest.len(),
+        };
+        let (token, tail) = rest.split_at(token_len);
+
+        if token.len() >= MIN_SECRET_LEN && SECRET_PREFIXES.iter().any(|p| token.starts_with(p)) {
+            output.push_str("[REDACTED]");
+        } else {
+            output.push_str(token);
+        }
+        rest = tail;
+    }
+
+    output
+}
+
+/// Append an entry to a log file, redacting anything that looks like an API key
+fn append_to(path: &Path, entry: &ConversationLogEntry) -> Result<(), ConversationLogError> {
+    let entry = ConversationLogEntry {
+        prompt: redact_secrets(&entry.prompt),
+        context: redact_secrets(&entry.context),
+        response: redact_secrets(&entry.response),
+        ..entry.clone()
+    };
+    let line = serde_json::to_string(&entry)
+        .map_err(|e| ConversationLogError::SerializeError(e.to_string()))?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| ConversationLogError::IoError(e.to_string()))?;
+    writeln!(file, "{}", line).map_err(|e| ConversationLogError::IoError(e.to_string()))
+}
+
+/// Append an entry to the conversation log
+pub fn append(entry: &ConversationLogEntry) -> Result<(), ConversationLogError> {
+    append_to(&get_log_path()?, entry)
+}
+
+/// Delete the conversation log
+pub fn clear() -> Result<(), ConversationLogError> {
+    let path = get_log_path()?;
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| ConversationLogError::IoError(e.to_string()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_secrets() {
+        assert_eq!(
+            redact_secrets("key: sk-abcdefghijklmnopqrstuvwx end"),
+            "key: [REDACTED] end"
+        );
+        assert_eq!(redact_secrets("AIzaSyA1234567890abcdefghij"), "[REDACTED]");
+        // Short or embedded matches are left alone
+        assert_eq!(redact_secrets("task-list and ask-me"), "task-list and ask-me");
+        assert_eq!(redact_secrets("Hyvää päivää sk-1"), "Hyvää päivää sk-1");
+    }
+
+    #[test]
+    fn test_append_writes_json_lines() {
+        let path = std::env::temp_dir().join(format!(
+            "hexstickynote-log-{}.jsonl",
+            uuid::Uuid::new_v4()
+        ));
+        let entry = ConversationLogEntry {
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            provider: "openai".to_string(),
+            model: "gpt-4o".to_string(),
+            prompt: "Summarize".to_string(),
+            context: "note with sk-abcdefghijklmnopqrstuvwx".to_string(),
+            response: "Summary".to_string(),
+            error: None,
+        };
+
+        append_to(&path, &entry).unwrap();
+        append_to(&path, &entry).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let parsed: ConversationLogEntry = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(parsed.context, "note with [REDACTED]");
+        assert!(!lines[0].contains("\"error\""));
+
+        fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,178 @@
+//! GPU Detection - Reports which GPU backends can be used for local inference
+//!
+//! A backend has to be compiled in (cargo feature) and its runtime library has to
+//! be installed. Library presence is a heuristic: it doesn't prove a compatible
+//! device exists, and a library outside the usual locations is reported as untested.
+
+use crate::keyring_store::GpuType;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpuBackendInfo {
+    pub gpu_type: GpuType,
+    /// Compiled in and its runtime library was found
+    pub available: bool,
+    /// Compiled in, but detection couldn't confirm the runtime either way
+    pub untested: bool,
+    pub note: String,
+}
+
+/// Whether a backend was compiled into this build
+fn is_compiled_in(gpu_type: GpuType) -> bool {
+    match gpu_type {
+        GpuType::Cpu => true,
+        GpuType::Cuda => cfg!(feature = "cuda"),
+        GpuType::Vulkan => cfg!(feature = "vulkan"),
+        GpuType::Rocm => cfg!(feature = "rocm"),
+    }
+}
+
+/// Runtime library file names for a backend on this platform
+fn runtime_libraries(gpu_type: GpuType) -> &'static [&'static str] {
+    if cfg!(target_os = "windows") {
+        match gpu_type {
+            GpuType::Cuda => &["nvcuda.dll"],
+            GpuType::Vulkan => &["vulkan-1.dll"],
+            GpuType::Rocm => &["amdhip64.dll", "amdhip64_6.dll"],
+            GpuType::Cpu => &[],
+        }
+    } else if cfg!(target_os = "macos") {
+        match gpu_type {
+            GpuType::Vulkan => &["libvulkan.1.dylib", "libMoltenVK.dylib"],
+            _ => &[],
+        }
+    } else {
+        match gpu_type {
+            GpuType::Cuda => &["libcuda.so.1", "libcuda.so"],
+            GpuType::Vulkan => &["libvulkan.so.1", "libvulkan.so"],
+            GpuType::Rocm => &["libamdhip64.so", "libamdhip64.so.6", "libamdhip64.so.5"],
+            GpuType::Cpu => &[],
+        }
+    }
+}
+
+/// Directories to look for runtime libraries in: loader path variables first,
+/// then the usual system and vendor install locations
+fn library_search_dirs() -> Vec<PathBuf> {
+    let path_var = if cfg!(target_os = "windows") {
+        "PATH"
+    } else if cfg!(target_os = "macos") {
+        "DYLD_LIBRARY_PATH"
+    } else {
+        "LD_LIBRARY_PATH"
+    };
+
+    let mut dirs: Vec<PathBuf> = std::env::var_os(path_var)
+        .map(|paths| std::env::split_paths(&paths).collect())
+        .unwrap_or_default();
+
+    if cfg!(target_os = "windows") {
+        let system_root = std::env::var("SystemRoot").unwrap_or_else(|_| "C:\\Windows".to_string());
+        dirs.push(Path::new(&system_root).join("System32"));
+    } else if cfg!(target_os = "macos") {
+        dirs.extend(["/usr/local/lib", "/opt/homebrew/lib"].map(PathBuf::from));
+    } else {
+        dirs.extend(
+            [
+                "/usr/lib",
+                "/usr/lib64",
+                "/usr/lib/x86_64-linux-gnu",
+                "/usr/lib/aarch64-linux-gnu",
+                "/usr/local/lib",
+                "/usr/local/cuda/lib64",
+                "/opt/rocm/lib",
+            ]
+            .map(PathBuf::from),
+        );
+    }
+
+    for var in ["CUDA_PATH", "ROCM_PATH", "HIP_PATH", "VULKAN_SDK"] {
+        if let Some(root) = std::env::var_os(var) {
+            let root = PathBuf::from(root);
+            dirs.push(root.join("bin"));
+            dirs.push(root.join("lib"));
+            dirs.push(root.join("lib64"));
+        }
+    }
+
+    dirs
+}
+
+/// Find the first of `names` present in any of `dirs`
+fn find_library(names: &[&str], dirs: &[PathBuf]) -> Option<PathBuf> {
+    dirs.iter()
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// Check a single backend
+fn detect_backend(gpu_type: GpuType, dirs: &[PathBuf]) -> GpuBackendInfo {
+    let info = |available: bool, untested: bool, note: String| GpuBackendInfo {
+        gpu_type,
+        available,
+        untested,
+        note,
+    };
+
+    if gpu_type == GpuType::Cpu {
+        return info(true, false, "Always available".to_string());
+    }
+
+    if !is_compiled_in(gpu_type) {
+        return info(
+            false,
+            false,
+            format!("Not compiled into this build (enable the `{}` feature)", gpu_type.as_str()),
+        );
+    }
+
+    let libraries = runtime_libraries(gpu_type);
+    if libraries.is_empty() {
+        return info(false, false, "Not supported on this platform".to_string());
+    }
+
+    match find_library(libraries, dirs) {
+        Some(path) => info(true, false, format!("Runtime found at {}", path.display())),
+        None => info(
+            false,
+            true,
+            format!("Runtime library ({}) not found in the usual locations", libraries.join(", ")),
+        ),
+    }
+}
+
+/// Report every GPU backend and whether it appears usable on this machine
+pub fn detect_gpu_backends() -> Vec<GpuBackendInfo> {
+    let dirs = library_search_dirs();
+    [GpuType::Cpu, GpuType::Cuda, GpuType::Vulkan, GpuType::Rocm]
+        .into_iter()
+        .map(|gpu_type| detect_backend(gpu_type, &dirs))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_cpu_always_available() {
+        let cpu = detect_backend(GpuType::Cpu, &[]);
+        assert!(cpu.available);
+        assert!(!cpu.untested);
+    }
+
+    #[test]
+    fn test_find_library() {
+        let dir = TempDir::new("gpu");
+        std::fs::write(dir.join("libvulkan.so.1"), b"").unwrap();
+
+        let dirs = vec![PathBuf::from("/nonexistent"), dir.to_path_buf()];
+        assert_eq!(
+            find_library(&["libvulkan.so.1"], &dirs),
+            Some(dir.join("libvulkan.so.1"))
+        );
+        assert_eq!(find_library(&["libcuda.so.1"], &dirs), None);
+    }
+}
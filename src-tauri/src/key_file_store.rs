@@ -0,0 +1,239 @@
+//! Encrypted Key File - Fallback API key storage when no OS keyring is available
+//!
+//! Only used when the user opts in and the OS credential store can't be reached
+//! (e.g. headless Linux without a Secret Service). All secrets live in a single
+//! AES-256-GCM encrypted JSON file in the config directory. The encryption key is
+//! derived with PBKDF2 from the `HEXSTICKYNOTE_KEY_PASSPHRASE` environment variable,
+//! or from the machine id when no passphrase is set.
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use directories::ProjectDirs;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use thiserror::Error;
+
+/// Environment variable holding a user-provided passphrase
+const PASSPHRASE_ENV: &str = "HEXSTICKYNOTE_KEY_PASSPHRASE";
+
+const PBKDF2_ROUNDS: u32 = 100_000;
+const SALT_LEN: usize = 16;
+
+#[derive(Debug, Error)]
+pub enum KeyFileError {
+    #[error("Failed to access key file: {0}")]
+    IoError(String),
+    #[error("No passphrase or machine id available to protect the key file")]
+    NoSecret,
+    #[error("Failed to decrypt key file (wrong passphrase or machine?)")]
+    DecryptError,
+    #[error("Key file is corrupt: {0}")]
+    CorruptFile(String),
+}
+
+/// On-disk format; binary fields are hex encoded
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+// Serializes read-modify-write cycles on the key file
+static FILE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Get the path to the encrypted key file
+fn get_key_file_path() -> Result<PathBuf, KeyFileError> {
+    let proj_dirs = ProjectDirs::from("com", "HexStickyNote", "HexStickyNote").ok_or_else(|| {
+        KeyFileError::IoError("Failed to determine project directories".to_string())
+    })?;
+
+    let config_dir = proj_dirs.config_dir();
+    fs::create_dir_all(config_dir).map_err(|e| KeyFileError::IoError(e.to_string()))?;
+
+    Ok(config_dir.join("keys.enc"))
+}
+
+/// Read the machine id (systemd / D-Bus)
+fn get_machine_id() -> Option<String> {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .map(|id| id.trim().to_string())
+        .find(|id| !id.is_empty())
+}
+
+/// The secret the encryption key is derived from: passphrase first, machine id otherwise
+fn get_secret() -> Result<String, KeyFileError> {
+    match std::env::var(PASSPHRASE_ENV) {
+        Ok(passphrase) if !passphrase.is_empty() => Ok(passphrase),
+        _ => get_machine_id().ok_or(KeyFileError::NoSecret),
+    }
+}
+
+fn derive_key(secret: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(secret.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>, KeyFileError> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return Err(KeyFileError::CorruptFile("invalid hex".to_string()));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|e| KeyFileError::CorruptFile(e.to_string()))
+        })
+        .collect()
+}
+
+fn encrypt(entries: &HashMap<String, String>, secret: &str) -> Result<KeyFile, KeyFileError> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+
+    let key = derive_key(secret, &salt);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    let plaintext =
+        serde_json::to_vec(entries).map_err(|e| KeyFileError::CorruptFile(e.to_string()))?;
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_slice())
+        .map_err(|_| KeyFileError::IoError("Encryption failed".to_string()))?;
+
+    Ok(KeyFile {
+        salt: to_hex(&salt),
+        nonce: to_hex(&nonce),
+        ciphertext: to_hex(&ciphertext),
+    })
+}
+
+fn decrypt(file: &KeyFile, secret: &str) -> Result<HashMap<String, String>, KeyFileError> {
+    let salt = from_hex(&file.salt)?;
+    let nonce = from_hex(&file.nonce)?;
+    let ciphertext = from_hex(&file.ciphertext)?;
+
+    if nonce.len() != 12 {
+        return Err(KeyFileError::CorruptFile("invalid nonce length".to_string()));
+    }
+
+    let key = derive_key(secret, &salt);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| KeyFileError::DecryptError)?;
+
+    serde_json::from_slice(&plaintext).map_err(|e| KeyFileError::CorruptFile(e.to_string()))
+}
+
+/// Load and decrypt all entries (empty if the file doesn't exist yet)
+fn load_entries() -> Result<HashMap<String, String>, KeyFileError> {
+    let path = get_key_file_path()?;
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let contents = fs::read_to_string(&path).map_err(|e| KeyFileError::IoError(e.to_string()))?;
+    let file: KeyFile =
+        serde_json::from_str(&contents).map_err(|e| KeyFileError::CorruptFile(e.to_string()))?;
+
+    decrypt(&file, &get_secret()?)
+}
+
+/// Encrypt and write all entries
+fn save_entries(entries: &HashMap<String, String>) -> Result<(), KeyFileError> {
+    let path = get_key_file_path()?;
+    let file = encrypt(entries, &get_secret()?)?;
+    let json =
+        serde_json::to_string_pretty(&file).map_err(|e| KeyFileError::IoError(e.to_string()))?;
+
+    // Written to a temp file that is readable by the owner only from the start,
+    // then renamed over the old file so a crash never leaves a truncated key file
+    let temp_path = path.with_extension("enc.tmp");
+    let write_temp = || -> std::io::Result<()> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&temp_path)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()
+    };
+
+    if let Err(e) = write_temp().and_then(|_| fs::rename(&temp_path, &path)) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(KeyFileError::IoError(e.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Get a secret by entry name
+pub fn get(name: &str) -> Result<Option<String>, KeyFileError> {
+    let _lock = FILE_LOCK.lock().unwrap();
+    Ok(load_entries()?.remove(name))
+}
+
+/// Store a secret under an entry name
+pub fn set(name: &str, value: &str) -> Result<(), KeyFileError> {
+    let _lock = FILE_LOCK.lock().unwrap();
+    let mut entries = load_entries()?;
+    entries.insert(name.to_string(), value.to_string());
+    save_entries(&entries)
+}
+
+/// Remove a secret, returning whether it existed
+pub fn delete(name: &str) -> Result<bool, KeyFileError> {
+    let _lock = FILE_LOCK.lock().unwrap();
+    let mut entries = load_entries()?;
+    let existed = entries.remove(name).is_some();
+    if existed {
+        save_entries(&entries)?;
+    }
+    Ok(existed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt_roundtrip() {
+        let mut entries = HashMap::new();
+        entries.insert("api_key_openai".to_string(), "sk-test".to_string());
+
+        let file = encrypt(&entries, "passphrase").unwrap();
+        assert!(!file.ciphertext.contains(&to_hex(b"sk-test")));
+        assert_eq!(decrypt(&file, "passphrase").unwrap(), entries);
+        assert!(matches!(
+            decrypt(&file, "wrong"),
+            Err(KeyFileError::DecryptError)
+        ));
+    }
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let bytes = [0u8, 15, 16, 255];
+        assert_eq!(to_hex(&bytes), "000f10ff");
+        assert_eq!(from_hex("000f10ff").unwrap(), bytes);
+        assert!(from_hex("abc").is_err());
+    }
+}
@@ -1,13 +1,24 @@
-//! Secure API key storage using Windows Credential Locker
+//! Secure API key storage using the OS credential store
 //!
 //! This module provides secure storage for API keys using the OS-level
-//! credential manager. Keys are NEVER stored in plaintext files.
+//! credential manager (Windows Credential Locker, macOS Keychain or the Secret
+//! Service on Linux). Keys are NEVER stored in plaintext files.
+//! If the user opts in, an encrypted key file is used when no OS credential
+//! store is available (see `key_file_store`).
 
+use crate::key_file_store::{self, KeyFileError};
 use keyring::Entry;
+use std::sync::atomic::{AtomicBool, Ordering};
 use thiserror::Error;
 
 const SERVICE_NAME: &str = "HexStickyNote";
 
+/// Profile name used for the original single key per provider
+pub const DEFAULT_PROFILE: &str = "default";
+
+// Whether the encrypted key file may be used when the OS keyring is unavailable
+static FILE_FALLBACK_ENABLED: AtomicBool = AtomicBool::new(false);
+
 #[derive(Debug, Error)]
 pub enum KeyringError {
     #[error("Failed to access credential store: {0}")]
@@ -16,6 +27,10 @@ pub enum KeyringError {
     KeyNotFound(String),
     #[error("Invalid provider: {0}")]
     InvalidProvider(String),
+    #[error("Invalid profile name: {0}")]
+    InvalidProfile(String),
+    #[error("Encrypted key file error: {0}")]
+    KeyFileError(#[from] KeyFileError),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -24,6 +39,10 @@ pub enum AiProvider {
     OpenAI,
     Anthropic,
     Google,
+    Groq,
+    Grok,
+    Mistral,
+    Cohere,
     Poro2_8B,
     Llama3_8B,
 }
@@ -63,6 +82,10 @@ impl AiProvider {
             AiProvider::OpenAI => "openai",
             AiProvider::Anthropic => "anthropic",
             AiProvider::Google => "google",
+            AiProvider::Groq => "groq",
+            AiProvider::Grok => "grok",
+            AiProvider::Mistral => "mistral",
+            AiProvider::Cohere => "cohere",
             AiProvider::Poro2_8B => "poro2_8b",
             AiProvider::Llama3_8B => "llama3_8b",
         }
@@ -73,6 +96,10 @@ impl AiProvider {
             AiProvider::OpenAI => "OpenAI",
             AiProvider::Anthropic => "Anthropic",
             AiProvider::Google => "Google",
+            AiProvider::Groq => "Groq",
+            AiProvider::Grok => "xAI Grok",
+            AiProvider::Mistral => "Mistral",
+            AiProvider::Cohere => "Cohere",
             AiProvider::Poro2_8B => "Poro 2 8B Instruct",
             AiProvider::Llama3_8B => "Llama 3.1 8B Instruct",
         }
@@ -83,6 +110,10 @@ impl AiProvider {
             "openai" => Ok(AiProvider::OpenAI),
             "anthropic" => Ok(AiProvider::Anthropic),
             "google" => Ok(AiProvider::Google),
+            "groq" => Ok(AiProvider::Groq),
+            "grok" | "xai" => Ok(AiProvider::Grok),
+            "mistral" => Ok(AiProvider::Mistral),
+            "cohere" => Ok(AiProvider::Cohere),
             "poro2_8b" => Ok(AiProvider::Poro2_8B),
             "llama3_8b" => Ok(AiProvider::Llama3_8B),
             _ => Err(KeyringError::InvalidProvider(s.to_string())),
@@ -94,6 +125,10 @@ impl AiProvider {
             AiProvider::OpenAI,
             AiProvider::Anthropic,
             AiProvider::Google,
+            AiProvider::Groq,
+            AiProvider::Grok,
+            AiProvider::Mistral,
+            AiProvider::Cohere,
             AiProvider::Poro2_8B,
             AiProvider::Llama3_8B,
         ]
@@ -102,68 +137,137 @@ impl AiProvider {
     /// Returns true if this provider requires an API key
     pub fn requires_api_key(&self) -> bool {
         match self {
-            AiProvider::OpenAI | AiProvider::Anthropic | AiProvider::Google => true,
+            AiProvider::OpenAI
+            | AiProvider::Anthropic
+            | AiProvider::Google
+            | AiProvider::Groq
+            | AiProvider::Grok
+            | AiProvider::Mistral
+            | AiProvider::Cohere => true,
             AiProvider::Poro2_8B | AiProvider::Llama3_8B => false,
         }
     }
+
+    /// Returns true if the note tools (create/update/delete note) are offered to this provider
+    pub fn supports_tools(&self) -> bool {
+        match self {
+            AiProvider::OpenAI
+            | AiProvider::Google
+            | AiProvider::Groq
+            | AiProvider::Grok
+            | AiProvider::Mistral => true,
+            AiProvider::Anthropic
+            | AiProvider::Cohere
+            | AiProvider::Poro2_8B
+            | AiProvider::Llama3_8B => false,
+        }
+    }
+
+    /// Returns true if responses arrive incrementally as 'ai-stream-chunk' events
+    pub fn supports_streaming(&self) -> bool {
+        match self {
+            AiProvider::OpenAI
+            | AiProvider::Anthropic
+            | AiProvider::Google
+            | AiProvider::Groq
+            | AiProvider::Grok
+            | AiProvider::Mistral
+            | AiProvider::Cohere
+            | AiProvider::Poro2_8B
+            | AiProvider::Llama3_8B => true,
+        }
+    }
 }
 
 /// Keyring-based secure credential store
+///
+/// Each provider can hold several keys under named profiles. The default profile
+/// uses the original `api_key_{provider}` entry so existing keys keep working;
+/// other profiles are stored as `api_key_{provider}_{profile}`. Since the OS
+/// credential store cannot be enumerated, profile names are tracked in a separate
+/// `profiles_{provider}` entry.
 pub struct KeyringStore;
 
 impl KeyringStore {
     /// Save an API key securely to the OS credential store
-    pub fn save_api_key(provider: AiProvider, api_key: &str) -> Result<(), KeyringError> {
-        let entry = Self::get_entry(provider)?;
+    pub fn save_api_key(
+        provider: AiProvider,
+        profile: &str,
+        api_key: &str,
+    ) -> Result<(), KeyringError> {
+        Self::validate_profile(profile)?;
+        Self::write_secret(&Self::entry_username(provider, profile), api_key)?;
 
-        entry
-            .set_password(api_key)
-            .map_err(|e| KeyringError::AccessError(e.to_string()))?;
+        if profile != DEFAULT_PROFILE {
+            let mut profiles = Self::load_profile_index(provider);
+            if !profiles.iter().any(|p| p == profile) {
+                profiles.push(profile.to_string());
+                Self::save_profile_index(provider, &profiles)?;
+            }
+        }
 
-        log::info!("API key saved securely for provider: {}", provider.as_str());
+        log::info!(
+            "API key saved securely for provider: {} (profile: {})",
+            provider.as_str(),
+            profile
+        );
         Ok(())
     }
 
     /// Retrieve an API key from the OS credential store
-    pub fn get_api_key(provider: AiProvider) -> Result<String, KeyringError> {
-        let entry = Self::get_entry(provider)?;
-
-        entry
-            .get_password()
-            .map_err(|e| match e {
-                keyring::Error::NoEntry => KeyringError::KeyNotFound(provider.as_str().to_string()),
-                _ => KeyringError::AccessError(e.to_string()),
-            })
+    pub fn get_api_key(provider: AiProvider, profile: &str) -> Result<String, KeyringError> {
+        Self::read_secret(&Self::entry_username(provider, profile))?
+            .ok_or_else(|| KeyringError::KeyNotFound(provider.as_str().to_string()))
     }
 
     /// Delete an API key from the OS credential store
-    pub fn delete_api_key(provider: AiProvider) -> Result<(), KeyringError> {
-        let entry = Self::get_entry(provider)?;
+    pub fn delete_api_key(provider: AiProvider, profile: &str) -> Result<(), KeyringError> {
+        Self::delete_secret(&Self::entry_username(provider, profile))?;
 
-        entry
-            .delete_credential()
-            .map_err(|e| KeyringError::AccessError(e.to_string()))?;
+        if profile != DEFAULT_PROFILE {
+            let mut profiles = Self::load_profile_index(provider);
+            profiles.retain(|p| p != profile);
+            Self::save_profile_index(provider, &profiles)?;
+        }
 
-        log::info!("API key deleted for provider: {}", provider.as_str());
+        log::info!(
+            "API key deleted for provider: {} (profile: {})",
+            provider.as_str(),
+            profile
+        );
         Ok(())
     }
 
-    /// Check if an API key exists for a provider (or if local model is available)
-    pub fn has_api_key(provider: AiProvider) -> bool {
+    /// List the profiles that have a key stored for a provider
+    pub fn list_profiles(provider: AiProvider) -> Vec<String> {
+        let mut profiles = Vec::new();
+
+        if Self::get_api_key(provider, DEFAULT_PROFILE).is_ok() {
+            profiles.push(DEFAULT_PROFILE.to_string());
+        }
+        profiles.extend(Self::load_profile_index(provider));
+
+        profiles
+    }
+
+    /// Check if an API key exists for a provider's profile (or if local model is available)
+    /// Pass the active profile: requests use only that profile's key.
+    pub fn has_api_key(provider: AiProvider, profile: &str) -> bool {
         // Local providers don't need API keys, check model availability instead
         if !provider.requires_api_key() {
             return true; // We'll check model files separately in local_model module
         }
-        Self::get_api_key(provider).is_ok()
+        Self::get_api_key(provider, profile).is_ok()
     }
 
     /// Get list of providers with configured API keys (or available local models)
-    pub fn get_configured_providers() -> Vec<AiProvider> {
+    /// `active_profile` gives the profile whose key each provider uses.
+    pub fn get_configured_providers(active_profile: impl Fn(AiProvider) -> String) -> Vec<AiProvider> {
         AiProvider::all()
             .into_iter()
             .filter(|p| {
                 if p.requires_api_key() {
-                    Self::has_api_key(*p)
+                    Self::has_api_key(*p, &active_profile(*p))
                 } else {
                     // Local models are always "configured" (UI will handle download state)
                     true
@@ -172,11 +276,95 @@ impl KeyringStore {
             .collect()
     }
 
-    fn get_entry(provider: AiProvider) -> Result<Entry, KeyringError> {
-        let username = format!("api_key_{}", provider.as_str());
+    /// Profile names may only contain letters, digits, '-' and '_'
+    fn validate_profile(profile: &str) -> Result<(), KeyringError> {
+        let valid = !profile.is_empty()
+            && profile
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+        if valid {
+            Ok(())
+        } else {
+            Err(KeyringError::InvalidProfile(profile.to_string()))
+        }
+    }
+
+    /// Load the names of non-default profiles for a provider
+    fn load_profile_index(provider: AiProvider) -> Vec<String> {
+        let username = format!("profiles_{}", provider.as_str());
+        Self::read_secret(&username)
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save the names of non-default profiles for a provider
+    fn save_profile_index(provider: AiProvider, profiles: &[String]) -> Result<(), KeyringError> {
+        let username = format!("profiles_{}", provider.as_str());
+        let json = serde_json::to_string(profiles)
+            .map_err(|e| KeyringError::AccessError(e.to_string()))?;
+
+        Self::write_secret(&username, &json)
+    }
+
+    /// Keyring username for a provider's profile
+    fn entry_username(provider: AiProvider, profile: &str) -> String {
+        if profile == DEFAULT_PROFILE {
+            format!("api_key_{}", provider.as_str())
+        } else {
+            format!("api_key_{}_{}", provider.as_str(), profile)
+        }
+    }
+
+    /// Enable or disable the encrypted key file fallback (opt-in setting)
+    pub fn set_file_fallback_enabled(enabled: bool) {
+        FILE_FALLBACK_ENABLED.store(enabled, Ordering::Relaxed);
+    }
 
-        Entry::new(SERVICE_NAME, &username)
-            .map_err(|e| KeyringError::AccessError(e.to_string()))
+    /// Whether an error means there is no usable OS credential store at all
+    /// and the encrypted key file should be used instead
+    fn should_fall_back(error: &keyring::Error) -> bool {
+        FILE_FALLBACK_ENABLED.load(Ordering::Relaxed)
+            && matches!(
+                error,
+                keyring::Error::NoStorageAccess(_) | keyring::Error::PlatformFailure(_)
+            )
+    }
+
+    /// Read a secret from the OS credential store (None if it doesn't exist)
+    fn read_secret(username: &str) -> Result<Option<String>, KeyringError> {
+        match Entry::new(SERVICE_NAME, username).and_then(|entry| entry.get_password()) {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) if Self::should_fall_back(&e) => Ok(key_file_store::get(username)?),
+            Err(e) => Err(KeyringError::AccessError(e.to_string())),
+        }
+    }
+
+    /// Write a secret to the OS credential store
+    fn write_secret(username: &str, secret: &str) -> Result<(), KeyringError> {
+        match Entry::new(SERVICE_NAME, username).and_then(|entry| entry.set_password(secret)) {
+            Ok(()) => Ok(()),
+            Err(e) if Self::should_fall_back(&e) => {
+                log::warn!("OS credential store unavailable ({}), using encrypted key file", e);
+                Ok(key_file_store::set(username, secret)?)
+            }
+            Err(e) => Err(KeyringError::AccessError(e.to_string())),
+        }
+    }
+
+    /// Delete a secret from the OS credential store
+    fn delete_secret(username: &str) -> Result<(), KeyringError> {
+        match Entry::new(SERVICE_NAME, username).and_then(|entry| entry.delete_credential()) {
+            Ok(()) => Ok(()),
+            Err(e) if Self::should_fall_back(&e) => {
+                key_file_store::delete(username)?;
+                Ok(())
+            }
+            Err(e) => Err(KeyringError::AccessError(e.to_string())),
+        }
     }
 }
 
@@ -192,4 +380,16 @@ mod tests {
             assert_eq!(provider, parsed);
         }
     }
+
+    #[test]
+    fn test_default_profile_uses_legacy_username() {
+        assert_eq!(
+            KeyringStore::entry_username(AiProvider::OpenAI, DEFAULT_PROFILE),
+            "api_key_openai"
+        );
+        assert_eq!(
+            KeyringStore::entry_username(AiProvider::OpenAI, "work"),
+            "api_key_openai_work"
+        );
+    }
 }
@@ -4,16 +4,25 @@
 //! including secure API key storage and AI provider integration.
 
 pub mod ai_manager;
+pub mod ai_providers;
 pub mod ai_tools;
+pub mod app_log;
 pub mod card_manager;
-pub mod claude_mcp;
+pub mod card_watcher;
 pub mod commands;
+pub mod conversation_log;
+pub mod gpu_detect;
+pub mod key_file_store;
 pub mod keyring_store;
 pub mod local_inference;
 pub mod local_model;
+pub mod mcp_integration;
 pub mod settings_manager;
 pub mod window_state;
 
+#[cfg(test)]
+mod test_util;
+
 pub use ai_manager::AiManager;
 pub use keyring_store::{AiProvider, KeyringStore};
 pub use settings_manager::SettingsManager;
@@ -2,7 +2,7 @@
 //!
 //! Handles loading and running local GGUF models for inference.
 
-use crate::ai_manager::AiStreamChunk;
+use crate::ai_manager::{AiStreamChunk, ChunkSink, DiscardSink, StreamMode, StreamOptions};
 use crate::keyring_store::AiProvider;
 use crate::local_model;
 use crate::settings_manager::SettingsManager;
@@ -15,12 +15,73 @@ use llama_cpp_2::model::AddBos;
 use llama_cpp_2::token::data_array::LlamaTokenDataArray;
 use llama_cpp_2::token::LlamaToken;
 use std::num::NonZeroU32;
-use std::sync::OnceLock;
-use tauri::{AppHandle, Emitter};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
 static LLAMA_BACKEND: OnceLock<LlamaBackend> = OnceLock::new();
 
+/// GPU layers offloaded when the user hasn't configured a count
+const DEFAULT_GPU_LAYERS: u32 = 32;
+
+/// Layer count passed to llama.cpp for "offload everything" (more than any model has)
+const ALL_GPU_LAYERS: u32 = 999;
+
+/// Map the configured GPU layer count to what llama.cpp gets (-1 = all, None = default)
+fn resolve_gpu_layers(configured: Option<i32>) -> u32 {
+    match configured {
+        None => DEFAULT_GPU_LAYERS,
+        Some(n) if n < 0 => ALL_GPU_LAYERS,
+        Some(n) => (n as u32).min(ALL_GPU_LAYERS),
+    }
+}
+
+/// Stop sequences used when the local model config doesn't set its own
+const DEFAULT_STOP_SEQUENCES: &[&str] = &[
+    "Kysymys:",
+    "Käyttäjä:",
+    "Expected Output:",
+    "User Request:",
+    "Instruction:",
+    "Vastaus:",
+    "<|eot_id|>",
+    "<|end_of_text|>",
+    "\n\n\n", // Stop on excessive newlines
+];
+
+/// Phases reported as 'local-inference-state' events while a request runs
+pub const STATE_LOADING_MODEL: &str = "loading-model";
+pub const STATE_CREATING_CONTEXT: &str = "creating-context";
+pub const STATE_TOKENIZING: &str = "tokenizing";
+pub const STATE_GENERATING: &str = "generating";
+
+/// A model kept in memory between inferences
+struct LoadedModel {
+    path: PathBuf,
+    /// GPU layers requested by settings (part of the cache key)
+    requested_gpu_layers: u32,
+    /// GPU layers actually used (0 if the GPU load fell back to CPU)
+    actual_gpu_layers: u32,
+    model: Arc<LlamaModel>,
+}
+
+// The most recently loaded model; reloaded only when the path or GPU setting changes
+static LOADED_MODEL: Mutex<Option<LoadedModel>> = Mutex::new(None);
+
+/// Token counts, timings and text of one local inference (model loading excluded)
+#[derive(Debug, Clone, Default)]
+pub struct InferenceStats {
+    pub prompt_tokens: usize,
+    pub generated_tokens: usize,
+    /// Everything sent to the sink, including text flushed at the end
+    pub response: String,
+    /// Time spent evaluating the prompt (the initial decode)
+    pub prompt_eval: Duration,
+    /// Time spent generating tokens after the prompt was evaluated
+    pub generation: Duration,
+}
+
 #[derive(Debug, Error)]
 pub enum LocalInferenceError {
     #[error("Failed to load model: {0}")]
@@ -35,10 +96,127 @@ pub enum LocalInferenceError {
     ModelNotDownloaded,
     #[error("Backend not initialized")]
     BackendNotInitialized,
+    #[error("Generation stopped: {0}")]
+    Aborted(String),
     #[error("Local model error: {0}")]
     LocalModelError(#[from] local_model::LocalModelError),
 }
 
+/// Accumulates raw token bytes and releases only complete UTF-8 text
+///
+/// A multi-byte character (e.g. "ä" or an emoji) can be split across two tokens.
+/// Decoding each token on its own would produce replacement characters, so
+/// incomplete trailing bytes are held back until the next token completes them.
+#[derive(Debug, Default)]
+struct Utf8StreamDecoder {
+    pending: Vec<u8>,
+}
+
+impl Utf8StreamDecoder {
+    /// Add token bytes and return the text that is now complete
+    fn push(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+        let mut output = String::new();
+
+        loop {
+            match std::str::from_utf8(&self.pending) {
+                Ok(text) => {
+                    output.push_str(text);
+                    self.pending.clear();
+                    return output;
+                }
+                Err(e) => {
+                    let valid = e.valid_up_to();
+                    match e.error_len() {
+                        // Incomplete sequence at the end: emit the valid prefix, keep the tail
+                        None => {
+                            output.push_str(&String::from_utf8_lossy(&self.pending[..valid]));
+                            self.pending.drain(..valid);
+                            return output;
+                        }
+                        // Invalid bytes that can never complete: replace them and continue
+                        Some(len) => {
+                            output.push_str(&String::from_utf8_lossy(&self.pending[..valid + len]));
+                            self.pending.drain(..valid + len);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Flush any remaining bytes at the end of generation
+    fn finish(&mut self) -> String {
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        text
+    }
+}
+
+/// Detects stop sequences at the end of the generated text
+///
+/// Only a sequence the output currently ends with counts, so a stop word that
+/// appeared earlier in valid output doesn't abort generation. Text that could be
+/// the start of a stop sequence is held back until it's ruled out, which lets the
+/// matched sequence be trimmed from the output instead of reaching the sink.
+#[derive(Debug)]
+struct StopSequenceMatcher {
+    sequences: Vec<String>,
+    pending: String,
+    stopped: Option<String>,
+}
+
+impl StopSequenceMatcher {
+    fn new(sequences: Vec<String>) -> Self {
+        Self {
+            sequences: sequences.into_iter().filter(|s| !s.is_empty()).collect(),
+            pending: String::new(),
+            stopped: None,
+        }
+    }
+
+    /// Add generated text and return the part that is safe to emit
+    fn push(&mut self, text: &str) -> String {
+        if self.stopped.is_some() {
+            return String::new();
+        }
+        self.pending.push_str(text);
+
+        if let Some(seq) = self.sequences.iter().find(|seq| self.pending.ends_with(seq.as_str())) {
+            let end = self.pending.len() - seq.len();
+            let output = self.pending[..end].to_string();
+            self.stopped = Some(seq.clone());
+            self.pending.clear();
+            return output;
+        }
+
+        // Hold back the longest tail that is still a prefix of some stop sequence
+        let held = self
+            .pending
+            .char_indices()
+            .map(|(i, _)| i)
+            .find(|&i| {
+                let tail = &self.pending[i..];
+                self.sequences.iter().any(|seq| seq.starts_with(tail))
+            })
+            .unwrap_or(self.pending.len());
+
+        let output = self.pending[..held].to_string();
+        self.pending.drain(..held);
+        output
+    }
+
+    /// The stop sequence that ended generation, if any
+    fn stopped(&self) -> Option<&str> {
+        self.stopped.as_deref()
+    }
+
+    /// Release held-back text at the end of generation
+    fn finish(&mut self) -> String {
+        std::mem::take(&mut self.pending)
+    }
+}
+
 /// Initialize the llama backend (call once at startup)
 /// Returns false if initialization fails (e.g. missing Vulkan drivers)
 pub fn init_backend() -> bool {
@@ -61,19 +239,230 @@ fn get_backend() -> Result<&'static LlamaBackend, LocalInferenceError> {
         .ok_or(LocalInferenceError::BackendNotInitialized)
 }
 
+/// Get the cached model, loading it if the path or GPU layer count changed
+/// Returns the model and the number of GPU layers actually in use.
+fn get_or_load_model(
+    sink: &dyn ChunkSink,
+    backend: &LlamaBackend,
+    model_path: &Path,
+    n_gpu_layers: u32,
+) -> Result<(Arc<LlamaModel>, u32), LocalInferenceError> {
+    let mut loaded = LOADED_MODEL.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Some(cached) = loaded.as_ref() {
+        if cached.path == model_path && cached.requested_gpu_layers == n_gpu_layers {
+            log::info!("Reusing loaded model: {:?}", model_path);
+            return Ok((cached.model.clone(), cached.actual_gpu_layers));
+        }
+    }
+
+    // Free the previous model before loading a new one to avoid holding two in memory
+    *loaded = None;
+
+    log::info!("Loading model: {:?}", model_path);
+    sink.send_state(STATE_LOADING_MODEL);
+
+    let model_params = LlamaModelParams::default().with_n_gpu_layers(n_gpu_layers);
+
+    let (model, actual_gpu_layers) = match LlamaModel::load_from_file(backend, model_path, &model_params) {
+        Ok(m) => (m, n_gpu_layers),
+        Err(e) => {
+            if n_gpu_layers > 0 {
+                log::warn!("Failed to load model with GPU ({} layers): {}. Falling back to CPU.", n_gpu_layers, e);
+                let cpu_params = LlamaModelParams::default().with_n_gpu_layers(0);
+                let model = LlamaModel::load_from_file(backend, model_path, &cpu_params)
+                    .map_err(|e2| LocalInferenceError::ModelLoadError(format!("CPU fallback also failed: {}", e2)))?;
+                (model, 0)
+            } else {
+                return Err(LocalInferenceError::ModelLoadError(e.to_string()));
+            }
+        }
+    };
+
+    let model = Arc::new(model);
+    *loaded = Some(LoadedModel {
+        path: model_path.to_path_buf(),
+        requested_gpu_layers: n_gpu_layers,
+        actual_gpu_layers,
+        model: model.clone(),
+    });
+
+    Ok((model, actual_gpu_layers))
+}
+
+/// GPU layers to offload for the configured GPU type, 0 on CPU
+fn gpu_layers_from_settings(settings: Option<&SettingsManager>) -> u32 {
+    let gpu_type = settings.map(|s| s.get_gpu_type()).unwrap_or(crate::keyring_store::GpuType::Cpu);
+    if gpu_type == crate::keyring_store::GpuType::Cpu {
+        return 0;
+    }
+
+    let configured = settings.and_then(|s| s.get_gpu_layers());
+    let layers = resolve_gpu_layers(configured);
+    log::info!(
+        "GPU acceleration enabled ({:?}), offloading {} layers{}",
+        gpu_type,
+        if layers == ALL_GPU_LAYERS { "all".to_string() } else { layers.to_string() },
+        if configured.is_none() { " (default)" } else { "" }
+    );
+    layers
+}
+
+/// Load a local model into memory ahead of the first inference
+/// Blocks until the model is loaded; does nothing if it is already in memory.
+/// Returns the number of GPU layers in use.
+pub fn preload_model(
+    provider: AiProvider,
+    settings: Option<&SettingsManager>,
+) -> Result<u32, LocalInferenceError> {
+    if !local_model::is_model_downloaded(provider, settings)? {
+        return Err(LocalInferenceError::ModelNotDownloaded);
+    }
+
+    let model_path = local_model::get_model_path(provider, settings)?;
+    let n_gpu_layers = gpu_layers_from_settings(settings);
+    let (_, gpu_layers) = get_or_load_model(&DiscardSink, get_backend()?, &model_path, n_gpu_layers)?;
+    Ok(gpu_layers)
+}
+
+/// Drop the cached model (e.g. before deleting its file)
+pub fn unload_model() {
+    let mut loaded = LOADED_MODEL.lock().unwrap_or_else(|e| e.into_inner());
+    if loaded.take().is_some() {
+        log::info!("Unloaded cached model");
+    }
+}
+
+/// Default system prompt for Poro 2 (Finnish text editor)
+const PORO2_SYSTEM_PROMPT: &str = "Olet muistiolapun tekstieditori. Päivitä lapun sisältö käyttäjän pyynnön mukaan. \nSÄÄNNÖT:\n1. Kirjoita AINA suomeksi.\n2. Käytä Markdown-muotoilua (otsikot, listat, lihavointi jne.).\n3. Tulosta VAIN päivitetty muistiolapun sisältö.\n4. Älä kirjoita mitään muuta (ei selityksiä, ei tervehdyksiä).";
+
+/// Default system prompt for Llama 3.1 (English note editor)
+const LLAMA3_SYSTEM_PROMPT: &str = "You are a helpful note editor. Update the note content according to the user's request. Use Markdown formatting. Output only the updated content without explanations.";
+
+/// Poro 2 system prompt when the response is inserted into the note
+const PORO2_APPEND_SYSTEM_PROMPT: &str = "Olet muistiolapun kirjoitusavustaja. Kirjoita lappuun lisättävä teksti käyttäjän pyynnön mukaan. \nSÄÄNNÖT:\n1. Kirjoita AINA suomeksi.\n2. Käytä Markdown-muotoilua (otsikot, listat, lihavointi jne.).\n3. Tulosta VAIN lisättävä teksti, älä toista nykyistä sisältöä.\n4. Älä kirjoita mitään muuta (ei selityksiä, ei tervehdyksiä).";
+
+/// Llama 3.1 system prompt when the response is inserted into the note
+const LLAMA3_APPEND_SYSTEM_PROMPT: &str = "You are a helpful writing assistant for notes. Write the text the user asks for. Use Markdown formatting. Output only the new text to insert, without repeating the existing content or adding explanations.";
+
+/// Language of the built-in instructions for models that handle more than one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PromptLanguage {
+    Finnish,
+    English,
+}
+
+/// Common short words used to tell Finnish from English text
+const FINNISH_MARKERS: &[&str] = &[
+    "ja", "on", "ei", "se", "että", "oli", "ovat", "mutta", "tai", "kun", "jos", "myös", "kanssa",
+    "tämä", "minä", "sinä", "hän", "me", "te", "he", "mitä", "kuin", "vain", "voi", "ole",
+];
+const ENGLISH_MARKERS: &[&str] = &[
+    "the", "and", "is", "are", "of", "to", "in", "that", "it", "for", "with", "this", "was", "be",
+    "not", "or", "you", "have", "on", "what", "but", "can", "my", "please", "write",
+];
+
+/// Guess whether text is mostly Finnish or English; None if there's too little to tell
+fn detect_language(text: &str) -> Option<PromptLanguage> {
+    let mut finnish = 0usize;
+    let mut english = 0usize;
+
+    for word in text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+    {
+        if FINNISH_MARKERS.contains(&word.as_str()) || word.contains(['ä', 'ö']) {
+            finnish += 1;
+        }
+        if ENGLISH_MARKERS.contains(&word.as_str()) {
+            english += 1;
+        }
+    }
+
+    if finnish + english < 3 || finnish == english {
+        None
+    } else if finnish > english {
+        Some(PromptLanguage::Finnish)
+    } else {
+        Some(PromptLanguage::English)
+    }
+}
+
+/// Pick the instruction language: the configured response language ("fi"/"en") wins,
+/// then detection on the request and note, then Finnish
+fn resolve_prompt_language(configured: Option<&str>, prompt: &str, context: &str) -> PromptLanguage {
+    match configured.map(|l| l.trim().to_lowercase()).as_deref() {
+        Some("fi") => PromptLanguage::Finnish,
+        Some("en") => PromptLanguage::English,
+        _ => detect_language(&format!("{}\n{}", prompt, context)).unwrap_or(PromptLanguage::Finnish),
+    }
+}
+
+/// The exact prompt text a local model is given for a request
+/// Resolves the configured system prompt and response language, then formats,
+/// unless the model is set to raw prompting.
+pub fn build_prompt(
+    provider: AiProvider,
+    prompt: &str,
+    context: &str,
+    options: &StreamOptions,
+    settings: Option<&SettingsManager>,
+) -> String {
+    if settings.is_some_and(|s| s.get_raw_prompt(provider)) {
+        return raw_prompt(prompt, context);
+    }
+
+    let system_prompt = options
+        .system_prompt
+        .clone()
+        .or_else(|| settings.and_then(|s| s.get_system_prompt(provider)));
+    let configured_language = settings.and_then(|s| s.get_response_language(provider));
+    let language = resolve_prompt_language(configured_language.as_deref(), prompt, context);
+    format_prompt(
+        provider,
+        prompt,
+        context,
+        system_prompt.as_deref(),
+        options.mode,
+        language,
+    )
+}
+
+/// Context and prompt as plain text, for models without an instruct template
+fn raw_prompt(prompt: &str, context: &str) -> String {
+    if context.is_empty() {
+        prompt.to_string()
+    } else {
+        format!("{}\n\n{}", context, prompt)
+    }
+}
+
 /// Format prompt for the model based on provider
-fn format_prompt(provider: AiProvider, prompt: &str, context: &str) -> String {
+/// `system_prompt` overrides the built-in system prompt for the stream mode when set.
+/// `language` picks Poro 2's instruction language; Llama 3.1 is always prompted in English.
+fn format_prompt(
+    provider: AiProvider,
+    prompt: &str,
+    context: &str,
+    system_prompt: Option<&str>,
+    mode: StreamMode,
+    language: PromptLanguage,
+) -> String {
+    let append = mode == StreamMode::Append;
     match provider {
-        AiProvider::Poro2_8B => {
+        AiProvider::Poro2_8B if language == PromptLanguage::Finnish => {
             // Llama 3.1 Instruct format - act as text editor, not chatbot
             // Specifically instruct to use Finnish and Markdown
             format!(
-                "<|start_header_id|>system<|end_header_id|>\n\nOlet muistiolapun tekstieditori. Päivitä lapun sisältö käyttäjän pyynnön mukaan. \nSÄÄNNÖT:\n1. Kirjoita AINA suomeksi.\n2. Käytä Markdown-muotoilua (otsikot, listat, lihavointi jne.).\n3. Tulosta VAIN päivitetty muistiolapun sisältö.\n4. Älä kirjoita mitään muuta (ei selityksiä, ei tervehdyksiä).<|eot_id|><|start_header_id|>user<|end_header_id|>\n\nNykyinen sisältö:\n{}\n\nKäyttäjän pyyntö: {}<|eot_id|><|start_header_id|>assistant<|end_header_id|>\n\n",
-                context, prompt
+                "<|start_header_id|>system<|end_header_id|>\n\n{}<|eot_id|><|start_header_id|>user<|end_header_id|>\n\nNykyinen sisältö:\n{}\n\nKäyttäjän pyyntö: {}<|eot_id|><|start_header_id|>assistant<|end_header_id|>\n\n",
+                system_prompt.unwrap_or(if append { PORO2_APPEND_SYSTEM_PROMPT } else { PORO2_SYSTEM_PROMPT }),
+                context,
+                prompt
             )
         }
-        AiProvider::Llama3_8B => {
-            // Llama 3.1 Instruct format - English version
+        AiProvider::Poro2_8B | AiProvider::Llama3_8B => {
+            // Llama 3.1 Instruct format - English version (also Poro 2 for English notes)
             // System: You are a note editor
             // User: Current content + request
             let user_message = if context.is_empty() {
@@ -83,28 +472,36 @@ fn format_prompt(provider: AiProvider, prompt: &str, context: &str) -> String {
             };
 
             format!(
-                "<|start_header_id|>system<|end_header_id|>\n\nYou are a helpful note editor. Update the note content according to the user's request. Use Markdown formatting. Output only the updated content without explanations.<|eot_id|><|start_header_id|>user<|end_header_id|>\n\n{}<|eot_id|><|start_header_id|>assistant<|end_header_id|>\n\n",
+                "<|start_header_id|>system<|end_header_id|>\n\n{}<|eot_id|><|start_header_id|>user<|end_header_id|>\n\n{}<|eot_id|><|start_header_id|>assistant<|end_header_id|>\n\n",
+                system_prompt.unwrap_or(if append { LLAMA3_APPEND_SYSTEM_PROMPT } else { LLAMA3_SYSTEM_PROMPT }),
                 user_message
             )
         }
         _ => {
             // Fallback format
-            format!(
-                "Context: {}\n\nUser: {}\n\nAssistant:",
-                context, prompt
-            )
+            match system_prompt {
+                Some(system) => format!(
+                    "System: {}\n\nContext: {}\n\nUser: {}\n\nAssistant:",
+                    system, context, prompt
+                ),
+                None => format!(
+                    "Context: {}\n\nUser: {}\n\nAssistant:",
+                    context, prompt
+                ),
+            }
         }
     }
 }
 
-/// Run local inference with streaming
+/// Run local inference, streaming chunks to the sink
 pub async fn run_local_inference(
-    app: &AppHandle,
+    sink: &dyn ChunkSink,
     provider: AiProvider,
     prompt: &str,
     context: &str,
+    options: &StreamOptions,
     settings: Option<&SettingsManager>,
-) -> Result<(), LocalInferenceError> {
+) -> Result<InferenceStats, LocalInferenceError> {
     // Check if model is downloaded
     if !local_model::is_model_downloaded(provider, settings)? {
         return Err(LocalInferenceError::ModelNotDownloaded);
@@ -113,36 +510,10 @@ pub async fn run_local_inference(
     let model_path = local_model::get_model_path(provider, settings)?;
     let backend = get_backend()?;
 
-    log::info!("Loading model: {:?}", model_path);
+    let n_gpu_layers = gpu_layers_from_settings(settings);
 
-    // Get GPU setting
-    let gpu_type = settings.map(|s| s.get_gpu_type()).unwrap_or(crate::keyring_store::GpuType::Cpu);
-    let mut n_gpu_layers = if gpu_type != crate::keyring_store::GpuType::Cpu {
-        log::info!("GPU acceleration enabled ({:?}), offloading 32 layers", gpu_type);
-        32
-    } else {
-        0
-    };
-
-    // Load model
-    let mut model_params = LlamaModelParams::default()
-        .with_n_gpu_layers(n_gpu_layers);
-    
-    let mut current_n_gpu_layers = n_gpu_layers;
-    let model = match LlamaModel::load_from_file(backend, &model_path, &model_params) {
-        Ok(m) => m,
-        Err(e) => {
-            if n_gpu_layers > 0 {
-                log::warn!("Failed to load model with GPU ({} layers): {}. Falling back to CPU.", n_gpu_layers, e);
-                current_n_gpu_layers = 0;
-                model_params = LlamaModelParams::default().with_n_gpu_layers(0);
-                LlamaModel::load_from_file(backend, &model_path, &model_params)
-                    .map_err(|e2| LocalInferenceError::ModelLoadError(format!("CPU fallback also failed: {}", e2)))?
-            } else {
-                return Err(LocalInferenceError::ModelLoadError(e.to_string()));
-            }
-        }
-    };
+    // Load model (or reuse the one kept in memory); contexts are created per request
+    let (model, current_n_gpu_layers) = get_or_load_model(sink, backend, &model_path, n_gpu_layers)?;
 
     let actual_device = if current_n_gpu_layers > 0 {
         "GPU".to_string()
@@ -156,6 +527,7 @@ pub async fn run_local_inference(
         .with_n_batch(512); // Increased from 128
 
     log::info!("Creating context with n_ctx=2048, n_batch=512");
+    sink.send_state(STATE_CREATING_CONTEXT);
 
     let mut ctx = model
         .new_context(backend, ctx_params)
@@ -164,7 +536,8 @@ pub async fn run_local_inference(
     log::info!("Context created successfully");
 
     // Format and tokenize prompt
-    let formatted_prompt = format_prompt(provider, prompt, context);
+    sink.send_state(STATE_TOKENIZING);
+    let formatted_prompt = build_prompt(provider, prompt, context, options, settings);
     let tokens = model
         .str_to_token(&formatted_prompt, AddBos::Always)
         .map_err(|e| LocalInferenceError::TokenizationError(e.to_string()))?;
@@ -191,11 +564,15 @@ pub async fn run_local_inference(
     }
 
     log::info!("Starting initial decode (this may take a moment on CPU)...");
+    sink.send_state(STATE_GENERATING);
 
+    let prompt_eval_started = Instant::now();
     ctx.decode(&mut batch)
         .map_err(|e| LocalInferenceError::InferenceError(e.to_string()))?;
+    let prompt_eval = prompt_eval_started.elapsed();
 
-    log::info!("Initial decode completed");
+    log::info!("Initial decode completed in {:?}", prompt_eval);
+    let generation_started = Instant::now();
 
     // Generate tokens
     let mut all_tokens = tokens.clone();
@@ -203,11 +580,19 @@ pub async fn run_local_inference(
     const MAX_TOKENS: usize = 512; // Reduced for CPU inference (was 2048)
     let mut generated_tokens = 0;
     let mut emitted_chunks = 0;
-    let mut full_response = String::new();
+    let mut response = String::new();
+    let mut decoder = Utf8StreamDecoder::default();
+    let stop_sequences = settings
+        .and_then(|s| s.get_stop_sequences(provider))
+        .unwrap_or_else(|| DEFAULT_STOP_SEQUENCES.iter().map(|s| s.to_string()).collect());
+    let mut stop_matcher = StopSequenceMatcher::new(stop_sequences);
+    let max_new_tokens = options.max_tokens.unwrap_or(usize::MAX);
+    // Set on every early exit; falling out of the loop means a token limit was hit
+    let mut stopped_early = false;
 
     log::info!("Starting token generation (max {} tokens)...", MAX_TOKENS);
 
-    while n_cur < MAX_TOKENS {
+    while n_cur < MAX_TOKENS && generated_tokens < max_new_tokens {
         // Sample next token
         let candidates = ctx.candidates();
         let mut candidates_array = LlamaTokenDataArray::from_iter(candidates, false);
@@ -249,6 +634,7 @@ pub async fn run_local_inference(
             token_id
         } else {
             log::info!("No more candidate tokens available");
+            stopped_early = true;
             break; // No more tokens
         };
 
@@ -258,42 +644,17 @@ pub async fn run_local_inference(
         // Check for EOS
         if model.is_eog_token(token) {
             log::info!("EOS token reached after {} tokens", generated_tokens);
+            stopped_early = true;
             break;
         }
 
-        // Decode token to text
-        let text_res = model.token_to_str(token, llama_cpp_2::model::Special::Plaintext);
-        
+        // Decode token to bytes; text is released only at UTF-8 boundaries
+        let text_res = model
+            .token_to_bytes(token, llama_cpp_2::model::Special::Plaintext)
+            .map(|bytes| decoder.push(&bytes));
+
         match text_res {
             Ok(text) => {
-                full_response.push_str(&text);
-
-                // Stop sequence detection (case insensitive-ish)
-                let stop_sequences = [
-                    "Kysymys:", 
-                    "Käyttäjä:", 
-                    "Expected Output:", 
-                    "User Request:", 
-                    "Instruction:",
-                    "Vastaus:",
-                    "<|eot_id|>",
-                    "<|end_of_text|>",
-                    "\n\n\n" // Stop on excessive newlines
-                ];
-                
-                let mut should_stop = false;
-                for seq in stop_sequences {
-                    if full_response.contains(seq) {
-                        log::info!("Stop sequence '{}' detected. Stopping.", seq);
-                        should_stop = true;
-                        break;
-                    }
-                }
-                
-                if should_stop {
-                    break;
-                }
-
                 // Log first 5 tokens to see what we're getting
                 if generated_tokens <= 5 {
                     log::info!("Token {}: id={} text={:?}", generated_tokens, token, text);
@@ -307,20 +668,30 @@ pub async fn run_local_inference(
                 } else if text == "<unk>" || text == " <unk>" {
                     log::info!("Skipping <unk> token {} (id: {})", generated_tokens, token);
                 } else {
-                    // Emit chunk to frontend
-                    if emitted_chunks < 5 {
-                        log::info!("Emitting chunk {}: {:?}", emitted_chunks + 1, text);
-                    }
-                    app.emit(
-                        "ai-stream-chunk",
-                        AiStreamChunk {
-                            chunk: text.clone(),
+                    let output = stop_matcher.push(&text);
+                    if !output.is_empty() {
+                        // Emit chunk to frontend
+                        if emitted_chunks < 5 {
+                            log::info!("Emitting chunk {}: {:?}", emitted_chunks + 1, output);
+                        }
+                        response.push_str(&output);
+                        sink.send(AiStreamChunk {
+                            chunk: output,
                             done: false,
                             gpu_info: Some(actual_device.clone()),
-                        },
-                    )
-                    .ok();
-                    emitted_chunks += 1;
+                        });
+                        emitted_chunks += 1;
+
+                        if let Some(reason) = sink.failure() {
+                            return Err(LocalInferenceError::Aborted(reason));
+                        }
+                    }
+
+                    if let Some(seq) = stop_matcher.stopped() {
+                        log::info!("Stop sequence {:?} detected. Stopping.", seq);
+                        stopped_early = true;
+                        break;
+                    }
                 }
             }
             Err(e) => {
@@ -336,32 +707,210 @@ pub async fn run_local_inference(
         }
 
         // Prepare next batch
+        // Text has already been produced here, so a failure ends the generation with the
+        // partial output instead of discarding it; only the initial decode is a hard error.
         batch.clear();
-        batch
+        let decoded = batch
             .add(token, n_cur as i32, &[0], true)
-            .map_err(|e| LocalInferenceError::InferenceError(e.to_string()))?;
-
-        ctx.decode(&mut batch)
-            .map_err(|e| LocalInferenceError::InferenceError(e.to_string()))?;
+            .map_err(|e| e.to_string())
+            .and_then(|_| ctx.decode(&mut batch).map_err(|e| e.to_string()));
+        if let Err(e) = decoded {
+            log::warn!(
+                "Decode failed after {} generated tokens, returning partial output: {}",
+                generated_tokens,
+                e
+            );
+            stopped_early = true;
+            break;
+        }
 
         n_cur += 1;
     }
 
+    let tail = flush_tail(&mut decoder, &mut stop_matcher);
+    if !tail.is_empty() {
+        response.push_str(&tail);
+        sink.send(AiStreamChunk {
+            chunk: tail,
+            done: false,
+            gpu_info: Some(actual_device.clone()),
+        });
+        emitted_chunks += 1;
+    }
+
+    if !stopped_early {
+        log::warn!("Local response truncated after {} tokens", generated_tokens);
+        sink.send_truncated();
+    }
+
     // Emit done signal
-    app.emit(
-        "ai-stream-chunk",
-        AiStreamChunk {
-            chunk: String::new(),
-            done: true,
-            gpu_info: Some(actual_device),
-        },
-    )
-    .ok();
+    sink.send(AiStreamChunk {
+        chunk: String::new(),
+        done: true,
+        gpu_info: Some(actual_device),
+    });
 
     log::info!(
         "Local inference completed: generated {} tokens, emitted {} chunks",
         generated_tokens,
         emitted_chunks
     );
-    Ok(())
+    Ok(InferenceStats {
+        prompt_tokens: tokens.len(),
+        generated_tokens,
+        response,
+        prompt_eval,
+        generation: generation_started.elapsed(),
+    })
+}
+
+/// Text still held back when generation ends: bytes the decoder kept (e.g. generation
+/// stopped mid-character) and text kept as a possible stop sequence start
+fn flush_tail(decoder: &mut Utf8StreamDecoder, stop_matcher: &mut StopSequenceMatcher) -> String {
+    let mut tail = stop_matcher.push(&decoder.finish());
+    tail.push_str(&stop_matcher.finish());
+    tail
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_gpu_layers() {
+        assert_eq!(resolve_gpu_layers(None), DEFAULT_GPU_LAYERS);
+        assert_eq!(resolve_gpu_layers(Some(-1)), ALL_GPU_LAYERS);
+        assert_eq!(resolve_gpu_layers(Some(0)), 0);
+        assert_eq!(resolve_gpu_layers(Some(20)), 20);
+    }
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(
+            detect_language("Kirjoita lyhyt lista asioista, jotka pitää tehdä tänään ja huomenna"),
+            Some(PromptLanguage::Finnish)
+        );
+        assert_eq!(
+            detect_language("Please write a short list of the things I have to do this week"),
+            Some(PromptLanguage::English)
+        );
+        assert_eq!(detect_language("TODO"), None);
+    }
+
+    #[test]
+    fn test_prompt_language_override_and_default() {
+        let english = "Please summarize this note and the list in it";
+        assert_eq!(resolve_prompt_language(None, english, ""), PromptLanguage::English);
+        assert_eq!(resolve_prompt_language(Some("fi"), english, ""), PromptLanguage::Finnish);
+        assert_eq!(resolve_prompt_language(None, "ok", ""), PromptLanguage::Finnish);
+
+        let formatted = format_prompt(
+            AiProvider::Poro2_8B,
+            english,
+            "",
+            None,
+            StreamMode::ReplaceContent,
+            PromptLanguage::English,
+        );
+        assert!(formatted.contains(LLAMA3_SYSTEM_PROMPT));
+        assert!(!formatted.contains("Käyttäjän pyyntö"));
+    }
+
+    #[test]
+    fn test_raw_prompt_has_no_template() {
+        assert_eq!(raw_prompt("Once upon a time", ""), "Once upon a time");
+        let raw = raw_prompt("Continue:", "# Story\nIt was dark.");
+        assert_eq!(raw, "# Story\nIt was dark.\n\nContinue:");
+        assert!(!raw.contains("<|start_header_id|>"));
+    }
+
+    #[test]
+    fn test_decoder_joins_split_two_byte_char() {
+        let mut decoder = Utf8StreamDecoder::default();
+        let bytes = "Hyvää".as_bytes();
+        // Split inside the first "ä" (0xC3 0xA4)
+        let split = 4;
+
+        let first = decoder.push(&bytes[..split]);
+        let second = decoder.push(&bytes[split..]);
+
+        assert_eq!(first, "Hyv");
+        assert_eq!(second, "ää");
+        assert!(!format!("{}{}", first, second).contains('\u{FFFD}'));
+    }
+
+    #[test]
+    fn test_decoder_joins_split_emoji() {
+        let mut decoder = Utf8StreamDecoder::default();
+        let emoji = "🙂".as_bytes();
+
+        assert_eq!(decoder.push(&emoji[..1]), "");
+        assert_eq!(decoder.push(&emoji[1..3]), "");
+        assert_eq!(decoder.push(&emoji[3..]), "🙂");
+        assert_eq!(decoder.finish(), "");
+    }
+
+    #[test]
+    fn test_decoder_replaces_invalid_bytes() {
+        let mut decoder = Utf8StreamDecoder::default();
+        assert_eq!(decoder.push(&[b'a', 0xFF, b'b']), "a\u{FFFD}b");
+    }
+
+    fn matcher(sequences: &[&str]) -> StopSequenceMatcher {
+        StopSequenceMatcher::new(sequences.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn test_stop_sequence_trimmed_from_output() {
+        let mut m = matcher(&["Vastaus:"]);
+        assert_eq!(m.push("Hei maailma"), "Hei maailma");
+        assert_eq!(m.push("\nVast"), "\n");
+        assert_eq!(m.push("aus:"), "");
+        assert_eq!(m.stopped(), Some("Vastaus:"));
+        assert_eq!(m.push("more"), "");
+        assert_eq!(m.finish(), "");
+    }
+
+    #[test]
+    fn test_stop_sequence_only_matches_at_end() {
+        let mut m = matcher(&["Instruction:"]);
+        assert_eq!(m.push("Instruction: follow the steps"), "Instruction: follow the steps");
+        assert_eq!(m.stopped(), None);
+    }
+
+    #[test]
+    fn test_stop_sequence_releases_false_prefix() {
+        let mut m = matcher(&["<|eot_id|>"]);
+        assert_eq!(m.push("a <|"), "a ");
+        assert_eq!(m.push("b"), "<|b");
+        assert_eq!(m.push("<|eot"), "");
+        assert_eq!(m.finish(), "<|eot");
+        assert_eq!(m.stopped(), None);
+    }
+
+    #[test]
+    fn test_stop_sequence_in_same_chunk_as_text() {
+        let mut m = matcher(&["\n\n\n"]);
+        assert_eq!(m.push("Lista valmis\n\n\n"), "Lista valmis");
+        assert_eq!(m.stopped(), Some("\n\n\n"));
+    }
+
+    #[test]
+    fn test_decoder_flushes_incomplete_tail() {
+        let mut decoder = Utf8StreamDecoder::default();
+        assert_eq!(decoder.push(&[0xC3]), "");
+        assert_eq!(decoder.finish(), "\u{FFFD}");
+    }
+
+    #[test]
+    fn test_flush_tail_includes_held_back_text() {
+        let mut decoder = Utf8StreamDecoder::default();
+        let mut m = StopSequenceMatcher::new(vec!["\n\nUser:".to_string()]);
+
+        // "\n\n" could start the stop sequence and the last byte is half a character
+        let mut response = m.push(&decoder.push(b"Moi\n\n\xC3"));
+        assert_eq!(response, "Moi");
+        response.push_str(&flush_tail(&mut decoder, &mut m));
+        assert_eq!(response, "Moi\n\n\u{FFFD}");
+    }
 }
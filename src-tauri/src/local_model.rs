@@ -5,13 +5,86 @@
 use crate::keyring_store::AiProvider;
 use crate::settings_manager::SettingsManager;
 use directories::ProjectDirs;
-use futures::StreamExt;
+use futures::{Stream, StreamExt};
+use once_cell::sync::Lazy;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter};
 use thiserror::Error;
+use url::Url;
+
+/// How long a HuggingFace repo file listing is reused before re-fetching
+const REPO_LISTING_TTL: Duration = Duration::from_secs(600);
+
+/// Download speed is averaged over this much recent time, not the whole download
+const DOWNLOAD_RATE_WINDOW: Duration = Duration::from_secs(5);
+
+/// How often a download that is waiting for data checks whether it was cancelled
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Cached GGUF listings per HuggingFace repo
+static REPO_LISTINGS: Lazy<Mutex<HashMap<String, (Instant, Vec<QuantizationInfo>)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Temp files of downloads in progress with their cancel flags; cleanup must leave these alone
+static ACTIVE_DOWNLOADS: Lazy<Mutex<HashMap<PathBuf, Arc<AtomicBool>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Marks a temp file as in use for as long as the download runs
+struct ActiveDownload {
+    temp_path: PathBuf,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ActiveDownload {
+    fn register(temp_path: &Path) -> Self {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        ACTIVE_DOWNLOADS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(temp_path.to_path_buf(), cancelled.clone());
+        Self {
+            temp_path: temp_path.to_path_buf(),
+            cancelled,
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Wait for the next chunk, giving up as soon as the download is cancelled
+    /// A stalled connection would otherwise never get to see the cancel flag.
+    async fn next_chunk<S: Stream + Unpin>(&self, stream: &mut S) -> Result<Option<S::Item>, LocalModelError> {
+        loop {
+            if self.is_cancelled() {
+                return Err(LocalModelError::Cancelled);
+            }
+            if let Ok(item) = tokio::time::timeout(CANCEL_POLL_INTERVAL, stream.next()).await {
+                return Ok(item);
+            }
+        }
+    }
+}
+
+impl Drop for ActiveDownload {
+    fn drop(&mut self) {
+        let mut active = ACTIVE_DOWNLOADS.lock().unwrap_or_else(|e| e.into_inner());
+        // The entry may already have been reset and replaced by a newer download
+        if active
+            .get(&self.temp_path)
+            .is_some_and(|flag| Arc::ptr_eq(flag, &self.cancelled))
+        {
+            active.remove(&self.temp_path);
+        }
+    }
+}
 
 #[derive(Debug, Error)]
 pub enum LocalModelError {
@@ -23,6 +96,12 @@ pub enum LocalModelError {
     IoError(#[from] std::io::Error),
     #[error("Invalid provider for local model: {0}")]
     InvalidProvider(String),
+    #[error("HuggingFace API error: {0}")]
+    HubError(String),
+    #[error("Invalid model URL: {0}")]
+    InvalidUrl(String),
+    #[error("Download cancelled")]
+    Cancelled,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +110,59 @@ pub struct ModelDownloadProgress {
     pub bytes_downloaded: u64,
     pub total_bytes: Option<u64>,
     pub percentage: f64,
+    /// Average speed over the last few seconds
+    pub bytes_per_second: f64,
+    /// Estimated time left at the current speed (None if the size or speed is unknown)
+    pub eta_seconds: Option<u64>,
+}
+
+/// Rolling download speed over `DOWNLOAD_RATE_WINDOW`
+struct RateTracker {
+    /// (time, total bytes downloaded at that time), oldest first
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl RateTracker {
+    fn new(start: Instant) -> Self {
+        Self {
+            samples: VecDeque::from([(start, 0)]),
+        }
+    }
+
+    /// Record the running total, dropping samples that fell out of the window
+    /// The newest sample at or before the window start is kept as the baseline.
+    fn record(&mut self, now: Instant, downloaded: u64) {
+        self.samples.push_back((now, downloaded));
+        while self.samples.len() > 2
+            && now.duration_since(self.samples[1].0) >= DOWNLOAD_RATE_WINDOW
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    fn bytes_per_second(&self) -> f64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some(&(start, from)), Some(&(end, to))) => {
+                let secs = end.duration_since(start).as_secs_f64();
+                if secs > 0.0 {
+                    (to - from) as f64 / secs
+                } else {
+                    0.0
+                }
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Seconds until `total` bytes are downloaded at the current speed
+    fn eta_seconds(&self, total: Option<u64>) -> Option<u64> {
+        let rate = self.bytes_per_second();
+        let downloaded = self.samples.back()?.1;
+        match total {
+            Some(total) if rate > 0.0 => Some((total.saturating_sub(downloaded) as f64 / rate).ceil() as u64),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,6 +179,50 @@ pub struct ModelStatus {
     pub path: Option<String>,
 }
 
+/// A GGUF file found in the models directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalModelFile {
+    pub filename: String,
+    pub path: String,
+    pub size: u64,
+    /// Last modification time (Unix seconds)
+    pub modified_at: Option<i64>,
+    /// Providers configured to use this file (empty = not referenced by any provider)
+    pub providers: Vec<String>,
+}
+
+/// All downloaded model files and the disk space they use
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalModelList {
+    pub models: Vec<LocalModelFile>,
+    pub total_bytes: u64,
+}
+
+/// A GGUF file available in a model's HuggingFace repo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuantizationInfo {
+    pub filename: String,
+    /// Quantization tag parsed from the filename (e.g. "Q4_K_M")
+    pub quantization: String,
+    pub size_bytes: Option<u64>,
+}
+
+/// Estimated memory needed to run a local model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelRequirements {
+    pub provider: String,
+    pub quantization: String,
+    /// Model weights size in bytes (the GGUF file size if already downloaded)
+    pub model_bytes: u64,
+    pub needed_ram: u64,
+    /// VRAM needed for the offloaded layers (None when running on CPU)
+    pub needed_vram: Option<u64>,
+    pub available_ram: u64,
+    pub will_fit: bool,
+    /// Message for the UI to show before downloading when the model likely won't fit
+    pub warning: Option<String>,
+}
+
 /// Get the directory where local models are stored
 pub fn get_models_dir() -> Result<PathBuf, LocalModelError> {
     let proj_dirs = ProjectDirs::from("com", "HexStickyNote", "HexStickyNote")
@@ -58,6 +234,65 @@ pub fn get_models_dir() -> Result<PathBuf, LocalModelError> {
     Ok(models_dir)
 }
 
+/// Filename used when neither the URL nor the server names the file
+const FALLBACK_MODEL_FILENAME: &str = "model.gguf";
+
+/// Parse a custom model URL, accepting only http and https
+fn parse_model_url(raw: &str) -> Result<Url, LocalModelError> {
+    let url = Url::parse(raw.trim()).map_err(|e| LocalModelError::InvalidUrl(format!("{}: {}", raw, e)))?;
+    match url.scheme() {
+        "http" | "https" => Ok(url),
+        scheme => Err(LocalModelError::InvalidUrl(format!(
+            "unsupported scheme \"{}\" (expected http or https)",
+            scheme
+        ))),
+    }
+}
+
+/// Keep only the final path component so a name can't point outside the models directory
+fn sanitize_model_filename(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or("").trim();
+    if name.is_empty() || name == "." || name == ".." {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+/// Filename from the last path segment of a URL (query and fragment ignored)
+fn filename_from_url(url: &Url) -> Option<String> {
+    url.path_segments()?
+        .filter(|segment| !segment.is_empty())
+        .last()
+        .and_then(sanitize_model_filename)
+}
+
+/// Filename from a Content-Disposition header, preferring the RFC 5987 `filename*` form
+fn filename_from_content_disposition(header: &str) -> Option<String> {
+    let params: Vec<(String, &str)> = header
+        .split(';')
+        .filter_map(|part| part.split_once('='))
+        .map(|(key, value)| (key.trim().to_ascii_lowercase(), value.trim()))
+        .collect();
+
+    let extended = params
+        .iter()
+        .find(|(key, _)| key == "filename*")
+        .and_then(|(_, value)| value.split_once("''"))
+        .map(|(_, encoded)| {
+            url::form_urlencoded::parse(format!("n={}", encoded.replace('+', "%2B")).as_bytes())
+                .next()
+                .map(|(_, decoded)| decoded.into_owned())
+                .unwrap_or_default()
+        });
+    let plain = params
+        .iter()
+        .find(|(key, _)| key == "filename")
+        .map(|(_, value)| value.trim_matches('"').to_string());
+
+    extended.or(plain).as_deref().and_then(sanitize_model_filename)
+}
+
 /// Get the download URL and filename for a provider
 fn get_model_info(
     provider: AiProvider,
@@ -67,13 +302,12 @@ fn get_model_info(
     if let Some(settings_mgr) = settings {
         if let Some(config) = settings_mgr.get_local_model_config(provider) {
             if let Some(custom_url) = config.custom_url {
-                // Extract filename from URL
-                let filename = custom_url
-                    .split('/')
-                    .last()
-                    .unwrap_or("model.gguf")
-                    .to_string();
-                return Ok((custom_url, filename));
+                // Filename from the URL path, else one saved from Content-Disposition on download
+                let url = parse_model_url(&custom_url)?;
+                let filename = filename_from_url(&url)
+                    .or_else(|| sanitize_model_filename(&config.filename))
+                    .unwrap_or_else(|| FALLBACK_MODEL_FILENAME.to_string());
+                return Ok((url.to_string(), filename));
             }
             // Use repo/filename from settings
             if !config.repo.is_empty() && !config.filename.is_empty() {
@@ -100,6 +334,201 @@ fn get_model_info(
     }
 }
 
+/// Default HuggingFace repo for a local provider
+fn default_repo(provider: AiProvider) -> Option<&'static str> {
+    match provider {
+        AiProvider::Poro2_8B => Some("mradermacher/Llama-Poro-2-8B-Instruct-GGUF"),
+        AiProvider::Llama3_8B => Some("mradermacher/Meta-Llama-3.1-8B-Instruct-GGUF"),
+        _ => None,
+    }
+}
+
+/// Get the HuggingFace repo configured for a provider
+fn get_model_repo(
+    provider: AiProvider,
+    settings: Option<&SettingsManager>,
+) -> Result<String, LocalModelError> {
+    let configured = settings
+        .and_then(|s| s.get_local_model_config(provider))
+        .map(|config| config.repo)
+        .filter(|repo| !repo.is_empty());
+
+    configured
+        .or_else(|| default_repo(provider).map(|r| r.to_string()))
+        .ok_or_else(|| LocalModelError::InvalidProvider(format!("{:?} is not a local model provider", provider)))
+}
+
+/// Parse the quantization tag from a GGUF filename ("Model.Q4_K_M.gguf" -> "Q4_K_M")
+fn parse_quantization(filename: &str) -> String {
+    let stem = filename.strip_suffix(".gguf").unwrap_or(filename);
+    stem.rsplit(['.', '-']).next().unwrap_or(stem).to_string()
+}
+
+/// List the GGUF quantization variants available in a provider's HuggingFace repo
+/// Listings are cached briefly to avoid hammering the API.
+pub async fn get_available_quantizations(
+    provider: AiProvider,
+    settings: Option<&SettingsManager>,
+) -> Result<Vec<QuantizationInfo>, LocalModelError> {
+    let repo = get_model_repo(provider, settings)?;
+
+    let cached = REPO_LISTINGS
+        .lock()
+        .unwrap()
+        .get(&repo)
+        .filter(|(fetched_at, _)| fetched_at.elapsed() < REPO_LISTING_TTL)
+        .map(|(_, files)| files.clone());
+    if let Some(files) = cached {
+        return Ok(files);
+    }
+
+    let url = format!("https://huggingface.co/api/models/{}/tree/main", repo);
+    let client = settings.map_or_else(Client::new, |s| s.http_client());
+    let response = client.get(&url).send().await?;
+
+    if !response.status().is_success() {
+        return Err(LocalModelError::HubError(format!(
+            "Failed to list files in {}: HTTP {}",
+            repo,
+            response.status()
+        )));
+    }
+
+    let entries: Vec<serde_json::Value> = response.json().await?;
+    let mut files: Vec<QuantizationInfo> = entries
+        .iter()
+        .filter(|entry| entry["type"].as_str() == Some("file"))
+        .filter_map(|entry| {
+            let filename = entry["path"].as_str()?;
+            if !filename.ends_with(".gguf") {
+                return None;
+            }
+            // LFS files report their real size under lfs.size
+            let size_bytes = entry["lfs"]["size"].as_u64().or_else(|| entry["size"].as_u64());
+            Some(QuantizationInfo {
+                filename: filename.to_string(),
+                quantization: parse_quantization(filename),
+                size_bytes,
+            })
+        })
+        .collect();
+    files.sort_by_key(|f| f.size_bytes.unwrap_or(0));
+
+    REPO_LISTINGS
+        .lock()
+        .unwrap()
+        .insert(repo, (Instant::now(), files.clone()));
+
+    Ok(files)
+}
+
+/// Parameter count used when the filename doesn't mention one (both default models are 8B)
+const DEFAULT_PARAMETER_BILLIONS: f64 = 8.0;
+
+/// Rough extra memory for the KV cache (2048 token context) and runtime buffers
+const INFERENCE_OVERHEAD_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Layer count assumed when working out which share of a model is offloaded
+const TYPICAL_LAYER_COUNT: f64 = 32.0;
+
+/// Parse the parameter count in billions from a model filename ("Llama-3.1-8B" -> 8, "8x7B" -> 56)
+fn parse_parameter_billions(filename: &str) -> Option<f64> {
+    filename
+        .split(['-', '_', '.'])
+        .filter_map(|part| {
+            let size = part.strip_suffix(['B', 'b'])?;
+            match size.split_once(['x', 'X']) {
+                Some((experts, per_expert)) => {
+                    Some(experts.parse::<f64>().ok()? * per_expert.parse::<f64>().ok()?)
+                }
+                None => size.parse::<f64>().ok(),
+            }
+        })
+        .next()
+}
+
+/// Approximate bits per weight for a GGUF quantization tag
+fn bits_per_weight(quantization: &str) -> f64 {
+    let quant = quantization.to_uppercase();
+    match quant.as_str() {
+        "F32" => 32.0,
+        "F16" | "BF16" => 16.0,
+        q if q.starts_with("Q8") => 8.5,
+        q if q.starts_with("Q6") => 6.6,
+        q if q.starts_with("Q5") => 5.7,
+        q if q.starts_with("Q4") || q.starts_with("IQ4") => 4.8,
+        q if q.starts_with("Q3") || q.starts_with("IQ3") => 3.9,
+        q if q.starts_with("Q2") || q.starts_with("IQ2") => 2.9,
+        q if q.starts_with("IQ1") => 1.8,
+        _ => 4.8,
+    }
+}
+
+/// Estimate model weights size in bytes from the filename alone
+fn estimate_model_bytes(filename: &str) -> u64 {
+    let billions = parse_parameter_billions(filename).unwrap_or(DEFAULT_PARAMETER_BILLIONS);
+    let bits = bits_per_weight(&parse_quantization(filename));
+    (billions * 1e9 * bits / 8.0) as u64
+}
+
+/// Estimate RAM/VRAM needed to run a model and compare it with the available memory
+/// This is a heuristic based on the quantization tag and parameter count, not an exact figure.
+pub fn estimate_requirements(
+    provider: AiProvider,
+    settings: Option<&SettingsManager>,
+) -> Result<ModelRequirements, LocalModelError> {
+    let (_, filename) = get_model_info(provider, settings)?;
+    let model_path = get_models_dir()?.join(&filename);
+
+    let model_bytes = match fs::metadata(&model_path) {
+        Ok(metadata) => metadata.len(),
+        Err(_) => estimate_model_bytes(&filename),
+    };
+
+    // Share of the weights that lives in VRAM when offloading
+    let gpu_enabled = settings
+        .map(|s| s.get_gpu_type() != crate::keyring_store::GpuType::Cpu)
+        .unwrap_or(false);
+    let offload_share = if gpu_enabled {
+        match settings.and_then(|s| s.get_gpu_layers()) {
+            Some(n) if n < 0 => 1.0,
+            Some(n) => (n as f64 / TYPICAL_LAYER_COUNT).min(1.0),
+            None => 1.0,
+        }
+    } else {
+        0.0
+    };
+
+    let offloaded_bytes = (model_bytes as f64 * offload_share) as u64;
+    let needed_ram = model_bytes - offloaded_bytes + INFERENCE_OVERHEAD_BYTES;
+    let needed_vram = gpu_enabled.then_some(offloaded_bytes + INFERENCE_OVERHEAD_BYTES);
+
+    let mut system = sysinfo::System::new();
+    system.refresh_memory();
+    let available_ram = system.available_memory();
+
+    let will_fit = needed_ram <= available_ram;
+    let warning = (!will_fit).then(|| {
+        format!(
+            "{} needs about {:.1} GB of RAM but only {:.1} GB is available",
+            provider.display_name(),
+            needed_ram as f64 / 1e9,
+            available_ram as f64 / 1e9
+        )
+    });
+
+    Ok(ModelRequirements {
+        provider: provider.as_str().to_string(),
+        quantization: parse_quantization(&filename),
+        model_bytes,
+        needed_ram,
+        needed_vram,
+        available_ram,
+        will_fit,
+        warning,
+    })
+}
+
 /// Get the path to a model file
 pub fn get_model_path(
     provider: AiProvider,
@@ -142,6 +571,68 @@ pub fn get_model_status(
     })
 }
 
+/// Check if a provider's model is being downloaded right now
+pub fn is_downloading(
+    provider: AiProvider,
+    settings: Option<&SettingsManager>,
+) -> Result<bool, LocalModelError> {
+    let temp_path = get_model_path(provider, settings)?.with_extension("tmp");
+    Ok(ACTIVE_DOWNLOADS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .contains_key(&temp_path))
+}
+
+/// List the `.gguf` files in `dir`, tagged with the providers in `associations` (filename -> providers)
+fn collect_model_files(
+    dir: &Path,
+    associations: &HashMap<String, Vec<String>>,
+) -> Result<LocalModelList, LocalModelError> {
+    let mut models = Vec::new();
+
+    for entry in fs::read_dir(dir)?.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some("gguf") || !path.is_file() {
+            continue;
+        }
+
+        let metadata = entry.metadata()?;
+        let filename = entry.file_name().to_string_lossy().to_string();
+        let modified_at = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64);
+
+        models.push(LocalModelFile {
+            providers: associations.get(&filename).cloned().unwrap_or_default(),
+            path: path.to_string_lossy().to_string(),
+            size: metadata.len(),
+            modified_at,
+            filename,
+        });
+    }
+
+    models.sort_by(|a, b| a.filename.cmp(&b.filename));
+    let total_bytes = models.iter().map(|m| m.size).sum();
+    Ok(LocalModelList { models, total_bytes })
+}
+
+/// List every downloaded model file, including ones no provider uses anymore
+pub fn list_local_models(settings: Option<&SettingsManager>) -> Result<LocalModelList, LocalModelError> {
+    let mut associations: HashMap<String, Vec<String>> = HashMap::new();
+    for provider in AiProvider::all().into_iter().filter(|p| !p.requires_api_key()) {
+        if let Ok((_, filename)) = get_model_info(provider, settings) {
+            associations
+                .entry(filename)
+                .or_default()
+                .push(provider.as_str().to_string());
+        }
+    }
+
+    collect_model_files(&get_models_dir()?, &associations)
+}
+
 /// Download a model from HuggingFace with progress tracking
 pub async fn download_model(
     app: &AppHandle,
@@ -149,7 +640,7 @@ pub async fn download_model(
     settings: Option<&SettingsManager>,
 ) -> Result<(), LocalModelError> {
     let (url, _filename) = get_model_info(provider, settings)?;
-    let model_path = get_model_path(provider, settings)?;
+    let mut model_path = get_model_path(provider, settings)?;
 
     // Check if already downloaded
     if model_path.exists() {
@@ -163,8 +654,8 @@ pub async fn download_model(
 
     log::info!("Downloading model from: {}", url);
 
-    let client = Client::new();
-    let response = client.get(url).send().await?;
+    let client = settings.map_or_else(Client::new, |s| s.http_client());
+    let response = client.get(&url).send().await?;
 
     if !response.status().is_success() {
         return Err(LocalModelError::HttpError(
@@ -172,21 +663,64 @@ pub async fn download_model(
         ));
     }
 
+    // A custom URL without a filename in its path: use the server's name and remember it
+    let url_has_filename = Url::parse(&url).ok().and_then(|u| filename_from_url(&u)).is_some();
+    let disposition_name = response
+        .headers()
+        .get(reqwest::header::CONTENT_DISPOSITION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(filename_from_content_disposition);
+    if let (false, Some(name), Some(settings_mgr)) = (url_has_filename, disposition_name, settings) {
+        if let Some(mut config) = settings_mgr.get_local_model_config(provider) {
+            if config.filename != name {
+                log::info!("Using filename {} from Content-Disposition", name);
+                config.filename = name;
+                if let Err(e) = settings_mgr.set_local_model_config(provider, config) {
+                    log::warn!("Failed to save model filename: {}", e);
+                }
+                model_path = get_model_path(provider, settings)?;
+                if model_path.exists() {
+                    log::info!("Model already downloaded: {:?}", model_path);
+                    app.emit("local-model-download-complete", ModelDownloadComplete {
+                        provider: provider.as_str().to_string(),
+                        path: model_path.to_string_lossy().to_string(),
+                    }).ok();
+                    return Ok(());
+                }
+            }
+        }
+    }
+
     let total_size = response.content_length();
 
     // Create a temporary file
     let temp_path = model_path.with_extension("tmp");
+    let active = ActiveDownload::register(&temp_path);
     let mut file = tokio::fs::File::create(&temp_path).await?;
     let mut stream = response.bytes_stream();
     let mut downloaded: u64 = 0;
+    let mut rate = RateTracker::new(Instant::now());
 
     let mut last_emitted_percentage = -1.0;
 
-    while let Some(chunk_result) = stream.next().await {
+    loop {
+        // The temp file is ours to delete on cancel; the reset doesn't touch it while we run
+        let chunk_result = match active.next_chunk(&mut stream).await {
+            Ok(Some(chunk_result)) => chunk_result,
+            Ok(None) => break,
+            Err(e) => {
+                drop(file);
+                tokio::fs::remove_file(&temp_path).await.ok();
+                log::info!("Download of {:?} cancelled", model_path);
+                return Err(e);
+            }
+        };
+
         let chunk = chunk_result?;
         tokio::io::AsyncWriteExt::write_all(&mut file, &chunk).await?;
 
         downloaded += chunk.len() as u64;
+        rate.record(Instant::now(), downloaded);
 
         let percentage = if let Some(total) = total_size {
             (downloaded as f64 / total as f64) * 100.0
@@ -202,6 +736,8 @@ pub async fn download_model(
                 bytes_downloaded: downloaded,
                 total_bytes: total_size,
                 percentage,
+                bytes_per_second: rate.bytes_per_second(),
+                eta_seconds: rate.eta_seconds(total_size),
             }).ok();
         }
     }
@@ -223,6 +759,78 @@ pub async fn download_model(
     Ok(())
 }
 
+/// Result of removing leftover download temp files
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TempCleanupResult {
+    pub files_removed: usize,
+    pub bytes_freed: u64,
+}
+
+/// Delete `*.tmp` files in `dir` that aren't in `active`
+fn cleanup_temp_files_in(dir: &Path, active: &HashSet<PathBuf>) -> Result<TempCleanupResult, LocalModelError> {
+    let mut result = TempCleanupResult::default();
+
+    for entry in fs::read_dir(dir)?.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let is_temp = path.extension().and_then(|s| s.to_str()) == Some("tmp");
+        if !is_temp || !path.is_file() || active.contains(&path) {
+            continue;
+        }
+
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                log::info!("Removed leftover download file {:?} ({} bytes)", path, size);
+                result.files_removed += 1;
+                result.bytes_freed += size;
+            }
+            Err(e) => log::warn!("Failed to remove {:?}: {}", path, e),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Delete temp files left behind by failed or cancelled downloads
+/// Temp files of downloads that are still running are kept.
+pub fn cleanup_temp_files() -> Result<TempCleanupResult, LocalModelError> {
+    let active: HashSet<PathBuf> = ACTIVE_DOWNLOADS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .keys()
+        .cloned()
+        .collect();
+    cleanup_temp_files_in(&get_models_dir()?, &active)
+}
+
+/// Recover from an interrupted or stuck download of a provider's model
+/// Cancels the download if one is still running (it deletes its own temp file once it
+/// stops), forgets it, deletes a leftover temp file and returns the model status as it is now.
+pub fn reset_download_state(
+    provider: AiProvider,
+    settings: Option<&SettingsManager>,
+) -> Result<ModelStatus, LocalModelError> {
+    let temp_path = get_model_path(provider, settings)?.with_extension("tmp");
+
+    let active = ACTIVE_DOWNLOADS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&temp_path);
+    if let Some(cancelled) = active {
+        // The download may still have the file open (deleting it would fail on Windows)
+        cancelled.store(true, Ordering::Relaxed);
+        log::info!("Cancelled running download {:?}", temp_path);
+    } else {
+        match fs::remove_file(&temp_path) {
+            Ok(()) => log::info!("Removed download temp file {:?}", temp_path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Failed to remove download temp file {:?}: {}", temp_path, e),
+        }
+    }
+
+    get_model_status(provider, settings)
+}
+
 /// Delete a downloaded model
 pub async fn delete_model(
     provider: AiProvider,
@@ -237,3 +845,139 @@ pub async fn delete_model(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_rate_tracker_uses_recent_window() {
+        let start = Instant::now();
+        let mut rate = RateTracker::new(start);
+
+        // 10 s at 1 MB/s, then 5 s at 4 MB/s: only the recent speed counts
+        for second in 1..=10 {
+            rate.record(start + Duration::from_secs(second), second * 1_000_000);
+        }
+        for second in 11..=15 {
+            rate.record(start + Duration::from_secs(second), 10_000_000 + (second - 10) * 4_000_000);
+        }
+
+        assert!((rate.bytes_per_second() - 4_000_000.0).abs() < 1.0);
+        assert_eq!(rate.eta_seconds(Some(38_000_000)), Some(2));
+        assert_eq!(rate.eta_seconds(None), None);
+    }
+
+    #[test]
+    fn test_filename_from_url_ignores_query() {
+        let url = parse_model_url("https://huggingface.co/org/repo/resolve/main/model.Q4_K_M.gguf?download=true#x").unwrap();
+        assert_eq!(filename_from_url(&url).as_deref(), Some("model.Q4_K_M.gguf"));
+
+        let url = parse_model_url("https://example.com/files/").unwrap();
+        assert_eq!(filename_from_url(&url).as_deref(), Some("files"));
+
+        let url = parse_model_url("https://example.com/").unwrap();
+        assert_eq!(filename_from_url(&url), None);
+    }
+
+    #[test]
+    fn test_parse_model_url_rejects_other_schemes() {
+        assert!(parse_model_url("file:///etc/passwd").is_err());
+        assert!(parse_model_url("ftp://example.com/model.gguf").is_err());
+        assert!(parse_model_url("not a url").is_err());
+        assert!(parse_model_url("http://example.com/model.gguf").is_ok());
+    }
+
+    #[test]
+    fn test_filename_from_content_disposition() {
+        assert_eq!(
+            filename_from_content_disposition("attachment; filename=\"model.gguf\"").as_deref(),
+            Some("model.gguf")
+        );
+        assert_eq!(
+            filename_from_content_disposition(
+                "attachment; filename=\"fallback.gguf\"; filename*=UTF-8''my%20model.gguf"
+            )
+            .as_deref(),
+            Some("my model.gguf")
+        );
+        assert_eq!(
+            filename_from_content_disposition("attachment; filename=\"../../evil.gguf\"").as_deref(),
+            Some("evil.gguf")
+        );
+        assert_eq!(filename_from_content_disposition("inline"), None);
+    }
+
+    #[test]
+    fn test_parse_parameter_billions() {
+        assert_eq!(parse_parameter_billions("Llama-Poro-2-8B-Instruct.Q4_K_M.gguf"), Some(8.0));
+        assert_eq!(parse_parameter_billions("Meta-Llama-3.1-70B-Instruct.Q4_K_M.gguf"), Some(70.0));
+        assert_eq!(parse_parameter_billions("Mixtral-8x7B-v0.1.Q5_K_M.gguf"), Some(56.0));
+        assert_eq!(parse_parameter_billions("model.gguf"), None);
+    }
+
+    #[test]
+    fn test_estimate_model_bytes() {
+        // 8B at ~4.8 bits per weight is roughly 4.8 GB
+        let bytes = estimate_model_bytes("Meta-Llama-3.1-8B-Instruct.Q4_K_M.gguf");
+        assert!((4_500_000_000..5_200_000_000).contains(&bytes));
+
+        let q8 = estimate_model_bytes("Meta-Llama-3.1-8B-Instruct.Q8_0.gguf");
+        assert!(q8 > bytes);
+    }
+
+    #[test]
+    fn test_cleanup_skips_active_downloads() {
+        let dir = TempDir::new("models");
+        fs::write(dir.join("stale.tmp"), b"12345").unwrap();
+        fs::write(dir.join("running.tmp"), b"123").unwrap();
+        fs::write(dir.join("model.gguf"), b"123").unwrap();
+
+        let active: HashSet<PathBuf> = [dir.join("running.tmp")].into_iter().collect();
+        let result = cleanup_temp_files_in(&dir, &active).unwrap();
+
+        assert_eq!(result.files_removed, 1);
+        assert_eq!(result.bytes_freed, 5);
+        assert!(!dir.join("stale.tmp").exists());
+        assert!(dir.join("running.tmp").exists());
+        assert!(dir.join("model.gguf").exists());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_stalled_download() {
+        let dir = TempDir::new("models");
+        let active = ActiveDownload::register(&dir.join("stalled.tmp"));
+        let mut stalled = futures::stream::pending::<Vec<u8>>();
+
+        let cancelled = active.cancelled.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            cancelled.store(true, Ordering::Relaxed);
+        });
+
+        let result = tokio::time::timeout(Duration::from_secs(5), active.next_chunk(&mut stalled)).await;
+        assert!(matches!(result, Ok(Err(LocalModelError::Cancelled))));
+    }
+
+    #[test]
+    fn test_collect_model_files() {
+        let dir = TempDir::new("models");
+        fs::write(dir.join("used.gguf"), b"1234").unwrap();
+        fs::write(dir.join("orphan.gguf"), b"12").unwrap();
+        fs::write(dir.join("partial.tmp"), b"123456").unwrap();
+
+        let mut associations = HashMap::new();
+        associations.insert("used.gguf".to_string(), vec!["llama3_8b".to_string()]);
+        let list = collect_model_files(&dir, &associations).unwrap();
+
+        let summary: Vec<(&str, u64, usize)> = list
+            .models
+            .iter()
+            .map(|m| (m.filename.as_str(), m.size, m.providers.len()))
+            .collect();
+        assert_eq!(summary, vec![("orphan.gguf", 2, 0), ("used.gguf", 4, 1)]);
+        assert_eq!(list.total_bytes, 6);
+        assert!(list.models[0].modified_at.is_some());
+    }
+}
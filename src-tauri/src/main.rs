@@ -5,15 +5,21 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use hex_sticky_note::ai_manager::AiManager;
+use hex_sticky_note::app_log;
+use hex_sticky_note::card_manager;
+use hex_sticky_note::card_watcher;
 use hex_sticky_note::commands::*;
+use hex_sticky_note::keyring_store::KeyringStore;
 use hex_sticky_note::local_inference;
+use hex_sticky_note::local_model;
 use hex_sticky_note::settings_manager::SettingsManager;
+use hex_sticky_note::window_state::WindowState;
 use std::sync::Arc;
-use tauri::Manager;
+use tauri::{Emitter, Manager};
 
 fn main() {
-    // Initialize logging
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    // Initialize logging (stderr and the log file in the data directory)
+    app_log::init();
 
     log::info!("Starting HexStickyNote...");
 
@@ -28,6 +34,19 @@ fn main() {
     let settings = Arc::new(SettingsManager::new().expect("Failed to initialize settings"));
     log::info!("Settings manager initialized");
 
+    // Apply the saved log level (RUST_LOG wins when set)
+    app_log::apply_saved_level(settings.get_log_level().as_deref());
+
+    // Apply custom cards directory before cards are first loaded
+    card_manager::set_custom_cards_directory(settings.get_cards_directory());
+    card_manager::set_default_card_title(settings.get_default_card_title());
+    card_manager::set_store_frontmatter(settings.get_store_frontmatter());
+    card_manager::set_storage_limit(
+        settings.get_max_cards_storage_mb(),
+        settings.get_block_writes_over_storage_limit(),
+    );
+    KeyringStore::set_file_fallback_enabled(settings.get_keyring_file_fallback());
+
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .manage(AiManager::new(settings.clone()))
@@ -36,51 +55,176 @@ fn main() {
             // API Key Management
             save_api_key,
             delete_api_key,
+            list_api_key_profiles,
+            set_active_api_key_profile,
             get_providers,
+            get_provider_capabilities,
+            get_provider_overview,
             set_active_provider,
             get_active_provider,
+            check_provider_connectivity,
             // AI Streaming
             invoke_ai_stream,
+            preview_prompt,
+            continue_generation,
+            summarize_notes,
+            confirm_tool_call,
+            reject_tool_call,
+            invoke_ai_to_new_card,
+            get_usage_stats,
+            reset_usage_stats,
+            get_provider_latency_stats,
+            reset_ai_session,
+            get_request_queue_status,
             // Card Storage
             create_card,
             get_cards,
+            get_card,
+            get_changed_cards,
+            get_cards_sorted,
+            find_duplicate_cards,
             save_card,
+            flush_pending_saves,
+            set_card_pinned,
+            export_card_html,
+            save_attachment,
+            list_attachments,
+            delete_attachment,
+            set_card_color,
+            set_card_locked,
+            archive_card,
+            unarchive_card,
+            get_archived_cards,
+            get_card_raw,
+            save_card_raw,
+            duplicate_card,
+            merge_cards,
             delete_card,
+            bulk_delete_cards,
+            bulk_set_tag,
             reload_cards,
+            reconcile_cards,
+            diagnose_card_files,
+            repair_card_file,
+            import_markdown_folder,
+            set_cards_directory,
+            set_store_frontmatter,
+            estimate_tokens,
+            get_card_stats,
             // Settings
             get_all_settings,
             set_provider_model,
             set_local_model_config,
+            set_system_prompt,
+            set_stop_sequences,
+            set_response_language,
+            set_raw_prompt,
+            set_requests_per_minute,
             set_gpu_type,
+            detect_gpu_backends,
+            set_gpu_layers,
+            set_require_tool_confirmation,
+            set_default_card_title,
+            set_max_cards_storage_mb,
+            set_keyring_file_fallback,
+            set_http_proxy,
+            set_log_conversations,
+            get_conversation_log_path,
+            clear_conversation_log,
+            set_log_level,
+            get_log_file_path,
+            export_settings,
+            import_settings,
             get_recommended_models,
+            fetch_provider_models,
             // Local Models
             get_local_model_status,
+            list_local_models,
+            get_available_quantizations,
+            estimate_model_requirements,
             download_local_model,
+            test_local_model,
+            benchmark_local_model,
+            preload_local_model,
             delete_local_model,
+            reset_download_state,
+            cleanup_model_temp_files,
             // Window State
             load_window_state,
             save_main_window_position,
             save_orb_window_position,
+            save_main_window_size,
+            save_orb_window_size,
+            set_orb_always_on_top,
             // Application Control
             exit_app,
-            // Claude Desktop MCP
-            check_claude_mcp,
-            setup_claude_mcp,
-            remove_claude_mcp,
+            // MCP Integration
+            check_mcp,
+            setup_mcp,
+            remove_mcp,
+            list_mcp_backups,
+            restore_mcp_backup,
             // File System
             open_cards_directory,
+            open_card_file,
+            get_cards_directory_path,
+            get_models_directory_path,
+            get_settings_path,
         ])
         .setup(|app| {
-            // Route orb window to /orb page
-            if let Some(orb_window) = app.get_webview_window("orb") {
-                let _ = orb_window.eval("window.location.href = '/orb'");
-                log::info!("Orb window routed to /orb");
+            // Orb window loads /orb directly via its `url` in tauri.conf.json
+            if let Some(orb) = app.get_webview_window("orb") {
+                log::info!("Orb window ready");
+
+                // Restore the saved always-on-top choice
+                let state = WindowState::load().unwrap_or_default();
+                if let Err(e) = orb.set_always_on_top(state.orb_always_on_top) {
+                    log::warn!("Failed to set orb always-on-top: {}", e);
+                }
             } else {
                 log::warn!("Orb window not found during setup");
             }
 
+            // Card edits that failed to save are retried; let the UI know meanwhile
+            let handle = app.handle().clone();
+            card_manager::set_save_failed_handler(move |failure| {
+                handle.emit("save-failed", failure).ok();
+            });
+
+            // Reload cards live when their files are edited outside the app
+            if let Err(e) = card_watcher::start(app.handle()) {
+                log::warn!("Card file watcher not started: {}", e);
+            }
+
+            // Remove temp files from downloads interrupted in a previous session
+            match local_model::cleanup_temp_files() {
+                Ok(result) if result.files_removed > 0 => log::info!(
+                    "Cleaned up {} leftover model temp files ({} bytes)",
+                    result.files_removed,
+                    result.bytes_freed
+                ),
+                Ok(_) => {}
+                Err(e) => log::warn!("Model temp file cleanup failed: {}", e),
+            }
+
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("Error while running HexStickyNote");
+        .on_window_event(|window, event| {
+            // Closing the main window quits the app; save first like `exit_app` does
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                if window.label() == "main" {
+                    api.prevent_close();
+                    save_before_exit(window.app_handle());
+                    window.app_handle().exit(0);
+                }
+            }
+        })
+        .build(tauri::generate_context!())
+        .expect("Error while building HexStickyNote")
+        .run(|_app, event| {
+            // Write card edits still waiting for the autosave delay
+            if let tauri::RunEvent::Exit = event {
+                card_manager::flush_pending_saves();
+            }
+        });
 }
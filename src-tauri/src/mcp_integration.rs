@@ -0,0 +1,508 @@
+//! MCP Client Integration
+//!
+//! Manages the MCP configuration files of supported AI clients (Claude Desktop,
+//! Cursor, Windsurf) to register HexStickyNote's MCP server for AI tool integration.
+//! All clients share the same `mcpServers` config shape, only the file location differs.
+
+use directories::BaseDirs;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+/// Key of the HexStickyNote entry inside `mcpServers`
+const MCP_SERVER_KEY: &str = "hexstickynote";
+
+/// Backups kept per config file; older ones are deleted when a new one is made
+const MAX_CONFIG_BACKUPS: usize = 10;
+
+/// AI client whose MCP config can be managed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum McpTarget {
+    ClaudeDesktop,
+    Cursor,
+    Windsurf,
+}
+
+impl McpTarget {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            McpTarget::ClaudeDesktop => "claude_desktop",
+            McpTarget::Cursor => "cursor",
+            McpTarget::Windsurf => "windsurf",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            McpTarget::ClaudeDesktop => "Claude Desktop",
+            McpTarget::Cursor => "Cursor",
+            McpTarget::Windsurf => "Windsurf",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "claude_desktop" | "claude" => Ok(McpTarget::ClaudeDesktop),
+            "cursor" => Ok(McpTarget::Cursor),
+            "windsurf" => Ok(McpTarget::Windsurf),
+            _ => Err(format!("Unknown MCP target: {}", s)),
+        }
+    }
+
+    pub fn all() -> Vec<Self> {
+        vec![McpTarget::ClaudeDesktop, McpTarget::Cursor, McpTarget::Windsurf]
+    }
+
+    /// Get the MCP config file path for this client
+    pub fn config_path(&self) -> Result<PathBuf, String> {
+        match self {
+            McpTarget::ClaudeDesktop => get_claude_config_path(),
+            McpTarget::Cursor => Ok(get_home_dir()?.join(".cursor").join("mcp.json")),
+            McpTarget::Windsurf => Ok(get_home_dir()?
+                .join(".codeium")
+                .join("windsurf")
+                .join("mcp_config.json")),
+        }
+    }
+}
+
+/// Get the current user's home directory
+fn get_home_dir() -> Result<PathBuf, String> {
+    BaseDirs::new()
+        .map(|dirs| dirs.home_dir().to_path_buf())
+        .ok_or_else(|| "Failed to determine home directory".to_string())
+}
+
+/// Get the Claude Desktop config file path
+///
+/// - Windows: `%APPDATA%\Claude\claude_desktop_config.json`
+/// - macOS: `~/Library/Application Support/Claude/claude_desktop_config.json`
+/// - Linux: `$XDG_CONFIG_HOME/Claude/claude_desktop_config.json` (defaults to `~/.config`)
+fn get_claude_config_path() -> Result<PathBuf, String> {
+    claude_config_path_for(
+        std::env::consts::OS,
+        std::env::var("APPDATA").ok().as_deref(),
+        std::env::var("XDG_CONFIG_HOME").ok().as_deref(),
+        get_home_dir,
+    )
+}
+
+/// Claude Desktop's config file on `os` (as in `std::env::consts::OS`), given that
+/// OS's APPDATA / XDG_CONFIG_HOME values and home directory
+fn claude_config_path_for(
+    os: &str,
+    app_data: Option<&str>,
+    xdg_config_home: Option<&str>,
+    home_dir: impl FnOnce() -> Result<PathBuf, String>,
+) -> Result<PathBuf, String> {
+    let config_dir = match os {
+        "windows" => PathBuf::from(app_data.ok_or("APPDATA environment variable not set")?),
+        "macos" => home_dir()?.join("Library").join("Application Support"),
+        _ => match xdg_config_home {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => home_dir()?.join(".config"),
+        },
+    };
+
+    Ok(config_dir.join("Claude").join("claude_desktop_config.json"))
+}
+
+/// Get the path to the bundled MCP server
+fn get_mcp_server_path(app: &tauri::AppHandle) -> Result<String, String> {
+    use tauri::Manager;
+    let resource_path = app
+        .path()
+        .resource_dir()
+        .map_err(|e| format!("Failed to get resource dir: {}", e))?
+        .join("resources")
+        .join("hexstickynote-mcp.mjs");
+
+    // Convert to normal Windows path (remove UNC prefix if present)
+    let path_str = resource_path.to_string_lossy().to_string();
+    let normalized = if cfg!(target_os = "windows") && path_str.starts_with(r"\\?\") {
+        path_str[4..].to_string()
+    } else {
+        path_str
+    };
+
+    Ok(normalized)
+}
+
+/// Find the server version in the bundle's `new McpServer({ name: "hexstickynote", version: "..." })`
+fn parse_bundle_version(bundle: &str) -> Option<String> {
+    let name_pos = bundle.find(&format!("name: \"{}\"", MCP_SERVER_KEY))?;
+    let after_name = &bundle[name_pos..];
+    let window = &after_name[..after_name.len().min(200)];
+
+    let version_start = window.find("version: \"")? + "version: \"".len();
+    let version_len = window[version_start..].find('"')?;
+    Some(window[version_start..version_start + version_len].to_string())
+}
+
+/// Read the MCP server version from a sibling `package.json`, or from the bundle itself
+fn read_server_version(server_path: &Path) -> Option<String> {
+    let package_json = server_path.with_file_name("package.json");
+    if let Ok(content) = std::fs::read_to_string(&package_json) {
+        let version = serde_json::from_str::<Value>(&content)
+            .ok()
+            .and_then(|pkg| pkg["version"].as_str().map(str::to_string));
+        if version.is_some() {
+            return version;
+        }
+    }
+
+    let bundle = std::fs::read_to_string(server_path).ok()?;
+    parse_bundle_version(&bundle)
+}
+
+/// Build the `mcpServers.hexstickynote` entry shared by all clients
+fn build_server_entry(mcp_server_path: &str) -> Value {
+    json!({
+        "command": "node",
+        "args": [mcp_server_path]
+    })
+}
+
+/// Read a client config file, falling back to an empty object
+fn read_config(config_path: &Path) -> Result<Value, String> {
+    if !config_path.exists() {
+        return Ok(json!({}));
+    }
+
+    let content = std::fs::read_to_string(config_path)
+        .map_err(|e| format!("Failed to read config: {}", e))?;
+    Ok(serde_json::from_str(&content).unwrap_or(json!({})))
+}
+
+/// Write a client config file as pretty-printed JSON
+fn write_config(config_path: &Path, config: &Value) -> Result<(), String> {
+    let formatted = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+    std::fs::write(config_path, formatted)
+        .map_err(|e| format!("Failed to write config: {}", e))
+}
+
+/// A saved copy of a client config, taken before HexStickyNote changed it
+#[derive(Debug, Clone, Serialize)]
+pub struct McpBackup {
+    /// File name, e.g. `claude_desktop_config.json.bak-20250101-120000123`
+    pub name: String,
+    pub path: String,
+    pub size: u64,
+}
+
+/// File name prefix of a config file's backups: `<config file name>.bak-`
+fn backup_prefix(config_path: &Path) -> Result<String, String> {
+    config_path
+        .file_name()
+        .map(|name| format!("{}.bak-", name.to_string_lossy()))
+        .ok_or_else(|| format!("Invalid config path: {:?}", config_path))
+}
+
+/// Copy the config file to a timestamped backup next to it (nothing to do if it doesn't exist)
+/// When the newest backup already has the same content, that backup is returned
+/// instead. Only the newest `MAX_CONFIG_BACKUPS` backups are kept.
+fn backup_config(
+    config_path: &Path,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Option<PathBuf>, String> {
+    if !config_path.exists() {
+        return Ok(None);
+    }
+
+    let backups = list_backups_of(config_path)?;
+    if let Some(newest) = backups.first() {
+        let current = std::fs::read(config_path)
+            .map_err(|e| format!("Failed to read config: {}", e))?;
+        if std::fs::read(&newest.path).is_ok_and(|saved| saved == current) {
+            return Ok(Some(PathBuf::from(&newest.path)));
+        }
+    }
+
+    let backup_path = config_path.with_file_name(format!(
+        "{}{}",
+        backup_prefix(config_path)?,
+        now.format("%Y%m%d-%H%M%S%3f")
+    ));
+    std::fs::copy(config_path, &backup_path)
+        .map_err(|e| format!("Failed to back up config: {}", e))?;
+
+    log::info!("Backed up {:?} to {:?}", config_path, backup_path);
+
+    // `backups` is newest first and doesn't include the new one
+    for old in backups.iter().skip(MAX_CONFIG_BACKUPS - 1) {
+        if let Err(e) = std::fs::remove_file(&old.path) {
+            log::warn!("Failed to remove old config backup {}: {}", old.name, e);
+        }
+    }
+
+    Ok(Some(backup_path))
+}
+
+/// Backups of a config file, newest first
+fn list_backups_of(config_path: &Path) -> Result<Vec<McpBackup>, String> {
+    let prefix = backup_prefix(config_path)?;
+    let Some(dir) = config_path.parent().filter(|dir| dir.exists()) else {
+        return Ok(Vec::new());
+    };
+
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read config dir: {}", e))?;
+    let mut backups: Vec<McpBackup> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            Some(McpBackup {
+                name: entry.file_name().to_string_lossy().to_string(),
+                path: entry.path().to_string_lossy().to_string(),
+                size: metadata.len(),
+            })
+        })
+        .collect();
+
+    // The timestamp suffix sorts chronologically
+    backups.sort_by(|a, b| b.name.cmp(&a.name));
+    Ok(backups)
+}
+
+/// Replace the config file with one of its backups
+/// The current config is backed up first, so a restore can itself be undone.
+fn restore_backup_of(config_path: &Path, backup_name: &str) -> Result<(), String> {
+    let backup = list_backups_of(config_path)?
+        .into_iter()
+        .find(|b| b.name == backup_name)
+        .ok_or_else(|| format!("Backup not found: {}", backup_name))?;
+
+    backup_config(config_path, chrono::Utc::now())?;
+    std::fs::copy(&backup.path, config_path)
+        .map_err(|e| format!("Failed to restore config: {}", e))?;
+
+    log::info!("Restored {:?} from {}", config_path, backup_name);
+    Ok(())
+}
+
+/// List the backups of a client's config, newest first
+pub fn list_backups(target: McpTarget) -> Result<Vec<McpBackup>, String> {
+    list_backups_of(&target.config_path()?)
+}
+
+/// Restore a client's config from a backup listed by `list_backups`
+pub fn restore_backup(target: McpTarget, backup_name: &str) -> Result<(), String> {
+    restore_backup_of(&target.config_path()?, backup_name)?;
+    log::info!("{} MCP config restored from backup", target.display_name());
+    Ok(())
+}
+
+/// Status of the MCP integration for a single client
+#[derive(Debug, Serialize)]
+pub struct McpStatus {
+    /// The client this status refers to
+    pub target: McpTarget,
+    /// Whether the client config directory exists
+    pub client_installed: bool,
+    /// Whether HexStickyNote MCP is configured
+    pub mcp_configured: bool,
+    /// Path to the client's MCP config file
+    pub config_path: String,
+    /// Path to the MCP server bundle
+    pub mcp_server_path: String,
+    /// Whether the MCP server bundle exists at `mcp_server_path`
+    pub server_file_exists: bool,
+    /// Version of the bundled MCP server, if it could be read
+    pub server_version: Option<String>,
+}
+
+/// Check if the client is installed and MCP is configured
+pub fn check_status(app: &tauri::AppHandle, target: McpTarget) -> Result<McpStatus, String> {
+    let config_path = target.config_path()?;
+    let client_installed = config_path.parent().map_or(false, |p| p.exists());
+
+    let mcp_server_path = get_mcp_server_path(app).unwrap_or_default();
+    let server_path = Path::new(&mcp_server_path);
+    let server_file_exists = !mcp_server_path.is_empty() && server_path.is_file();
+    let server_version = if server_file_exists {
+        read_server_version(server_path)
+    } else {
+        None
+    };
+
+    let config = read_config(&config_path)?;
+    let mcp_configured = config
+        .get("mcpServers")
+        .and_then(|s| s.get(MCP_SERVER_KEY))
+        .is_some();
+
+    Ok(McpStatus {
+        target,
+        client_installed,
+        mcp_configured,
+        config_path: config_path.to_string_lossy().to_string(),
+        mcp_server_path,
+        server_file_exists,
+        server_version,
+    })
+}
+
+/// Add HexStickyNote MCP to the client config
+/// An existing config is backed up first (see `list_backups`).
+pub fn setup(app: &tauri::AppHandle, target: McpTarget) -> Result<(), String> {
+    let config_path = target.config_path()?;
+    let mcp_server_path = get_mcp_server_path(app)?;
+
+    // Ensure client config directory exists
+    if let Some(parent) = config_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            format!("Failed to create {} config dir: {}", target.display_name(), e)
+        })?;
+    }
+
+    // Keep a copy of a hand-written config before touching it
+    backup_config(&config_path, chrono::Utc::now())?;
+
+    // Read existing config or start fresh
+    let mut config = read_config(&config_path)?;
+
+    // Ensure mcpServers object exists
+    if config.get("mcpServers").is_none() {
+        config["mcpServers"] = json!({});
+    }
+
+    // Add/update hexstickynote entry
+    config["mcpServers"][MCP_SERVER_KEY] = build_server_entry(&mcp_server_path);
+
+    write_config(&config_path, &config)?;
+
+    log::info!("{} MCP configured at {:?}", target.display_name(), config_path);
+    Ok(())
+}
+
+/// Remove HexStickyNote MCP from the client config
+pub fn remove(target: McpTarget) -> Result<(), String> {
+    let config_path = target.config_path()?;
+
+    if !config_path.exists() {
+        return Ok(());
+    }
+
+    backup_config(&config_path, chrono::Utc::now())?;
+    let mut config = read_config(&config_path)?;
+
+    // Remove hexstickynote entry
+    if let Some(servers) = config.get_mut("mcpServers").and_then(|s| s.as_object_mut()) {
+        servers.remove(MCP_SERVER_KEY);
+    }
+
+    write_config(&config_path, &config)?;
+
+    log::info!("{} MCP removed", target.display_name());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_claude_config_path_per_os() {
+        let home = || -> Result<PathBuf, String> { Ok(PathBuf::from("/home/user")) };
+
+        assert_eq!(
+            claude_config_path_for("windows", Some(r"C:\Users\user\AppData\Roaming"), None, home).unwrap(),
+            PathBuf::from(r"C:\Users\user\AppData\Roaming").join("Claude").join("claude_desktop_config.json")
+        );
+        assert!(claude_config_path_for("windows", None, None, home).is_err());
+
+        assert_eq!(
+            claude_config_path_for("macos", None, None, || Ok(PathBuf::from("/Users/user"))).unwrap(),
+            Path::new("/Users/user/Library/Application Support/Claude/claude_desktop_config.json")
+        );
+
+        assert_eq!(
+            claude_config_path_for("linux", None, None, home).unwrap(),
+            Path::new("/home/user/.config/Claude/claude_desktop_config.json")
+        );
+        assert_eq!(
+            claude_config_path_for("linux", None, Some("/xdg"), home).unwrap(),
+            Path::new("/xdg/Claude/claude_desktop_config.json")
+        );
+        assert_eq!(
+            claude_config_path_for("linux", None, Some(""), home).unwrap(),
+            Path::new("/home/user/.config/Claude/claude_desktop_config.json")
+        );
+    }
+
+    #[test]
+    fn test_parse_bundle_version() {
+        let bundle = "var server = new McpServer({\n  name: \"hexstickynote\",\n  version: \"0.1.0\"\n});";
+        assert_eq!(parse_bundle_version(bundle), Some("0.1.0".to_string()));
+        assert_eq!(parse_bundle_version("version: \"v4\""), None);
+    }
+
+    #[test]
+    fn test_backup_and_restore_config() {
+        let dir = TempDir::new("mcp");
+        let config_path = dir.join("claude_desktop_config.json");
+
+        // Nothing to back up yet
+        assert!(backup_config(&config_path, chrono::Utc::now()).unwrap().is_none());
+
+        std::fs::write(&config_path, r#"{"hand":"crafted"}"#).unwrap();
+        let first = chrono::DateTime::parse_from_rfc3339("2025-01-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let backup = backup_config(&config_path, first).unwrap().unwrap();
+        assert!(backup.ends_with("claude_desktop_config.json.bak-20250101-120000000"));
+
+        std::fs::write(&config_path, r#"{"mcpServers":{}}"#).unwrap();
+        backup_config(&config_path, first + chrono::Duration::seconds(1)).unwrap();
+
+        let names: Vec<String> = list_backups_of(&config_path).unwrap().into_iter().map(|b| b.name).collect();
+        assert_eq!(
+            names,
+            vec![
+                "claude_desktop_config.json.bak-20250101-120001000",
+                "claude_desktop_config.json.bak-20250101-120000000",
+            ]
+        );
+
+        // The current config matches the newest backup, so restoring adds no new one
+        restore_backup_of(&config_path, &names[1]).unwrap();
+        assert_eq!(std::fs::read_to_string(&config_path).unwrap(), r#"{"hand":"crafted"}"#);
+        assert!(restore_backup_of(&config_path, "../elsewhere.json").is_err());
+        assert_eq!(list_backups_of(&config_path).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_backup_config_keeps_newest() {
+        let dir = TempDir::new("mcp-prune");
+        let config_path = dir.join("config.json");
+        let start = chrono::DateTime::parse_from_rfc3339("2025-01-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+
+        for i in 0..(MAX_CONFIG_BACKUPS as i64 + 2) {
+            std::fs::write(&config_path, format!(r#"{{"version":{}}}"#, i)).unwrap();
+            backup_config(&config_path, start + chrono::Duration::seconds(i)).unwrap();
+        }
+        let backups = list_backups_of(&config_path).unwrap();
+        assert_eq!(backups.len(), MAX_CONFIG_BACKUPS);
+        assert!(backups[0].name.ends_with("120011000"));
+        assert!(backups[MAX_CONFIG_BACKUPS - 1].name.ends_with("120002000"));
+
+        // Unchanged content reuses the newest backup
+        let again = backup_config(&config_path, start + chrono::Duration::minutes(5)).unwrap().unwrap();
+        assert!(again.ends_with(&backups[0].name));
+        assert_eq!(list_backups_of(&config_path).unwrap().len(), MAX_CONFIG_BACKUPS);
+    }
+
+    #[test]
+    fn test_target_roundtrip() {
+        for target in McpTarget::all() {
+            let parsed = McpTarget::from_str(target.as_str()).unwrap();
+            assert_eq!(target, parsed);
+        }
+    }
+}
@@ -3,12 +3,13 @@
 //! Manages user preferences including AI model selections and local model configurations.
 //! Settings are stored in a JSON file separate from API keys (which use keyring).
 
-use crate::keyring_store::{AiProvider, GpuType};
+use crate::keyring_store::{AiProvider, GpuType, DEFAULT_PROFILE};
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::RwLock;
 use thiserror::Error;
 
@@ -22,8 +23,14 @@ pub enum SettingsError {
     WriteError(String),
     #[error("Failed to parse settings: {0}")]
     ParseError(String),
+    #[error("Invalid proxy URL: {0}")]
+    InvalidProxy(String),
 }
 
+/// Current settings file schema version
+/// Bump this whenever `AppSettings` changes in a way that old files need migrating.
+pub const SETTINGS_SCHEMA_VERSION: u32 = 1;
+
 /// Configuration for a cloud AI provider
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProviderConfig {
@@ -31,6 +38,15 @@ pub struct ProviderConfig {
     pub model: String,
     /// Custom model name if user wants to use a different model
     pub custom_model: Option<String>,
+    /// Named API key profile to use (None = default profile)
+    #[serde(default)]
+    pub active_profile: Option<String>,
+    /// Custom system prompt (None = built-in default)
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// Outbound request limit; requests beyond it wait in a queue (None = unlimited)
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
 }
 
 impl Default for ProviderConfig {
@@ -38,6 +54,9 @@ impl Default for ProviderConfig {
         Self {
             model: String::new(),
             custom_model: None,
+            active_profile: None,
+            system_prompt: None,
+            requests_per_minute: None,
         }
     }
 }
@@ -51,6 +70,19 @@ pub struct LocalModelConfig {
     pub filename: String,
     /// Custom download URL (overrides repo/filename if set)
     pub custom_url: Option<String>,
+    /// Custom system prompt (None = built-in default for the model)
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// Sequences that end generation when the output ends with them (None = built-in defaults)
+    #[serde(default)]
+    pub stop_sequences: Option<Vec<String>>,
+    /// Language of the model's instructions, "fi" or "en" (None = detect from the note)
+    #[serde(default)]
+    pub response_language: Option<String>,
+    /// Pass the context and prompt through verbatim instead of the instruct template
+    /// (for base models or models with a different chat template)
+    #[serde(default)]
+    pub raw_prompt: bool,
 }
 
 impl Default for LocalModelConfig {
@@ -59,6 +91,10 @@ impl Default for LocalModelConfig {
             repo: String::new(),
             filename: String::new(),
             custom_url: None,
+            system_prompt: None,
+            stop_sequences: None,
+            response_language: None,
+            raw_prompt: false,
         }
     }
 }
@@ -66,7 +102,10 @@ impl Default for LocalModelConfig {
 /// Application settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppSettings {
-    /// Cloud provider configurations (openai, anthropic, google)
+    /// Settings file schema version (0 = written before versioning existed)
+    #[serde(default)]
+    pub schema_version: u32,
+    /// Cloud provider configurations (openai, anthropic, google, groq)
     #[serde(default)]
     pub providers: HashMap<String, ProviderConfig>,
     /// Local model configurations (poro2_8b, llama3_8b)
@@ -75,12 +114,52 @@ pub struct AppSettings {
     /// GPU acceleration type (cpu, vulkan, cuda, rocm)
     #[serde(default = "default_gpu_type")]
     pub gpu_type: GpuType,
+    /// Number of model layers to offload to the GPU (-1 = all, None = default of 32)
+    #[serde(default)]
+    pub n_gpu_layers: Option<i32>,
+    /// Custom cards storage directory (empty = default data directory)
+    #[serde(default)]
+    pub cards_directory: Option<String>,
+    /// Ask the user before executing AI tool calls that modify notes
+    #[serde(default)]
+    pub require_tool_confirmation: bool,
+    /// Store API keys in an encrypted file when the OS keyring is unavailable
+    #[serde(default)]
+    pub keyring_file_fallback: bool,
+    /// Append every prompt and response to conversation_log.jsonl (plaintext, for debugging)
+    #[serde(default)]
+    pub log_conversations: bool,
+    /// Title for cards without a heading or text (None = "Note")
+    #[serde(default)]
+    pub default_card_title: Option<String>,
+    /// Proxy for cloud API calls and model downloads (e.g. "http://proxy.corp:8080")
+    #[serde(default)]
+    pub http_proxy: Option<String>,
+    /// Log level applied at startup unless RUST_LOG is set (None = info)
+    #[serde(default)]
+    pub log_level: Option<String>,
+    /// Cap on the cards directory size in MB, attachments included (None = unlimited)
+    #[serde(default)]
+    pub max_cards_storage_mb: Option<u64>,
+    /// Refuse writes over the storage cap instead of only warning
+    #[serde(default)]
+    pub block_writes_over_storage_limit: bool,
+    /// Write YAML front matter into card files (off = metadata in cards/.index.json)
+    #[serde(default = "default_store_frontmatter")]
+    pub store_frontmatter: bool,
+    /// Keys this version doesn't know about, kept so they survive a rewrite
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 fn default_gpu_type() -> GpuType {
     GpuType::Cpu
 }
 
+fn default_store_frontmatter() -> bool {
+    true
+}
+
 impl Default for AppSettings {
     fn default() -> Self {
         let mut providers = HashMap::new();
@@ -92,6 +171,9 @@ impl Default for AppSettings {
             ProviderConfig {
                 model: "gpt-5.2-codex".to_string(),
                 custom_model: None,
+                active_profile: None,
+                system_prompt: None,
+                requests_per_minute: None,
             },
         );
         providers.insert(
@@ -99,6 +181,9 @@ impl Default for AppSettings {
             ProviderConfig {
                 model: "claude-sonnet-4-6".to_string(),
                 custom_model: None,
+                active_profile: None,
+                system_prompt: None,
+                requests_per_minute: None,
             },
         );
         providers.insert(
@@ -106,6 +191,50 @@ impl Default for AppSettings {
             ProviderConfig {
                 model: "gemini-3.1-pro-latest".to_string(),
                 custom_model: None,
+                active_profile: None,
+                system_prompt: None,
+                requests_per_minute: None,
+            },
+        );
+
+        providers.insert(
+            "groq".to_string(),
+            ProviderConfig {
+                model: "llama-3.3-70b-versatile".to_string(),
+                custom_model: None,
+                active_profile: None,
+                system_prompt: None,
+                requests_per_minute: None,
+            },
+        );
+        providers.insert(
+            "grok".to_string(),
+            ProviderConfig {
+                model: "grok-2-latest".to_string(),
+                custom_model: None,
+                active_profile: None,
+                system_prompt: None,
+                requests_per_minute: None,
+            },
+        );
+        providers.insert(
+            "mistral".to_string(),
+            ProviderConfig {
+                model: "mistral-large-latest".to_string(),
+                custom_model: None,
+                active_profile: None,
+                system_prompt: None,
+                requests_per_minute: None,
+            },
+        );
+        providers.insert(
+            "cohere".to_string(),
+            ProviderConfig {
+                model: "command-r-plus".to_string(),
+                custom_model: None,
+                active_profile: None,
+                system_prompt: None,
+                requests_per_minute: None,
             },
         );
 
@@ -116,6 +245,10 @@ impl Default for AppSettings {
                 repo: "mradermacher/Llama-Poro-2-8B-Instruct-GGUF".to_string(),
                 filename: "Llama-Poro-2-8B-Instruct.Q4_K_M.gguf".to_string(),
                 custom_url: None,
+                system_prompt: None,
+                stop_sequences: None,
+                response_language: None,
+                raw_prompt: false,
             },
         );
         local_models.insert(
@@ -124,13 +257,30 @@ impl Default for AppSettings {
                 repo: "mradermacher/Meta-Llama-3.1-8B-Instruct-GGUF".to_string(),
                 filename: "Meta-Llama-3.1-8B-Instruct.Q4_K_M.gguf".to_string(),
                 custom_url: None,
+                system_prompt: None,
+                stop_sequences: None,
+                response_language: None,
+                raw_prompt: false,
             },
         );
 
         Self {
+            schema_version: SETTINGS_SCHEMA_VERSION,
             providers,
             local_models,
             gpu_type: GpuType::Cpu,
+            n_gpu_layers: None,
+            cards_directory: None,
+            require_tool_confirmation: false,
+            keyring_file_fallback: false,
+            log_conversations: false,
+            default_card_title: None,
+            http_proxy: None,
+            log_level: None,
+            max_cards_storage_mb: None,
+            block_writes_over_storage_limit: false,
+            store_frontmatter: true,
+            extra: Map::new(),
         }
     }
 }
@@ -153,6 +303,17 @@ impl SettingsManager {
         })
     }
 
+    /// Settings manager backed by the given file instead of the config dir
+    #[cfg(test)]
+    pub fn at_path(settings_path: PathBuf) -> Result<Self, SettingsError> {
+        let settings = Self::load_from_disk(&settings_path)?;
+
+        Ok(Self {
+            settings: RwLock::new(settings),
+            settings_path,
+        })
+    }
+
     /// Get the path to the settings file
     fn get_settings_path() -> Result<PathBuf, SettingsError> {
         let proj_dirs = ProjectDirs::from("com", "HexStickyNote", "HexStickyNote")
@@ -180,17 +341,46 @@ impl SettingsManager {
         let contents = fs::read_to_string(path)
             .map_err(|e| SettingsError::ReadError(format!("Failed to read settings: {}", e)))?;
 
-        match serde_json::from_str(&contents) {
-            Ok(settings) => Ok(settings),
+        let raw: Value = match serde_json::from_str(&contents) {
+            Ok(raw) => raw,
             Err(e) => {
-                log::warn!("Failed to parse settings, using defaults: {}", e);
-                Ok(AppSettings::default())
+                log::warn!("Settings file is not valid JSON, using defaults: {}", e);
+                Self::backup(path);
+                let defaults = AppSettings::default();
+                Self::save_to_disk(path, &defaults)?;
+                return Ok(defaults);
+            }
+        };
+
+        match serde_json::from_value::<AppSettings>(raw.clone()) {
+            Ok(settings) if settings.schema_version >= SETTINGS_SCHEMA_VERSION => {
+                return Ok(settings);
             }
+            Ok(settings) => log::info!(
+                "Migrating settings from schema version {} to {}",
+                settings.schema_version,
+                SETTINGS_SCHEMA_VERSION
+            ),
+            Err(e) => log::warn!("Failed to parse settings, merging known fields: {}", e),
+        }
+
+        Self::backup(path);
+        let migrated = migrate_settings(&raw);
+        Self::save_to_disk(path, &migrated)?;
+        Ok(migrated)
+    }
+
+    /// Copy the settings file to `settings.json.bak` before it gets rewritten
+    fn backup(path: &Path) {
+        let backup_path = path.with_extension("json.bak");
+        match fs::copy(path, &backup_path) {
+            Ok(_) => log::info!("Backed up settings to {:?}", backup_path),
+            Err(e) => log::warn!("Failed to back up settings: {}", e),
         }
     }
 
     /// Save settings to disk
-    fn save_to_disk(path: &PathBuf, settings: &AppSettings) -> Result<(), SettingsError> {
+    fn save_to_disk(path: &Path, settings: &AppSettings) -> Result<(), SettingsError> {
         let json = serde_json::to_string_pretty(settings).map_err(|e| {
             SettingsError::WriteError(format!("Failed to serialize settings: {}", e))
         })?;
@@ -223,6 +413,10 @@ impl SettingsManager {
                 AiProvider::OpenAI => "gpt-5.2-codex".to_string(),
                 AiProvider::Anthropic => "claude-sonnet-4-6".to_string(),
                 AiProvider::Google => "gemini-3.1-pro-latest".to_string(),
+                AiProvider::Groq => "llama-3.3-70b-versatile".to_string(),
+                AiProvider::Grok => "grok-2-latest".to_string(),
+                AiProvider::Mistral => "mistral-large-latest".to_string(),
+                AiProvider::Cohere => "command-r-plus".to_string(),
                 _ => "unknown".to_string(),
             }
         }
@@ -254,6 +448,34 @@ impl SettingsManager {
         self.save()
     }
 
+    /// Get the active API key profile for a provider
+    pub fn get_active_profile(&self, provider: AiProvider) -> String {
+        let settings = self.settings.read().unwrap();
+        settings
+            .providers
+            .get(provider.as_str())
+            .and_then(|config| config.active_profile.clone())
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+    }
+
+    /// Set the active API key profile for a provider
+    pub fn set_active_profile(&self, provider: AiProvider, profile: String) -> Result<(), SettingsError> {
+        let mut settings = self.settings.write().unwrap();
+        let config = settings
+            .providers
+            .entry(provider.as_str().to_string())
+            .or_insert_with(ProviderConfig::default);
+
+        config.active_profile = if profile == DEFAULT_PROFILE {
+            None
+        } else {
+            Some(profile)
+        };
+
+        drop(settings);
+        self.save()
+    }
+
     /// Get local model configuration
     pub fn get_local_model_config(&self, provider: AiProvider) -> Option<LocalModelConfig> {
         let settings = self.settings.read().unwrap();
@@ -274,6 +496,154 @@ impl SettingsManager {
         self.save()
     }
 
+    /// Get the custom system prompt for a provider (cloud or local), if set
+    pub fn get_system_prompt(&self, provider: AiProvider) -> Option<String> {
+        let settings = self.settings.read().unwrap();
+        let prompt = if provider.requires_api_key() {
+            settings
+                .providers
+                .get(provider.as_str())
+                .and_then(|config| config.system_prompt.clone())
+        } else {
+            settings
+                .local_models
+                .get(provider.as_str())
+                .and_then(|config| config.system_prompt.clone())
+        };
+        prompt.filter(|p| !p.trim().is_empty())
+    }
+
+    /// Set or clear the custom system prompt for a provider
+    pub fn set_system_prompt(
+        &self,
+        provider: AiProvider,
+        prompt: Option<String>,
+    ) -> Result<(), SettingsError> {
+        let prompt = prompt.filter(|p| !p.trim().is_empty());
+        let mut settings = self.settings.write().unwrap();
+        let key = provider.as_str().to_string();
+
+        if provider.requires_api_key() {
+            settings
+                .providers
+                .entry(key)
+                .or_insert_with(ProviderConfig::default)
+                .system_prompt = prompt;
+        } else {
+            settings
+                .local_models
+                .entry(key)
+                .or_insert_with(LocalModelConfig::default)
+                .system_prompt = prompt;
+        }
+
+        drop(settings);
+        self.save()
+    }
+
+    /// Get the custom stop sequences for a local provider (None = built-in defaults)
+    pub fn get_stop_sequences(&self, provider: AiProvider) -> Option<Vec<String>> {
+        let settings = self.settings.read().unwrap();
+        settings
+            .local_models
+            .get(provider.as_str())
+            .and_then(|config| config.stop_sequences.clone())
+    }
+
+    /// Set or clear the custom stop sequences for a local provider
+    pub fn set_stop_sequences(
+        &self,
+        provider: AiProvider,
+        sequences: Option<Vec<String>>,
+    ) -> Result<(), SettingsError> {
+        let sequences =
+            sequences.map(|seqs| seqs.into_iter().filter(|s| !s.is_empty()).collect());
+        let mut settings = self.settings.write().unwrap();
+        settings
+            .local_models
+            .entry(provider.as_str().to_string())
+            .or_insert_with(LocalModelConfig::default)
+            .stop_sequences = sequences;
+
+        drop(settings);
+        self.save()
+    }
+
+    /// Get the configured response language of a local provider (None = detect)
+    pub fn get_response_language(&self, provider: AiProvider) -> Option<String> {
+        let settings = self.settings.read().unwrap();
+        settings
+            .local_models
+            .get(provider.as_str())
+            .and_then(|config| config.response_language.clone())
+    }
+
+    /// Set or clear the response language of a local provider ("fi", "en" or None)
+    pub fn set_response_language(
+        &self,
+        provider: AiProvider,
+        language: Option<String>,
+    ) -> Result<(), SettingsError> {
+        let mut settings = self.settings.write().unwrap();
+        settings
+            .local_models
+            .entry(provider.as_str().to_string())
+            .or_insert_with(LocalModelConfig::default)
+            .response_language = language;
+
+        drop(settings);
+        self.save()
+    }
+
+    /// Whether a local provider gets the prompt without the instruct template
+    pub fn get_raw_prompt(&self, provider: AiProvider) -> bool {
+        let settings = self.settings.read().unwrap();
+        settings
+            .local_models
+            .get(provider.as_str())
+            .is_some_and(|config| config.raw_prompt)
+    }
+
+    /// Turn raw prompting on or off for a local provider
+    pub fn set_raw_prompt(&self, provider: AiProvider, enabled: bool) -> Result<(), SettingsError> {
+        let mut settings = self.settings.write().unwrap();
+        settings
+            .local_models
+            .entry(provider.as_str().to_string())
+            .or_insert_with(LocalModelConfig::default)
+            .raw_prompt = enabled;
+
+        drop(settings);
+        self.save()
+    }
+
+    /// Get the request limit of a cloud provider (None = unlimited)
+    pub fn get_requests_per_minute(&self, provider: AiProvider) -> Option<u32> {
+        let settings = self.settings.read().unwrap();
+        settings
+            .providers
+            .get(provider.as_str())
+            .and_then(|config| config.requests_per_minute)
+            .filter(|&rpm| rpm > 0)
+    }
+
+    /// Set or clear the request limit of a cloud provider
+    pub fn set_requests_per_minute(
+        &self,
+        provider: AiProvider,
+        requests_per_minute: Option<u32>,
+    ) -> Result<(), SettingsError> {
+        let mut settings = self.settings.write().unwrap();
+        settings
+            .providers
+            .entry(provider.as_str().to_string())
+            .or_insert_with(ProviderConfig::default)
+            .requests_per_minute = requests_per_minute.filter(|&rpm| rpm > 0);
+
+        drop(settings);
+        self.save()
+    }
+
     /// Get current GPU type
     pub fn get_gpu_type(&self) -> GpuType {
         let settings = self.settings.read().unwrap();
@@ -288,14 +658,335 @@ impl SettingsManager {
         self.save()
     }
 
+    /// Get the configured GPU layer count (None = default)
+    pub fn get_gpu_layers(&self) -> Option<i32> {
+        let settings = self.settings.read().unwrap();
+        settings.n_gpu_layers
+    }
+
+    /// Set the GPU layer count (-1 = all, None = default)
+    pub fn set_gpu_layers(&self, layers: Option<i32>) -> Result<(), SettingsError> {
+        let mut settings = self.settings.write().unwrap();
+        settings.n_gpu_layers = layers;
+        drop(settings);
+        self.save()
+    }
+
+    /// Path of the settings file
+    pub fn settings_path(&self) -> &Path {
+        &self.settings_path
+    }
+
+    /// Get the custom cards directory, if one is set
+    pub fn get_cards_directory(&self) -> Option<PathBuf> {
+        let settings = self.settings.read().unwrap();
+        settings
+            .cards_directory
+            .as_ref()
+            .filter(|dir| !dir.trim().is_empty())
+            .map(PathBuf::from)
+    }
+
+    /// Set the custom cards directory (None = default data directory)
+    pub fn set_cards_directory(&self, dir: Option<String>) -> Result<(), SettingsError> {
+        let mut settings = self.settings.write().unwrap();
+        settings.cards_directory = dir;
+        drop(settings);
+        self.save()
+    }
+
+    /// Get the title for cards without a heading or text, if one is set
+    pub fn get_default_card_title(&self) -> Option<String> {
+        let settings = self.settings.read().unwrap();
+        settings
+            .default_card_title
+            .clone()
+            .filter(|title| !title.trim().is_empty())
+    }
+
+    /// Set the title for cards without a heading or text (None = "Note")
+    pub fn set_default_card_title(&self, title: Option<String>) -> Result<(), SettingsError> {
+        let mut settings = self.settings.write().unwrap();
+        settings.default_card_title = title;
+        drop(settings);
+        self.save()
+    }
+
+    /// Get the cards directory size cap in MB, if one is set
+    pub fn get_max_cards_storage_mb(&self) -> Option<u64> {
+        let settings = self.settings.read().unwrap();
+        settings.max_cards_storage_mb.filter(|mb| *mb > 0)
+    }
+
+    /// Whether writes over the storage cap are refused (otherwise only warned about)
+    pub fn get_block_writes_over_storage_limit(&self) -> bool {
+        let settings = self.settings.read().unwrap();
+        settings.block_writes_over_storage_limit
+    }
+
+    /// Set the cards directory size cap (None = unlimited) and what happens when it's reached
+    pub fn set_max_cards_storage(&self, max_mb: Option<u64>, block_writes: bool) -> Result<(), SettingsError> {
+        let mut settings = self.settings.write().unwrap();
+        settings.max_cards_storage_mb = max_mb;
+        settings.block_writes_over_storage_limit = block_writes;
+        drop(settings);
+        self.save()
+    }
+
+    /// Whether card files are written with YAML front matter
+    pub fn get_store_frontmatter(&self) -> bool {
+        let settings = self.settings.read().unwrap();
+        settings.store_frontmatter
+    }
+
+    /// Set whether card files are written with YAML front matter
+    pub fn set_store_frontmatter(&self, enabled: bool) -> Result<(), SettingsError> {
+        let mut settings = self.settings.write().unwrap();
+        settings.store_frontmatter = enabled;
+        drop(settings);
+        self.save()
+    }
+
+    /// Get the configured HTTP proxy, if any
+    pub fn get_http_proxy(&self) -> Option<String> {
+        let settings = self.settings.read().unwrap();
+        settings
+            .http_proxy
+            .clone()
+            .filter(|proxy| !proxy.trim().is_empty())
+    }
+
+    /// Set the HTTP proxy (None = connect directly); the URL is validated first
+    pub fn set_http_proxy(&self, proxy: Option<String>) -> Result<(), SettingsError> {
+        if let Some(ref url) = proxy {
+            reqwest::Proxy::all(url).map_err(|e| SettingsError::InvalidProxy(format!("{}: {}", url, e)))?;
+        }
+
+        let mut settings = self.settings.write().unwrap();
+        settings.http_proxy = proxy;
+        drop(settings);
+        self.save()
+    }
+
+    /// Build an HTTP client that uses the configured proxy
+    /// An invalid saved proxy is logged and ignored rather than breaking every request.
+    pub fn http_client(&self) -> reqwest::Client {
+        let mut builder = reqwest::Client::builder();
+        if let Some(url) = self.get_http_proxy() {
+            match reqwest::Proxy::all(&url) {
+                Ok(proxy) => builder = builder.proxy(proxy),
+                Err(e) => log::warn!("Ignoring invalid proxy {}: {}", url, e),
+            }
+        }
+        builder.build().unwrap_or_else(|e| {
+            log::warn!("Failed to build HTTP client, using defaults: {}", e);
+            reqwest::Client::new()
+        })
+    }
+
+    /// Get the saved log level, if one is set
+    pub fn get_log_level(&self) -> Option<String> {
+        let settings = self.settings.read().unwrap();
+        settings.log_level.clone()
+    }
+
+    /// Save the log level to apply on the next start
+    pub fn set_log_level(&self, level: Option<String>) -> Result<(), SettingsError> {
+        let mut settings = self.settings.write().unwrap();
+        settings.log_level = level;
+        drop(settings);
+        self.save()
+    }
+
+    /// Check whether AI tool calls need user confirmation
+    pub fn get_require_tool_confirmation(&self) -> bool {
+        let settings = self.settings.read().unwrap();
+        settings.require_tool_confirmation
+    }
+
+    /// Set whether AI tool calls need user confirmation
+    pub fn set_require_tool_confirmation(&self, required: bool) -> Result<(), SettingsError> {
+        let mut settings = self.settings.write().unwrap();
+        settings.require_tool_confirmation = required;
+        drop(settings);
+        self.save()
+    }
+
+    /// Check whether the encrypted key file fallback is enabled
+    pub fn get_keyring_file_fallback(&self) -> bool {
+        let settings = self.settings.read().unwrap();
+        settings.keyring_file_fallback
+    }
+
+    /// Enable or disable the encrypted key file fallback
+    pub fn set_keyring_file_fallback(&self, enabled: bool) -> Result<(), SettingsError> {
+        let mut settings = self.settings.write().unwrap();
+        settings.keyring_file_fallback = enabled;
+        drop(settings);
+        self.save()
+    }
+
+    /// Check whether prompts and responses are written to the conversation log
+    pub fn get_log_conversations(&self) -> bool {
+        let settings = self.settings.read().unwrap();
+        settings.log_conversations
+    }
+
+    /// Enable or disable the conversation log
+    pub fn set_log_conversations(&self, enabled: bool) -> Result<(), SettingsError> {
+        let mut settings = self.settings.write().unwrap();
+        settings.log_conversations = enabled;
+        drop(settings);
+        self.save()
+    }
+
+    /// Export the current settings to an arbitrary path
+    ///
+    /// API keys live in the keyring and are never part of `AppSettings`,
+    /// so the exported file only contains model/provider preferences.
+    pub fn export_to(&self, dest: &Path) -> Result<(), SettingsError> {
+        let settings = self.settings.read().unwrap().clone();
+        Self::save_to_disk(dest, &settings)
+    }
+
+    /// Import settings from an arbitrary path, replacing the live settings
+    ///
+    /// The file must deserialize as `AppSettings`; nothing is changed otherwise.
+    /// The cards directory is machine-specific and is kept as it is, as is the front
+    /// matter setting, which has to match the files in it.
+    pub fn import_from(&self, src: &Path) -> Result<AppSettings, SettingsError> {
+        let contents = fs::read_to_string(src)
+            .map_err(|e| SettingsError::ReadError(format!("Failed to read {:?}: {}", src, e)))?;
+        let mut imported: AppSettings = serde_json::from_str(&contents)
+            .map_err(|e| SettingsError::ParseError(format!("Invalid settings file: {}", e)))?;
+
+        let mut settings = self.settings.write().unwrap();
+        imported.schema_version = SETTINGS_SCHEMA_VERSION;
+        imported.cards_directory = settings.cards_directory.clone();
+        imported.store_frontmatter = settings.store_frontmatter;
+        *settings = imported.clone();
+        drop(settings);
+
+        self.save()?;
+        log::info!("Imported settings from {:?}", src);
+        Ok(imported)
+    }
+
     /// Get all settings (for frontend)
     pub fn get_all_settings(&self) -> AppSettings {
         self.settings.read().unwrap().clone()
     }
 }
 
+/// Build current settings from an older or partially invalid settings JSON
+///
+/// Starts from the defaults and takes over every top-level key from `old` that still
+/// deserializes. Object values that fail as a whole (e.g. `providers` with one broken
+/// entry) are merged entry by entry so the valid entries are kept.
+/// Unknown keys end up in `AppSettings::extra`.
+fn migrate_settings(old: &Value) -> AppSettings {
+    let defaults = AppSettings::default();
+    let Some(old) = old.as_object() else {
+        return defaults;
+    };
+
+    let mut merged = match serde_json::to_value(&defaults) {
+        Ok(Value::Object(map)) => map,
+        _ => return defaults,
+    };
+
+    let is_valid = |candidate: &Map<String, Value>| {
+        serde_json::from_value::<AppSettings>(Value::Object(candidate.clone())).is_ok()
+    };
+
+    for (key, value) in old {
+        if key == "schema_version" {
+            continue;
+        }
+
+        let mut candidate = merged.clone();
+        candidate.insert(key.clone(), value.clone());
+        if is_valid(&candidate) {
+            merged = candidate;
+            continue;
+        }
+
+        // Fall back to merging the entries of an object one by one
+        let (Some(old_entries), Some(Value::Object(base))) = (value.as_object(), merged.get(key))
+        else {
+            log::warn!("Dropping invalid setting '{}'", key);
+            continue;
+        };
+
+        let mut entries = base.clone();
+        for (entry_key, entry_value) in old_entries {
+            let mut with_entry = entries.clone();
+            with_entry.insert(entry_key.clone(), entry_value.clone());
+
+            let mut candidate = merged.clone();
+            candidate.insert(key.clone(), Value::Object(with_entry.clone()));
+            if is_valid(&candidate) {
+                entries = with_entry;
+            } else {
+                log::warn!("Dropping invalid setting '{}.{}'", key, entry_key);
+            }
+        }
+        merged.insert(key.clone(), Value::Object(entries));
+    }
+
+    let mut settings: AppSettings =
+        serde_json::from_value(Value::Object(merged)).unwrap_or(defaults);
+    settings.schema_version = SETTINGS_SCHEMA_VERSION;
+    settings
+}
+
 impl Default for SettingsManager {
     fn default() -> Self {
         Self::new().unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_migrate_keeps_valid_fields() {
+        let old = json!({
+            "providers": {
+                "openai": { "model": "gpt-4o", "custom_model": "my-model" }
+            },
+            "gpu_type": "vulkan",
+            "require_tool_confirmation": "not a bool",
+            "future_option": 42
+        });
+
+        let settings = migrate_settings(&old);
+        assert_eq!(settings.schema_version, SETTINGS_SCHEMA_VERSION);
+        assert_eq!(
+            settings.providers["openai"].custom_model.as_deref(),
+            Some("my-model")
+        );
+        assert_eq!(settings.gpu_type, GpuType::Vulkan);
+        assert!(!settings.require_tool_confirmation);
+        assert_eq!(settings.extra.get("future_option"), Some(&json!(42)));
+    }
+
+    #[test]
+    fn test_migrate_drops_only_broken_entries() {
+        let old = json!({
+            "local_models": {
+                "poro2_8b": { "repo": "me/custom-GGUF", "filename": "custom.gguf" },
+                "llama3_8b": { "repo": 7 }
+            }
+        });
+
+        let settings = migrate_settings(&old);
+        assert_eq!(settings.local_models["poro2_8b"].repo, "me/custom-GGUF");
+        assert_eq!(
+            settings.local_models["llama3_8b"].repo,
+            AppSettings::default().local_models["llama3_8b"].repo
+        );
+    }
+}
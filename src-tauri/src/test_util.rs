@@ -0,0 +1,32 @@
+//! Helpers shared by the unit tests
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A fresh directory under the system temp dir, removed when dropped (also when
+/// the test panics)
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Create `<temp dir>/hexstickynote-<label>-<uuid>`
+    pub fn new(label: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("hexstickynote-{}-{}", label, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&path).expect("Failed to create temp dir");
+        Self(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
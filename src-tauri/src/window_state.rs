@@ -1,11 +1,17 @@
 //! Window State Management
 //!
-//! Saves and loads window positions to maintain state across app restarts.
+//! Saves and loads window positions and sizes to maintain state across app restarts.
 
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
+use tauri::Manager;
+
+/// Default main window size (matches tauri.conf.json)
+const DEFAULT_MAIN_SIZE: (u32, u32) = (1200, 800);
+/// Default orb window size (matches tauri.conf.json)
+const DEFAULT_ORB_SIZE: (u32, u32) = (76, 76);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WindowPosition {
@@ -13,10 +19,100 @@ pub struct WindowPosition {
     pub y: i32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Rectangle of a connected monitor in physical pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MonitorBounds {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl MonitorBounds {
+    /// Returns true if the given window rectangle overlaps this monitor
+    fn intersects(&self, x: i32, y: i32, width: u32, height: u32) -> bool {
+        let (x, y) = (x as i64, y as i64);
+        let (mx, my) = (self.x as i64, self.y as i64);
+
+        x < mx + self.width as i64
+            && x + width as i64 > mx
+            && y < my + self.height as i64
+            && y + height as i64 > my
+    }
+}
+
+impl From<&tauri::Monitor> for MonitorBounds {
+    fn from(monitor: &tauri::Monitor) -> Self {
+        Self {
+            x: monitor.position().x,
+            y: monitor.position().y,
+            width: monitor.size().width,
+            height: monitor.size().height,
+        }
+    }
+}
+
+/// Reset a window position to the primary monitor if it is entirely off-screen
+/// Returns true if the position was changed.
+fn clamp_position(
+    position: &mut Option<WindowPosition>,
+    size: (u32, u32),
+    monitors: &[MonitorBounds],
+) -> bool {
+    let (Some(pos), Some(primary)) = (position.as_mut(), monitors.first()) else {
+        return false;
+    };
+
+    if monitors
+        .iter()
+        .any(|m| m.intersects(pos.x, pos.y, size.0, size.1))
+    {
+        return false;
+    }
+
+    log::info!(
+        "Saved window position ({}, {}) is off-screen, resetting to primary monitor",
+        pos.x,
+        pos.y
+    );
+    pos.x = primary.x;
+    pos.y = primary.y;
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WindowState {
     pub main_window: Option<WindowPosition>,
     pub orb_window: Option<WindowPosition>,
+    #[serde(default)]
+    pub main_window_size: Option<WindowSize>,
+    #[serde(default)]
+    pub orb_window_size: Option<WindowSize>,
+    /// Keep the orb above other windows (on by default, as in tauri.conf.json)
+    #[serde(default = "default_orb_always_on_top")]
+    pub orb_always_on_top: bool,
+}
+
+fn default_orb_always_on_top() -> bool {
+    true
+}
+
+impl Default for WindowState {
+    fn default() -> Self {
+        Self {
+            main_window: None,
+            orb_window: None,
+            main_window_size: None,
+            orb_window_size: None,
+            orb_always_on_top: default_orb_always_on_top(),
+        }
+    }
 }
 
 impl WindowState {
@@ -75,4 +171,124 @@ impl WindowState {
     pub fn set_orb_position(&mut self, x: i32, y: i32) {
         self.orb_window = Some(WindowPosition { x, y });
     }
+
+    /// Record where the main and orb windows currently are
+    /// Minimized windows are skipped; their reported position is not a real one.
+    pub fn capture(&mut self, app: &tauri::AppHandle) {
+        if let Some(main) = app.get_webview_window("main") {
+            if !main.is_minimized().unwrap_or(false) {
+                if let Ok(position) = main.outer_position() {
+                    self.set_main_position(position.x, position.y);
+                }
+                if let Ok(size) = main.inner_size() {
+                    self.set_main_size(size.width, size.height);
+                }
+            }
+        }
+
+        if let Some(orb) = app.get_webview_window("orb") {
+            if !orb.is_minimized().unwrap_or(false) {
+                if let Ok(position) = orb.outer_position() {
+                    self.set_orb_position(position.x, position.y);
+                }
+            }
+        }
+    }
+
+    /// Move windows that would be entirely off-screen back onto the primary monitor
+    /// The first entry of `monitors` is treated as the primary monitor.
+    /// Returns true if any position was changed.
+    pub fn clamp_to_visible(&mut self, monitors: &[MonitorBounds]) -> bool {
+        let main_size = self
+            .main_window_size
+            .as_ref()
+            .map_or(DEFAULT_MAIN_SIZE, |s| (s.width, s.height));
+        let orb_size = self
+            .orb_window_size
+            .as_ref()
+            .map_or(DEFAULT_ORB_SIZE, |s| (s.width, s.height));
+
+        let main_changed = clamp_position(&mut self.main_window, main_size, monitors);
+        let orb_changed = clamp_position(&mut self.orb_window, orb_size, monitors);
+
+        main_changed || orb_changed
+    }
+
+    /// Update main window size
+    pub fn set_main_size(&mut self, width: u32, height: u32) {
+        self.main_window_size = Some(WindowSize { width, height });
+    }
+
+    /// Update orb window size
+    pub fn set_orb_size(&mut self, width: u32, height: u32) {
+        self.orb_window_size = Some(WindowSize { width, height });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitors() -> Vec<MonitorBounds> {
+        vec![
+            MonitorBounds {
+                x: 0,
+                y: 0,
+                width: 1920,
+                height: 1080,
+            },
+            MonitorBounds {
+                x: 1920,
+                y: 0,
+                width: 1920,
+                height: 1080,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_off_screen_window_is_reset() {
+        let mut state = WindowState::default();
+        state.set_main_position(5000, 200);
+        state.set_orb_position(-500, -500);
+
+        assert!(state.clamp_to_visible(&monitors()[..1]));
+
+        let main = state.main_window.unwrap();
+        let orb = state.orb_window.unwrap();
+        assert_eq!((main.x, main.y), (0, 0));
+        assert_eq!((orb.x, orb.y), (0, 0));
+    }
+
+    #[test]
+    fn test_partially_visible_window_is_kept() {
+        let mut state = WindowState::default();
+        state.set_main_position(1800, 100);
+        state.set_main_size(800, 600);
+        state.set_orb_position(-40, 500);
+
+        assert!(!state.clamp_to_visible(&monitors()[..1]));
+
+        let main = state.main_window.unwrap();
+        let orb = state.orb_window.unwrap();
+        assert_eq!((main.x, main.y), (1800, 100));
+        assert_eq!((orb.x, orb.y), (-40, 500));
+    }
+
+    #[test]
+    fn test_window_on_secondary_monitor_is_kept() {
+        let mut state = WindowState::default();
+        state.set_main_position(2500, 300);
+
+        assert!(!state.clamp_to_visible(&monitors()));
+        assert_eq!(state.main_window.unwrap().x, 2500);
+    }
+
+    #[test]
+    fn test_orb_always_on_top_defaults_to_true() {
+        let state: WindowState =
+            serde_json::from_str(r#"{ "main_window": null, "orb_window": null }"#).unwrap();
+        assert!(state.orb_always_on_top);
+        assert!(WindowState::default().orb_always_on_top);
+    }
 }
@@ -7,7 +7,7 @@
  * - Card content updates from AI streaming
  */
 
-import { writable, derived } from 'svelte/store';
+import { writable, derived, get } from 'svelte/store';
 import { invoke } from '@tauri-apps/api/core';
 import { listen } from '@tauri-apps/api/event';
 
@@ -20,6 +20,30 @@ export interface Card {
   content: string;
   created_at: number;
   updated_at: number;
+  word_count?: number;
+  char_count?: number;
+  pinned?: boolean;
+  color?: string | null;
+  locked?: boolean;
+  archived?: boolean;
+  tags?: string[];
+  /** SHA-256 of the markdown body, for change detection */
+  content_hash?: string;
+}
+
+/** Text the backend appended to a card while generating it */
+export interface AiCardStreamChunk {
+  card_id: string;
+  chunk: string;
+  done: boolean;
+}
+
+/** A background card write that failed; the backend keeps retrying it */
+export interface SaveFailure {
+  id: string;
+  error: string;
+  attempts: number;
+  retry_in_ms: number;
 }
 
 export type CardMode = 'view' | 'edit';
@@ -85,6 +109,36 @@ function createCardStore() {
     listen('refresh-required', () => {
       reloadCards();
     });
+
+    // Cards directory changed on disk (e.g. edited by Claude Desktop via MCP)
+    listen<Card[]>('cards-changed', (event) => {
+      update(s => ({ ...s, cards: event.payload }));
+    });
+
+    // A response streamed into a card by the backend (e.g. a new AI note); the
+    // backend saves it, this only keeps the shown content in step
+    listen<AiCardStreamChunk>('ai-card-stream-chunk', (event) => {
+      const { card_id, chunk } = event.payload;
+      if (chunk) {
+        update(s => ({
+          ...s,
+          cards: s.cards.map(card =>
+            card.id === card_id
+              ? { ...card, content: card.content + chunk, updated_at: Date.now() }
+              : card
+          )
+        }));
+      }
+    });
+
+    // An edit couldn't be written to disk yet; it stays in memory and is retried
+    listen<SaveFailure>('save-failed', (event) => {
+      const { error, retry_in_ms } = event.payload;
+      update(s => ({
+        ...s,
+        error: `Couldn't save card: ${error} (retrying in ${Math.ceil(retry_in_ms / 1000)} s)`
+      }));
+    });
   }
 
   return {
@@ -150,6 +204,16 @@ function createCardStore() {
       });
     },
 
+    /**
+     * Save a card to the backend without leaving edit mode
+     */
+    async saveCard(cardId: string) {
+      const card = get({ subscribe }).cards.find(c => c.id === cardId);
+      if (card) {
+        await invoke('save_card', { card });
+      }
+    },
+
     /**
      * Update card content (used during editing or AI streaming)
      */
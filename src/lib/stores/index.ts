@@ -1,3 +1,4 @@
 // Store exports
 export * from './cardStore';
 export * from './settingsStore';
+export * from './toolCallStore';
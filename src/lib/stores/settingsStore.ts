@@ -10,6 +10,7 @@
 import { writable, derived } from 'svelte/store';
 import { invoke } from '@tauri-apps/api/core';
 import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { getCurrentWindow } from '@tauri-apps/api/window';
 
 // ============================================================================
 // Types
@@ -27,6 +28,28 @@ export interface AiStreamChunk {
   gpu_info?: string;
 }
 
+/** How a streamed response applies to the card: inserted, or replacing the body */
+export type StreamMode = 'append' | 'replace_content';
+
+/** A local model that `preload_local_model` couldn't load */
+export interface LocalModelLoadError {
+  provider: string;
+  error: string;
+}
+
+/** Payload of 'ai-stream-start', sent before the first chunk of a response */
+export interface AiStreamStart {
+  mode: StreamMode;
+  /** Set for responses the backend writes into a card itself */
+  card_id?: string;
+}
+
+/** Add the notes most relevant to the context, leaving out the card itself */
+export interface RelatedNotesOptions {
+  card_id?: string;
+  limit?: number;
+}
+
 interface SettingsState {
   providers: Provider[];
   activeProviderId: string | null;
@@ -34,6 +57,10 @@ interface SettingsState {
   isStreaming: boolean;
   error: string | null;
   currentGpuInfo: string | null;
+  /** The last response stopped at the token limit and can be continued */
+  truncated: boolean;
+  /** Reasoning text of the current response, from models that stream it separately */
+  reasoning: string;
 }
 
 // ============================================================================
@@ -47,10 +74,105 @@ function createSettingsStore() {
     isLoading: false,
     isStreaming: false,
     error: null,
-    currentGpuInfo: null
+    currentGpuInfo: null,
+    truncated: false,
+    reasoning: ''
   });
 
   let streamUnlisten: UnlistenFn | null = null;
+  let truncatedUnlisten: UnlistenFn | null = null;
+  let reasoningUnlisten: UnlistenFn | null = null;
+  let startUnlisten: UnlistenFn | null = null;
+
+  function stopListening() {
+    if (streamUnlisten) {
+      streamUnlisten();
+      streamUnlisten = null;
+    }
+    if (truncatedUnlisten) {
+      truncatedUnlisten();
+      truncatedUnlisten = null;
+    }
+    if (reasoningUnlisten) {
+      reasoningUnlisten();
+      reasoningUnlisten = null;
+    }
+    if (startUnlisten) {
+      startUnlisten();
+      startUnlisten = null;
+    }
+  }
+
+  /**
+   * Listen for this window's stream events, then start the stream with `start`
+   * `onStart` gets the mode from 'ai-stream-start' before the first chunk.
+   */
+  async function runStream(
+    start: (targetWindow: string) => Promise<unknown>,
+    onChunk: (chunk: string, mode: StreamMode) => void,
+    onDone: () => void,
+    onError: (error: string) => void,
+    mode: StreamMode,
+    onStart?: (mode: StreamMode) => void
+  ) {
+    // Clean up previous listeners
+    stopListening();
+
+    update(s => ({ ...s, isStreaming: true, error: null, currentGpuInfo: null, truncated: false, reasoning: '' }));
+
+    // Stream only to this window so the other window doesn't receive our chunks
+    const currentWindow = getCurrentWindow();
+
+    try {
+      truncatedUnlisten = await currentWindow.listen('ai-stream-truncated', () => {
+        update(s => ({ ...s, truncated: true }));
+      });
+
+      // Responses written into a card by the backend go to every window; skip those
+      startUnlisten = await currentWindow.listen<AiStreamStart>('ai-stream-start', (event) => {
+        if (event.payload.card_id) return;
+        mode = event.payload.mode;
+        onStart?.(mode);
+      });
+
+      reasoningUnlisten = await currentWindow.listen<{ chunk: string }>('ai-reasoning-chunk', (event) => {
+        update(s => ({ ...s, reasoning: s.reasoning + event.payload.chunk }));
+      });
+
+      // Set up event listener for streaming chunks
+      streamUnlisten = await currentWindow.listen<AiStreamChunk>('ai-stream-chunk', (event) => {
+        if (event.payload.gpu_info) {
+          update(s => ({ ...s, currentGpuInfo: event.payload.gpu_info || null }));
+        }
+
+        if (event.payload.done) {
+          update(s => ({ ...s, isStreaming: false }));
+          onDone();
+          stopListening();
+        } else {
+          onChunk(event.payload.chunk, mode);
+        }
+      });
+
+      // Start the stream
+      await start(currentWindow.label);
+    } catch (error) {
+      update(s => ({
+        ...s,
+        isStreaming: false,
+        error: error instanceof Error ? error.message : String(error)
+      }));
+      onError(error instanceof Error ? error.message : String(error));
+      stopListening();
+    }
+  }
+
+  // A background model preload failed; otherwise the first prompt would be the first sign
+  if (typeof window !== 'undefined') {
+    listen<LocalModelLoadError>('local-model-error', (event) => {
+      update(s => ({ ...s, error: `Couldn't load the local model: ${event.payload.error}` }));
+    });
+  }
 
   return {
     subscribe,
@@ -137,53 +259,43 @@ function createSettingsStore() {
     async invokeAiStream(
       prompt: string,
       context: string,
-      onChunk: (chunk: string) => void,
+      onChunk: (chunk: string, mode: StreamMode) => void,
       onDone: () => void,
-      onError: (error: string) => void
+      onError: (error: string) => void,
+      mode: StreamMode = 'replace_content',
+      related?: RelatedNotesOptions,
+      onStart?: (mode: StreamMode) => void
     ) {
-      // Clean up previous listener
-      if (streamUnlisten) {
-        streamUnlisten();
-        streamUnlisten = null;
-      }
-
-      update(s => ({ ...s, isStreaming: true, error: null, currentGpuInfo: null }));
-
-      try {
-        // Set up event listener for streaming chunks
-        streamUnlisten = await listen<AiStreamChunk>('ai-stream-chunk', (event) => {
-          if (event.payload.gpu_info) {
-            update(s => ({ ...s, currentGpuInfo: event.payload.gpu_info || null }));
-          }
-
-          if (event.payload.done) {
-            update(s => ({ ...s, isStreaming: false }));
-            onDone();
-
-            if (streamUnlisten) {
-              streamUnlisten();
-              streamUnlisten = null;
-            }
-          } else {
-            onChunk(event.payload.chunk);
-          }
-        });
-
-        // Start the stream
-        await invoke('invoke_ai_stream', { prompt, context });
-      } catch (error) {
-        update(s => ({
-          ...s,
-          isStreaming: false,
-          error: error instanceof Error ? error.message : String(error)
-        }));
-        onError(error instanceof Error ? error.message : String(error));
+      await runStream(
+        targetWindow => invoke('invoke_ai_stream', { prompt, context, mode, targetWindow, related }),
+        onChunk,
+        onDone,
+        onError,
+        mode,
+        onStart
+      );
+    },
 
-        if (streamUnlisten) {
-          streamUnlisten();
-          streamUnlisten = null;
-        }
-      }
+    /**
+     * Continue a response that stopped at the token limit
+     * The continuation is appended to the card; save the card first so the
+     * backend sees the partial output.
+     */
+    async continueGeneration(
+      cardId: string,
+      onChunk: (chunk: string, mode: StreamMode) => void,
+      onDone: () => void,
+      onError: (error: string) => void,
+      onStart?: (mode: StreamMode) => void
+    ) {
+      await runStream(
+        targetWindow => invoke('continue_generation', { cardId, targetWindow }),
+        onChunk,
+        onDone,
+        onError,
+        'append',
+        onStart
+      );
     },
 
     /**
@@ -0,0 +1,58 @@
+/**
+ * Tool Call Store - AI tool calls waiting for the user's confirmation
+ *
+ * With "require tool confirmation" on, the backend emits 'tool-call-pending'
+ * before a tool changes notes and waits (up to 5 minutes) for
+ * confirm_tool_call or reject_tool_call.
+ */
+
+import { writable } from 'svelte/store';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+
+// ============================================================================
+// Types
+// ============================================================================
+
+export interface ToolCallPending {
+  call_id: string;
+  name: string;
+  arguments: string;
+  /** What the call would change, e.g. 'Would delete note … titled "…"' */
+  preview: string;
+}
+
+// ============================================================================
+// Store Implementation
+// ============================================================================
+
+function createToolCallStore() {
+  const { subscribe, update } = writable<ToolCallPending[]>([]);
+
+  if (typeof window !== 'undefined') {
+    listen<ToolCallPending>('tool-call-pending', (event) => {
+      update(calls => [...calls, event.payload]);
+    });
+  }
+
+  /**
+   * Answer a pending call; it's dropped even if the backend stopped waiting
+   */
+  async function resolve(callId: string, approved: boolean) {
+    update(calls => calls.filter(c => c.call_id !== callId));
+    try {
+      await invoke(approved ? 'confirm_tool_call' : 'reject_tool_call', { callId });
+    } catch (error) {
+      console.error('Failed to resolve tool call:', error);
+    }
+  }
+
+  return {
+    subscribe,
+    confirm: (callId: string) => resolve(callId, true),
+    reject: (callId: string) => resolve(callId, false)
+  };
+}
+
+// Export singleton store instance
+export const toolCallStore = createToolCallStore();